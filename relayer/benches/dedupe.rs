@@ -0,0 +1,100 @@
+//! Dedupe throughput: events/sec through `DeduplicationEngine::is_duplicate` with
+//! several concurrent callers, the way the router sees one event from many relays.
+//!
+//! Run with `cargo bench --bench dedupe`.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use moltrade_relayer::core::dedupe_engine::DeduplicationEngine;
+use moltrade_relayer::storage::rocksdb_store::RocksDBStore;
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Distinct events per measured batch
+const EVENTS: usize = 1_000;
+/// Copies of each event, as if delivered by this many relays
+const RELAYS: usize = 4;
+const CONCURRENCY: [usize; 4] = [1, 4, 16, 64];
+
+/// Events with random ids and a borrowed signature: dedupe never verifies them,
+/// and signing would dominate the measurement
+fn batch(template: &Event) -> Vec<Event> {
+    let mut deliveries = Vec::with_capacity(EVENTS * RELAYS);
+    for _ in 0..EVENTS {
+        let event = Event::new(
+            EventId::from_byte_array(rand::random()),
+            template.pubkey,
+            Timestamp::now(),
+            template.kind,
+            template.tags.clone(),
+            template.content.clone(),
+            template.sig,
+        );
+        // Copies are adjacent, so they land on different callers at the same time
+        deliveries.extend(std::iter::repeat_n(event, RELAYS));
+    }
+    deliveries
+}
+
+async fn run(engine: Arc<DeduplicationEngine>, deliveries: Vec<Event>, concurrency: usize) {
+    let deliveries = Arc::new(deliveries);
+    let tasks: Vec<_> = (0..concurrency)
+        .map(|worker| {
+            let engine = engine.clone();
+            let deliveries = deliveries.clone();
+            tokio::spawn(async move {
+                for event in deliveries.iter().skip(worker).step_by(concurrency) {
+                    std::hint::black_box(engine.is_duplicate(event).await);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.expect("dedupe worker panicked");
+    }
+}
+
+fn dedupe_throughput(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    let dir = tempfile::tempdir().expect("tempdir");
+    let rocksdb = Arc::new(RocksDBStore::new(dir.path()).expect("open RocksDB"));
+    let engine = Arc::new(DeduplicationEngine::new_with_params(
+        rocksdb, 100_000, 1_000_000, 100_000,
+    ));
+    let template = EventBuilder::new(Kind::Custom(30931), "benchmark")
+        .sign_with_keys(&Keys::generate())
+        .expect("sign template");
+
+    let mut group = c.benchmark_group("is_duplicate");
+    group.throughput(Throughput::Elements((EVENTS * RELAYS) as u64));
+    group.sample_size(20);
+    for concurrency in CONCURRENCY {
+        group.bench_with_input(
+            BenchmarkId::new("concurrency", concurrency),
+            &concurrency,
+            |b, &concurrency| {
+                b.to_async(&runtime).iter_custom(|iters| {
+                    let engine = engine.clone();
+                    let template = template.clone();
+                    async move {
+                        let mut elapsed = Duration::ZERO;
+                        for _ in 0..iters {
+                            let deliveries = batch(&template);
+                            let start = Instant::now();
+                            run(engine.clone(), deliveries, concurrency).await;
+                            elapsed += start.elapsed();
+                        }
+                        elapsed
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dedupe_throughput);
+criterion_main!(benches);
@@ -0,0 +1,239 @@
+//! Memory and CPU cost per relay of the two relay pool designs:
+//! one `Client` (and notification task) per relay vs relays multiplexed over shard clients.
+//!
+//! Starts an in-process mock relay serving `--relays` distinct URLs, connects to all of
+//! them and streams events for `--duration` seconds. Run each mode in its own process:
+//!
+//!   cargo run --release --example pool_bench -- --mode per-relay --relays 500
+//!   cargo run --release --example pool_bench -- --mode shared --relays 500 --shards 1
+
+use axum::{
+    Router,
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    response::IntoResponse,
+    routing::get,
+};
+use clap::{Parser, ValueEnum};
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use sysinfo::{ProcessesToUpdate, System};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Mode {
+    /// One client and notification task per relay (previous design)
+    PerRelay,
+    /// Relays multiplexed over `--shards` clients
+    Shared,
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(long, value_enum, default_value = "shared")]
+    mode: Mode,
+    #[arg(long, default_value_t = 200)]
+    relays: usize,
+    #[arg(long, default_value_t = 1)]
+    shards: usize,
+    /// Events each mock relay pushes per second
+    #[arg(long, default_value_t = 1)]
+    events_per_sec: u64,
+    /// Measurement window in seconds
+    #[arg(long, default_value_t = 20)]
+    duration: u64,
+}
+
+/// Pre-signed events the mock relays cycle through, so signing does not skew the CPU numbers
+struct MockRelay {
+    events: Vec<String>,
+    interval: Duration,
+}
+
+async fn relay_ws(
+    ws: WebSocketUpgrade,
+    axum::extract::State(mock): axum::extract::State<Arc<MockRelay>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| serve_relay(socket, mock))
+}
+
+async fn serve_relay(mut socket: WebSocket, mock: Arc<MockRelay>) {
+    let mut subscription: Option<String> = None;
+    let mut ticker = tokio::time::interval(mock.interval);
+    let mut next = 0usize;
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    // Pings are answered by axum
+                    Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Binary(_))) => continue,
+                    _ => break,
+                };
+                let Ok(serde_json::Value::Array(req)) = serde_json::from_str(&text) else { continue };
+                match (req.first().and_then(|v| v.as_str()), req.get(1).and_then(|v| v.as_str())) {
+                    (Some("REQ"), Some(id)) => {
+                        let eose = format!(r#"["EOSE","{}"]"#, id);
+                        if socket.send(Message::Text(eose.into())).await.is_err() {
+                            break;
+                        }
+                        // Liveness probes (`limit: 0`) do not replace the stream subscription
+                        if subscription.is_none() {
+                            subscription = Some(id.to_string());
+                        }
+                    }
+                    (Some("CLOSE"), Some(id)) if subscription.as_deref() == Some(id) => {
+                        subscription = None;
+                    }
+                    _ => {}
+                }
+            }
+            _ = ticker.tick() => {
+                let Some(id) = &subscription else { continue };
+                let event = &mock.events[next % mock.events.len()];
+                next += 1;
+                let msg = format!(r#"["EVENT","{}",{}]"#, id, event);
+                if socket.send(Message::Text(msg.into())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Resident memory (bytes) and accumulated CPU time (ms) of this process
+fn usage(sys: &mut System) -> (u64, u64) {
+    sys.refresh_processes(ProcessesToUpdate::All, true);
+    let pid = sysinfo::get_current_pid().expect("current pid");
+    let process = sys.process(pid).expect("current process");
+    (process.memory(), process.accumulated_cpu_time())
+}
+
+async fn count_events(client: Arc<Client>, received: Arc<AtomicU64>) {
+    let mut notifications = client.notifications();
+    loop {
+        match notifications.recv().await {
+            Ok(RelayPoolNotification::Message {
+                message: RelayMessage::Event { .. },
+                ..
+            }) => {
+                received.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(RelayPoolNotification::Shutdown) => break,
+            Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+            Err(_) => break,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let keys = Keys::generate();
+    let mut events = Vec::with_capacity(1000);
+    for i in 0..1000 {
+        let event = EventBuilder::new(Kind::Custom(30931), format!("bench signal {}", i))
+            .sign_with_keys(&keys)?;
+        events.push(event.as_json());
+    }
+    let mock = Arc::new(MockRelay {
+        events,
+        interval: Duration::from_millis(1000 / args.events_per_sec.max(1)),
+    });
+
+    let app = Router::new().route("/{id}", get(relay_ws)).with_state(mock);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut sys = System::new();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let (rss_before, _) = usage(&mut sys);
+
+    let filter = Filter::new().kind(Kind::Custom(30931));
+    let received = Arc::new(AtomicU64::new(0));
+    let urls: Vec<String> = (0..args.relays)
+        .map(|i| format!("ws://{}/{}", addr, i))
+        .collect();
+
+    let clients: Vec<Arc<Client>> = match args.mode {
+        Mode::PerRelay => (0..args.relays)
+            .map(|_| Arc::new(Client::new(Keys::generate())))
+            .collect(),
+        Mode::Shared => (0..args.shards.max(1))
+            .map(|_| {
+                let opts = ClientOptions::new()
+                    .pool(RelayPoolOptions::new().notification_channel_size(65_536));
+                Arc::new(
+                    Client::builder()
+                        .signer(Keys::generate())
+                        .opts(opts)
+                        .build(),
+                )
+            })
+            .collect(),
+    };
+    for client in &clients {
+        tokio::spawn(count_events(client.clone(), received.clone()));
+    }
+
+    let connect_started = Instant::now();
+    for (i, url) in urls.iter().enumerate() {
+        let client = &clients[i % clients.len()];
+        client.add_relay(url).await?;
+        client.connect_relay(url).await?;
+    }
+    for (i, url) in urls.iter().enumerate() {
+        let client = &clients[i % clients.len()];
+        let relay = client.relay(url).await?;
+        relay.wait_for_connection(Duration::from_secs(10)).await;
+        client
+            .subscribe_to([url.as_str()], filter.clone(), None)
+            .await?;
+    }
+    let connect_time = connect_started.elapsed();
+
+    // Let connections settle before measuring steady state
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let (rss_connected, cpu_start) = usage(&mut sys);
+    let received_start = received.load(Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_secs(args.duration)).await;
+    let (rss_end, cpu_end) = usage(&mut sys);
+    let received_total = received.load(Ordering::Relaxed) - received_start;
+
+    let relays = args.relays as f64;
+    let cpu_ms = cpu_end.saturating_sub(cpu_start) as f64;
+    println!("mode                 {:?}", args.mode);
+    println!("relays               {}", args.relays);
+    println!("clients              {}", clients.len());
+    println!("notification tasks   {}", clients.len());
+    println!("connect time         {:.2}s", connect_time.as_secs_f64());
+    println!(
+        "rss                  {:.1} MiB -> {:.1} MiB (steady {:.1} MiB)",
+        rss_before as f64 / 1048576.0,
+        rss_connected as f64 / 1048576.0,
+        rss_end as f64 / 1048576.0
+    );
+    println!(
+        "rss per relay        {:.1} KiB",
+        rss_end.saturating_sub(rss_before) as f64 / 1024.0 / relays
+    );
+    println!(
+        "events received      {} ({:.0}/s)",
+        received_total,
+        received_total as f64 / args.duration as f64
+    );
+    println!(
+        "cpu                  {:.1}% of one core",
+        cpu_ms / (args.duration as f64 * 10.0)
+    );
+    println!(
+        "cpu per relay        {:.3} ms/s",
+        cpu_ms / args.duration as f64 / relays
+    );
+    Ok(())
+}
@@ -1,15 +1,43 @@
 use prometheus::{
-    Gauge, Histogram, IntCounter, register_gauge, register_histogram, register_int_counter,
+    CounterVec, Gauge, GaugeVec, Histogram, IntCounter, IntCounterVec, register_counter_vec,
+    register_gauge, register_gauge_vec, register_histogram, register_int_counter,
+    register_int_counter_vec,
 };
 
 /// Metrics for monitoring the relay system
 pub struct Metrics {
     pub events_processed: IntCounter,
     pub duplicates_filtered: IntCounter,
+    pub events_replayed: IntCounter,
+    pub addressable_superseded: IntCounterVec,
+    pub addressable_shared: IntCounterVec,
+    pub semantic_duplicates: IntCounter,
+    pub ingress_rejected: IntCounterVec,
+    pub hot_set_evictions: IntCounter,
+    pub retention_pruned_events: IntCounter,
+    pub retention_reclaimed_bytes: IntCounter,
+    pub bloom_fill_ratio: Gauge,
+    pub bloom_estimated_fpr: Gauge,
+    pub bloom_rotations: IntCounter,
     pub processing_latency: Histogram,
     pub memory_usage: Gauge,
     pub active_connections: Gauge,
     pub events_in_queue: Gauge,
+    pub relay_events_received: IntCounterVec,
+    pub relay_unique_events: IntCounterVec,
+    pub relay_duplicate_events: IntCounterVec,
+    pub relay_reconnects: IntCounterVec,
+    pub relay_messages: IntCounterVec,
+    pub relay_last_event_timestamp: GaugeVec,
+    pub relay_score: GaugeVec,
+    pub relays_pruned: IntCounter,
+    pub relays_promoted: IntCounter,
+    pub relays_discovered: IntCounter,
+    pub subscribed_authors: Gauge,
+    pub pipeline_dropped: IntCounterVec,
+    pub pipeline_blocked: IntCounterVec,
+    pub pipeline_blocked_seconds: CounterVec,
+    pub pipeline_queue_depth: GaugeVec,
 }
 
 impl Metrics {
@@ -24,6 +52,53 @@ impl Metrics {
                 "duplicates_filtered_total",
                 "Total duplicates filtered"
             )?,
+            events_replayed: register_int_counter!(
+                "events_replayed_total",
+                "Unfinished events re-delivered on startup"
+            )?,
+            addressable_superseded: register_int_counter_vec!(
+                "addressable_superseded_total",
+                "Latest-wins addressable events dropped because a newer one was seen",
+                &["kind"]
+            )?,
+            addressable_shared: register_int_counter_vec!(
+                "addressable_shared_coordinate_total",
+                "Events reusing the addressable coordinate of an earlier event of the same kind",
+                &["kind"]
+            )?,
+            ingress_rejected: register_int_counter_vec!(
+                "ingress_rejected_total",
+                "Events refused before deduplication, by reason",
+                &["reason"]
+            )?,
+            semantic_duplicates: register_int_counter!(
+                "semantic_duplicates_total",
+                "Trade signals dropped as republished copies of one seen within the semantic dedupe window"
+            )?,
+            hot_set_evictions: register_int_counter!(
+                "dedupe_hot_set_evictions_total",
+                "Event IDs evicted from the dedupe hot set, oldest first"
+            )?,
+            retention_pruned_events: register_int_counter!(
+                "retention_pruned_events_total",
+                "Archived events deleted after their kind's retention ended"
+            )?,
+            retention_reclaimed_bytes: register_int_counter!(
+                "retention_reclaimed_bytes_total",
+                "Key and value bytes deleted from RocksDB by the retention pruner"
+            )?,
+            bloom_fill_ratio: register_gauge!(
+                "dedupe_bloom_fill_ratio",
+                "Fraction of bits set in the newest Bloom filter generation"
+            )?,
+            bloom_estimated_fpr: register_gauge!(
+                "dedupe_bloom_estimated_fpr",
+                "Estimated false positive rate across live Bloom filter generations"
+            )?,
+            bloom_rotations: register_int_counter!(
+                "dedupe_bloom_rotations_total",
+                "Bloom filter generations started"
+            )?,
             processing_latency: register_histogram!(
                 "processing_latency_seconds",
                 "Event processing latency in seconds"
@@ -37,6 +112,77 @@ impl Metrics {
                 "events_in_queue",
                 "Number of events waiting in queue"
             )?,
+            relay_events_received: register_int_counter_vec!(
+                "relay_events_received_total",
+                "Events received per relay",
+                &["relay"]
+            )?,
+            relay_unique_events: register_int_counter_vec!(
+                "relay_unique_events_total",
+                "Events a relay delivered before any other relay",
+                &["relay"]
+            )?,
+            relay_duplicate_events: register_int_counter_vec!(
+                "relay_duplicate_events_total",
+                "Events a relay delivered after another relay already had",
+                &["relay"]
+            )?,
+            relay_reconnects: register_int_counter_vec!(
+                "relay_reconnects_total",
+                "Successful reconnects per relay",
+                &["relay"]
+            )?,
+            relay_messages: register_int_counter_vec!(
+                "relay_messages_total",
+                "NOTICE, CLOSED and AUTH messages per relay",
+                &["relay", "type"]
+            )?,
+            relay_last_event_timestamp: register_gauge_vec!(
+                "relay_last_event_timestamp_seconds",
+                "Unix time of the last event received per relay",
+                &["relay"]
+            )?,
+            relay_score: register_gauge_vec!(
+                "relay_score",
+                "Relay usefulness score (0-1) from uniqueness, latency and uptime",
+                &["relay"]
+            )?,
+            relays_pruned: register_int_counter!(
+                "relays_pruned_total",
+                "Relays disconnected by the scorer for low scores"
+            )?,
+            relays_promoted: register_int_counter!(
+                "relays_promoted_total",
+                "Standby relays connected by the scorer"
+            )?,
+            relays_discovered: register_int_counter!(
+                "relays_discovered_total",
+                "Relays subscribed from registered bots' NIP-65 relay lists"
+            )?,
+            subscribed_authors: register_gauge!(
+                "relay_subscribed_authors",
+                "Pubkeys in the author-scoped relay subscription"
+            )?,
+            pipeline_dropped: register_int_counter_vec!(
+                "pipeline_dropped_events_total",
+                "Items dropped by a full pipeline stage",
+                &["stage", "reason"]
+            )?,
+            pipeline_blocked: register_int_counter_vec!(
+                "pipeline_blocked_sends_total",
+                "Sends that waited on a full pipeline stage",
+                &["stage"]
+            )?,
+            pipeline_blocked_seconds: register_counter_vec!(
+                "pipeline_blocked_seconds_total",
+                "Time spent waiting on full pipeline stages",
+                &["stage"]
+            )?,
+            pipeline_queue_depth: register_gauge_vec!(
+                "pipeline_queue_depth",
+                "Items queued in a pipeline stage",
+                &["stage"]
+            )?,
         })
     }
 }
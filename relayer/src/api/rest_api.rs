@@ -1,11 +1,13 @@
 use axum::{
     Router,
+    body::Body,
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Json},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
     routing::{delete, get, post},
 };
 use chrono::{Datelike, Utc};
+use futures::TryStreamExt;
 use prometheus::{Encoder, TextEncoder};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -13,8 +15,10 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio_postgres::error::SqlState;
+use tokio_util::io::{ReaderStream, StreamReader};
 
 use crate::api::metrics::Metrics;
+use crate::core::archive;
 use crate::core::dedupe_engine::DeduplicationEngine;
 use crate::core::relay_pool::RelayPool;
 use crate::core::subscription::{
@@ -22,8 +26,12 @@ use crate::core::subscription::{
     StrategyPerformanceInterval, StrategyPerformanceMetric, StrategyPerformanceSeries,
     StrategyRankBy, StrategyTrendingItem, SubscriptionService,
 };
+use crate::storage::event_index::{EventCursor, EventQuery};
+use crate::storage::rocksdb_store::RocksDBStore;
 
 const SKILL_MD_CONTENT: &str = include_str!("../../../skills/moltrade/SKILL.md");
+/// Bytes buffered between the export task and the response body
+const EXPORT_CHUNK: usize = 64 * 1024;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -98,6 +106,7 @@ pub fn create_router(
         .route("/api/relays/add", post(add_relay))
         .route("/api/relays/remove", delete(remove_relay))
         .route("/api/bots/register", post(register_bot))
+        .route("/api/bots/{bot_pubkey}", delete(remove_bot))
         .route("/api/subscriptions", post(add_subscription))
         .route("/api/subscriptions/{bot_pubkey}", get(list_subscriptions))
         .route(
@@ -116,6 +125,10 @@ pub fn create_router(
         )
         .route("/api/leaderboard", get(leaderboard))
         .route("/api/agents/{id}", get(agent_detail))
+        .route("/api/events", get(list_events))
+        .route("/api/admin/checkpoint", post(create_checkpoint))
+        .route("/api/admin/export", get(export_events))
+        .route("/api/admin/import", post(import_events))
         .with_state(state)
 }
 
@@ -150,24 +163,41 @@ async fn prometheus_metrics() -> Result<String, StatusCode> {
 
 /// Get connection status
 async fn status(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let statuses = state.pool.get_connection_statuses().await;
+    let relay_stats = state.pool.get_relay_stats().await;
     let active = state.pool.active_connections();
     let deque_status = state.dedupe.get_stats().await;
 
     Json(json!({
         "active_connections": active,
-        "connections": statuses.iter().map(|(url, status)| {
+        "connections": relay_stats.iter().map(|(url, status, stats)| {
             json!({
                 "url": url,
-                "status": format!("{:?}", status)
+                "status": format!("{:?}", status),
+                "events_received": stats.events_received,
+                "unique_events": stats.unique_events,
+                "duplicate_events": stats.duplicate_events,
+                "last_event_at": stats.last_event_at,
+                "backfill_completed_at": stats.backfill_completed_at,
+                "auth": stats.auth,
+                "last_notice": stats.last_notice,
             })
         }).collect::<Vec<_>>(),
         "relayer_nostr_pubkey": state.platform_pubkey,
         "deduplication_engine": {
             "bloom_filter_size": deque_status.bloom_filter_size,
+            "bloom_generations": deque_status.bloom_generations,
+            "bloom_fill_ratio": deque_status.bloom_fill_ratio,
+            "bloom_estimated_fpr": deque_status.bloom_estimated_fpr,
+            "bloom_rotations": deque_status.bloom_rotations,
             "lru_cache_size": deque_status.lru_cache_size,
             "rocksdb_entry_count": deque_status.rocksdb_approximate_count,
             "hot_set_size": deque_status.hot_set_size,
+            "hot_set_capacity": deque_status.hot_set_capacity,
+            "hot_set_evictions": deque_status.hot_set_evictions,
+            "latest_coordinates": deque_status.latest_coordinates,
+            "seen_coordinates": deque_status.seen_coordinates,
+            "shared_coordinates": deque_status.shared_coordinates,
+            "coordinate_capacity": deque_status.coordinate_capacity,
         }
     }))
 }
@@ -176,6 +206,9 @@ async fn status(State(state): State<AppState>) -> Json<serde_json::Value> {
 #[derive(Debug, Deserialize)]
 struct AddRelayRequest {
     url: String,
+    /// Recorded with the persisted relay (defaults to `api`)
+    #[serde(default)]
+    added_by: Option<String>,
 }
 
 /// Request body for removing a relay
@@ -201,7 +234,8 @@ async fn add_relay(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    match state.pool.connect_and_subscribe(payload.url.clone()).await {
+    let added_by = payload.added_by.as_deref().unwrap_or("api");
+    match state.pool.add_relay(payload.url.clone(), added_by).await {
         Ok(_) => Ok(Json(RelayResponse {
             success: true,
             message: format!("Successfully connected to relay: {}", payload.url),
@@ -223,7 +257,7 @@ async fn remove_relay(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    match state.pool.disconnect_relay(&payload.url).await {
+    match state.pool.remove_relay(&payload.url).await {
         Ok(_) => Ok(Json(RelayResponse {
             success: true,
             message: format!("Successfully disconnected relay: {}", payload.url),
@@ -237,14 +271,18 @@ async fn remove_relay(
 
 /// List all relays
 async fn list_relays(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let _relay_urls = state.pool.list_relays();
-    let statuses = state.pool.get_connection_statuses().await;
+    let relay_stats = state.pool.get_relay_stats().await;
+    let records = state.pool.relay_records().await;
 
     let mut relay_info = Vec::new();
-    for (url, status) in statuses {
+    for (url, status, stats) in relay_stats {
+        let record = records.get(&url);
         relay_info.push(json!({
             "url": url,
-            "status": format!("{:?}", status)
+            "status": format!("{:?}", status),
+            "added_by": record.map(|r| r.added_by.as_str()),
+            "added_at": record.map(|r| r.added_at),
+            "stats": stats,
         }));
     }
 
@@ -344,6 +382,34 @@ fn default_leaderboard_limit() -> i64 {
     50
 }
 
+/// NIP-01-like archive filter; list values are comma-separated
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    /// Hex or npub pubkeys
+    authors: Option<String>,
+    kinds: Option<String>,
+    since: Option<u64>,
+    until: Option<u64>,
+    #[serde(default = "default_events_limit")]
+    limit: usize,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
+    symbol: Option<String>,
+    strategy: Option<String>,
+    sid: Option<String>,
+}
+
+fn default_events_limit() -> usize {
+    100
+}
+
+#[derive(Debug, Serialize)]
+struct EventsResponse {
+    events: Vec<nostr_sdk::Event>,
+    /// Pass as `cursor` for the next page; absent on the last page
+    next_cursor: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 struct LeaderboardResponse {
     data: Vec<LeaderboardItem>,
@@ -418,6 +484,10 @@ async fn register_bot(
         tracing::error!("Failed to register bot: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    state.pool.discover_author_relays(&payload.nostr_pubkey);
+    if let Err(e) = state.pool.sync_authors(svc).await {
+        tracing::warn!("Failed to update relay author filter: {}", e);
+    }
 
     Ok(Json(RegisterBotResponse {
         success: true,
@@ -426,6 +496,38 @@ async fn register_bot(
     }))
 }
 
+/// Remove a bot and its subscriptions
+async fn remove_bot(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(bot_pubkey): Path<String>,
+) -> Result<Json<RelayResponse>, StatusCode> {
+    if !is_token_valid(&headers, state.settlement_token.as_deref()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let svc = match &state.subscriptions {
+        Some(s) => s,
+        None => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+
+    let removed = svc.remove_bot(&bot_pubkey).await.map_err(|e| {
+        tracing::error!("Failed to remove bot {}: {}", bot_pubkey, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Err(e) = state.pool.sync_authors(svc).await {
+        tracing::warn!("Failed to update relay author filter: {}", e);
+    }
+
+    Ok(Json(RelayResponse {
+        success: true,
+        message: format!("bot removed: {}", bot_pubkey),
+    }))
+}
+
 /// Add or update a subscription
 async fn add_subscription(
     State(state): State<AppState>,
@@ -457,6 +559,9 @@ async fn add_subscription(
         tracing::error!("Failed to add subscription: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    if let Err(e) = state.pool.sync_authors(svc).await {
+        tracing::warn!("Failed to update relay author filter: {}", e);
+    }
 
     Ok(Json(RelayResponse {
         success: true,
@@ -839,6 +944,16 @@ async fn agent_detail(
     Ok(Json(detail))
 }
 
+/// Admin endpoints require a configured token, unlike the settlement ones
+fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    if state.settlement_token.is_none()
+        || !is_token_valid(headers, state.settlement_token.as_deref())
+    {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(())
+}
+
 fn is_token_valid(headers: &HeaderMap, expected: Option<&str>) -> bool {
     match expected {
         None => true, // no token configured -> allow
@@ -959,3 +1074,165 @@ async fn memory(State(state): State<AppState>) -> Json<serde_json::Value> {
         "memory_usage_mb": memory_usage_mb,
     }))
 }
+
+/// Write a RocksDB checkpoint to the backup directory while the relayer keeps running
+/// Unlike other admin routes, refused when no settlement token is configured
+async fn create_checkpoint(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    authorize_admin(&state, &headers)?;
+
+    let path = rocksdb(&state)?.create_checkpoint().await.map_err(|e| {
+        tracing::error!("Failed to create checkpoint: {:#}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    tracing::info!("Checkpoint written to {}", path.display());
+    Ok(Json(json!({
+        "success": true,
+        "path": path,
+    })))
+}
+
+/// Stream the archived events matching the `/api/events` filters (without
+/// `limit`) as NIP-01 JSONL
+async fn export_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<EventsQuery>,
+) -> Result<Response, StatusCode> {
+    authorize_admin(&state, &headers)?;
+    let filter = parse_events_query(q).ok_or(StatusCode::BAD_REQUEST)?;
+
+    let (writer, reader) = tokio::io::duplex(EXPORT_CHUNK);
+    let store = rocksdb(&state)?.clone();
+    tokio::spawn(async move {
+        match archive::export_jsonl(store, filter, writer).await {
+            Ok(written) => tracing::info!("Exported {} events", written),
+            Err(e) => tracing::error!("Event export failed: {:#}", e),
+        }
+    });
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response())
+}
+
+/// Archive the NIP-01 JSONL events of the request body, verifying signatures
+/// and skipping stored ones; imported events are not forwarded
+async fn import_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    authorize_admin(&state, &headers)?;
+
+    let reader = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+    let stats = archive::import_jsonl(&state.dedupe, reader)
+        .await
+        .map_err(|e| {
+            tracing::error!("Event import failed: {:#}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    tracing::info!(
+        "Imported {} events ({} already stored, {} invalid)",
+        stats.imported,
+        stats.duplicates,
+        stats.invalid
+    );
+    Ok(Json(json!({
+        "success": true,
+        "imported": stats.imported,
+        "duplicates": stats.duplicates,
+        "invalid": stats.invalid,
+    })))
+}
+
+/// Archived events matching the filter, newest first
+/// At least one of `authors`, `kinds`, `symbol`, `strategy` or `sid` is required.
+async fn list_events(
+    State(state): State<AppState>,
+    Query(q): Query<EventsQuery>,
+) -> Result<Json<EventsResponse>, StatusCode> {
+    let query = parse_events_query(q).ok_or(StatusCode::BAD_REQUEST)?;
+    if !query.is_indexed() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let limit = query.limit;
+    let events = rocksdb(&state)?.query_events(query).await.map_err(|e| {
+        tracing::error!("Failed to query events: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let next_cursor = match events.last() {
+        Some(last) if events.len() == limit => Some(EventCursor::of(last).to_string()),
+        _ => None,
+    };
+    Ok(Json(EventsResponse {
+        events,
+        next_cursor,
+    }))
+}
+
+/// The RocksDB archive behind checkpoints, exports and event queries; the
+/// memory backend has none
+fn rocksdb(state: &AppState) -> Result<&Arc<RocksDBStore>, StatusCode> {
+    state.pool.store().ok_or_else(|| {
+        tracing::warn!("Event archive requested but the relayer runs without RocksDB");
+        StatusCode::NOT_IMPLEMENTED
+    })
+}
+
+fn parse_events_query(q: EventsQuery) -> Option<EventQuery> {
+    fn list(value: &Option<String>) -> Vec<&str> {
+        value
+            .as_deref()
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    let authors = list(&q.authors)
+        .into_iter()
+        .map(|a| nostr_sdk::PublicKey::parse(a).ok().map(|pk| pk.to_hex()))
+        .collect::<Option<Vec<_>>>()?;
+    let kinds = list(&q.kinds)
+        .into_iter()
+        .map(|k| k.parse().ok())
+        .collect::<Option<Vec<u16>>>()?;
+    let cursor = match &q.cursor {
+        Some(cursor) => Some(cursor.parse::<EventCursor>().ok()?),
+        None => None,
+    };
+    // Named explicitly so a reordered or extended INDEXED_TAGS cannot mix up filters
+    let tags = [
+        ("symbol", &q.symbol),
+        ("strategy", &q.strategy),
+        ("sid", &q.sid),
+    ]
+    .into_iter()
+    .map(|(name, values)| {
+        (
+            name.to_string(),
+            list(values)
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>(),
+        )
+    })
+    .filter(|(_, values)| !values.is_empty())
+    .collect();
+    Some(EventQuery {
+        authors,
+        kinds,
+        since: q.since,
+        until: q.until,
+        tags,
+        limit: q.limit.clamp(1, 1000),
+        cursor,
+    })
+}
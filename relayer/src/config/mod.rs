@@ -1,21 +1,283 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct RelayConfig {
     pub bootstrap_relays: Vec<String>,
     pub max_connections: usize,
     pub health_check_interval: u64,
+    /// Number of nostr clients the relays are multiplexed over
+    #[serde(default = "default_client_shards")]
+    pub client_shards: usize,
+    /// Seconds without relay traffic before a liveness probe is sent
+    #[serde(default = "default_liveness_timeout")]
+    pub liveness_timeout: u64,
+    /// Seconds a relay has to answer a liveness probe or reconnect attempt
+    #[serde(default = "default_probe_timeout")]
+    pub probe_timeout: u64,
+    /// Initial reconnect backoff in seconds (doubled per consecutive failure)
+    #[serde(default = "default_reconnect_base_delay")]
+    pub reconnect_base_delay: u64,
+    /// Maximum reconnect backoff in seconds
+    #[serde(default = "default_reconnect_max_delay")]
+    pub reconnect_max_delay: u64,
+    /// Seconds subtracted from the persisted per-relay cursor when resubscribing
+    #[serde(default = "default_backfill_overlap")]
+    pub backfill_overlap: u64,
+    /// Periodic relay scoring with pruning and standby promotion (disabled when absent)
+    #[serde(default)]
+    pub scoring: Option<RelayScoringConfig>,
+    /// NIP-65 relay discovery for registered bots
+    #[serde(default)]
+    pub discovery: RelayDiscoveryConfig,
+    /// Only subscribe to events authored by registered bots and their followers
+    #[serde(default)]
+    pub author_scoped: bool,
+    /// Kinds still accepted from any author when `author_scoped` is set, so that
+    /// registrations from bots that are not known yet arrive
+    #[serde(default = "default_open_kinds")]
+    pub open_kinds: Vec<u16>,
+}
+
+fn default_client_shards() -> usize {
+    1
+}
+
+fn default_open_kinds() -> Vec<u16> {
+    vec![30935]
+}
+
+fn default_liveness_timeout() -> u64 {
+    90
+}
+
+fn default_probe_timeout() -> u64 {
+    10
+}
+
+fn default_reconnect_base_delay() -> u64 {
+    2
+}
+
+fn default_reconnect_max_delay() -> u64 {
+    300
+}
+
+fn default_backfill_overlap() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelayDiscoveryConfig {
+    /// Subscribe to the relays registered bots publish to (kind 10002)
+    #[serde(default = "default_discovery_enabled")]
+    pub enabled: bool,
+    /// Relays queried for relay lists; defaults to the bootstrap relays
+    #[serde(default)]
+    pub index_relays: Vec<String>,
+    /// Seconds to wait for a relay list
+    #[serde(default = "default_discovery_fetch_timeout")]
+    pub fetch_timeout: u64,
+    /// Maximum relays added per bot
+    #[serde(default = "default_max_relays_per_bot")]
+    pub max_relays_per_bot: usize,
+    /// Seconds before a bot's relay list is looked up again
+    #[serde(default = "default_discovery_refresh_interval")]
+    pub refresh_interval: u64,
+}
+
+impl Default for RelayDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_discovery_enabled(),
+            index_relays: Vec::new(),
+            fetch_timeout: default_discovery_fetch_timeout(),
+            max_relays_per_bot: default_max_relays_per_bot(),
+            refresh_interval: default_discovery_refresh_interval(),
+        }
+    }
+}
+
+fn default_discovery_enabled() -> bool {
+    true
+}
+
+fn default_discovery_fetch_timeout() -> u64 {
+    10
+}
+
+fn default_max_relays_per_bot() -> usize {
+    10
+}
+
+fn default_discovery_refresh_interval() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelayScoringConfig {
+    /// Seconds between scoring rounds
+    #[serde(default = "default_scoring_interval")]
+    pub interval_secs: u64,
+    /// Relays scoring below this are candidates for pruning
+    #[serde(default = "default_min_score")]
+    pub min_score: f64,
+    /// Relays contributing less than this share of the top relay's first-seen
+    /// events are candidates for pruning, however reliable they are
+    #[serde(default = "default_min_uniqueness")]
+    pub min_uniqueness: f64,
+    /// Consecutive low-scoring rounds before a relay is pruned
+    #[serde(default = "default_prune_after_rounds")]
+    pub prune_after_rounds: u32,
+    /// Seconds a relay is observed before it can be pruned
+    #[serde(default = "default_min_relay_age")]
+    pub min_age_secs: u64,
+    /// Seconds before a pruned relay may be promoted or added by discovery again
+    #[serde(default = "default_prune_cooldown")]
+    pub prune_cooldown_secs: u64,
+    /// Pruning never takes the pool below this many relays
+    #[serde(default = "default_min_relays")]
+    pub min_relays: usize,
+    /// Allow pruning of bootstrap relays
+    #[serde(default)]
+    pub prune_bootstrap: bool,
+    /// Candidate relays connected while the pool is below `max_connections`
+    #[serde(default)]
+    pub standby_relays: Vec<String>,
+    /// Maximum standby relays promoted per round
+    #[serde(default = "default_promote_per_round")]
+    pub promote_per_round: usize,
+}
+
+impl Default for RelayScoringConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_scoring_interval(),
+            min_score: default_min_score(),
+            min_uniqueness: default_min_uniqueness(),
+            prune_after_rounds: default_prune_after_rounds(),
+            min_age_secs: default_min_relay_age(),
+            prune_cooldown_secs: default_prune_cooldown(),
+            min_relays: default_min_relays(),
+            prune_bootstrap: false,
+            standby_relays: Vec::new(),
+            promote_per_round: default_promote_per_round(),
+        }
+    }
+}
+
+fn default_scoring_interval() -> u64 {
+    300
+}
+
+fn default_min_score() -> f64 {
+    0.15
+}
+
+fn default_min_uniqueness() -> f64 {
+    0.05
+}
+
+fn default_prune_after_rounds() -> u32 {
+    3
+}
+
+fn default_min_relay_age() -> u64 {
+    3600
+}
+
+fn default_prune_cooldown() -> u64 {
+    86400
+}
+
+fn default_min_relays() -> usize {
+    3
+}
+
+fn default_promote_per_round() -> usize {
+    5
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct DeduplicationConfig {
     pub hotset_size: usize,
+    /// IDs per Bloom filter generation
     pub bloom_capacity: usize,
+    /// Target false positive rate across all live Bloom generations
+    #[serde(default = "default_bloom_false_positive_rate")]
+    pub bloom_false_positive_rate: f64,
+    /// Bloom generations kept before the oldest is dropped
+    #[serde(default = "default_bloom_generations")]
+    pub bloom_generations: usize,
+    /// Also start a new Bloom generation after this many seconds (0 = only when full)
+    #[serde(default)]
+    pub bloom_rotate_secs: u64,
     pub lru_size: usize,
+    /// Where events and forwarding status are kept
+    #[serde(default)]
+    pub storage: StorageBackend,
     pub rocksdb_path: String,
+    /// Dedupe snapshot file (default: `dedupe.snapshot` next to `rocksdb_path`)
+    #[serde(default)]
+    pub snapshot_path: Option<String>,
+    /// Seconds between dedupe snapshots (0 = no snapshots)
+    #[serde(default = "default_snapshot_interval")]
+    pub snapshot_interval_secs: u64,
+    /// Seconds after restoring a snapshot during which Bloom misses are verified in RocksDB
+    #[serde(default = "default_snapshot_verify")]
+    pub snapshot_verify_secs: u64,
+    /// Seconds within which a decrypted trade signal repeating an earlier one
+    /// (author, `sid`, symbol, side, size, price, oid/tx_hash) is dropped (0 = off)
+    #[serde(default)]
+    pub semantic_window_secs: u64,
+    /// Addressable coordinates remembered for latest-wins and reuse tracking
+    #[serde(default = "default_coordinate_capacity")]
+    pub coordinate_capacity: usize,
+}
+
+/// Event store behind the dedupe engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// RocksDB at `rocksdb_path`
+    #[default]
+    Rocksdb,
+    /// Process memory only: nothing survives a restart, and there are no
+    /// relay cursors, retention pruning or checkpoints (dev deployments)
+    Memory,
+}
+
+impl DeduplicationConfig {
+    /// Where dedupe snapshots are written and restored from
+    pub fn snapshot_path(&self) -> PathBuf {
+        match &self.snapshot_path {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(&self.rocksdb_path).with_file_name("dedupe.snapshot"),
+        }
+    }
+}
+
+fn default_coordinate_capacity() -> usize {
+    100_000
+}
+
+fn default_snapshot_interval() -> u64 {
+    300
+}
+
+fn default_snapshot_verify() -> u64 {
+    600
+}
+
+fn default_bloom_false_positive_rate() -> f64 {
+    0.01
+}
+
+fn default_bloom_generations() -> usize {
+    2
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -32,10 +294,242 @@ fn default_bind_address() -> String {
     "127.0.0.1".to_string()
 }
 
+/// What a pipeline stage does with a new item when its queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Wait for the consumer to make room
+    Block,
+    /// Drop new items whose kind is in `shed_kinds`, else evict the oldest queued
+    /// one of such a kind; other items are refused and left undelivered
+    DropOldest,
+    /// Drop items whose kind is in `shed_kinds`, block for everything else
+    DropByKind,
+}
+
+/// Queue capacities and overflow policies for the ingestion pipeline
+#[derive(Debug, Clone, Deserialize)]
+pub struct PipelineConfig {
+    /// Relay pool -> event router
+    #[serde(default = "default_ingest_capacity")]
+    pub ingest_capacity: usize,
+    #[serde(default = "default_ingest_policy")]
+    pub ingest_policy: OverflowPolicy,
+    /// Event router -> downstream WebSocket
+    #[serde(default = "default_stage_capacity")]
+    pub downstream_capacity: usize,
+    #[serde(default = "default_stage_policy")]
+    pub downstream_policy: OverflowPolicy,
+    /// Event router -> fanout WebSocket
+    #[serde(default = "default_stage_capacity")]
+    pub fanout_capacity: usize,
+    #[serde(default = "default_stage_policy")]
+    pub fanout_policy: OverflowPolicy,
+    /// Kinds shed first under `drop_by_kind` (heartbeats by default)
+    #[serde(default = "default_shed_kinds")]
+    pub shed_kinds: Vec<u16>,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            ingest_capacity: default_ingest_capacity(),
+            ingest_policy: default_ingest_policy(),
+            downstream_capacity: default_stage_capacity(),
+            downstream_policy: default_stage_policy(),
+            fanout_capacity: default_stage_capacity(),
+            fanout_policy: default_stage_policy(),
+            shed_kinds: default_shed_kinds(),
+        }
+    }
+}
+
+fn default_ingest_capacity() -> usize {
+    100_000
+}
+
+fn default_ingest_policy() -> OverflowPolicy {
+    OverflowPolicy::DropByKind
+}
+
+fn default_stage_capacity() -> usize {
+    10_000
+}
+
+fn default_stage_policy() -> OverflowPolicy {
+    OverflowPolicy::Block
+}
+
+fn default_shed_kinds() -> Vec<u16> {
+    vec![30933]
+}
+
+/// Checks applied to every event before deduplication
+#[derive(Debug, Clone, Deserialize)]
+pub struct IngressConfig {
+    /// Seconds an event's `created_at` may lie in the past
+    #[serde(default = "default_max_past_secs")]
+    pub max_past_secs: u64,
+    /// Seconds a relay catching up from its cursor may reach back; applies to
+    /// backfilled events instead of `max_past_secs` (never below it)
+    #[serde(default = "default_max_backfill_secs")]
+    pub max_backfill_secs: u64,
+    /// Seconds an event's `created_at` may lie in the future
+    #[serde(default = "default_max_future_secs")]
+    pub max_future_secs: u64,
+    /// Largest accepted `content`, in bytes
+    #[serde(default = "default_max_content_bytes")]
+    pub max_content_bytes: usize,
+    /// Most tags accepted on one event
+    #[serde(default = "default_max_tags")]
+    pub max_tags: usize,
+    /// NIP-13 proof-of-work (leading zero bits of the id) required for agent
+    /// registrations (0 = not required)
+    #[serde(default)]
+    pub register_pow_difficulty: u8,
+}
+
+impl IngressConfig {
+    /// Oldest `created_at` accepted through any path, live or backfill
+    pub fn max_age_secs(&self) -> u64 {
+        self.max_backfill_secs.max(self.max_past_secs)
+    }
+}
+
+impl Default for IngressConfig {
+    fn default() -> Self {
+        Self {
+            max_past_secs: default_max_past_secs(),
+            max_backfill_secs: default_max_backfill_secs(),
+            max_future_secs: default_max_future_secs(),
+            max_content_bytes: default_max_content_bytes(),
+            max_tags: default_max_tags(),
+            register_pow_difficulty: 0,
+        }
+    }
+}
+
+fn default_max_past_secs() -> u64 {
+    600
+}
+
+fn default_max_backfill_secs() -> u64 {
+    3600
+}
+
+fn default_max_future_secs() -> u64 {
+    300
+}
+
+fn default_max_content_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_max_tags() -> usize {
+    100
+}
+
+/// RocksDB checkpoints written on demand and on a schedule
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackupConfig {
+    /// Checkpoint directory (default: `checkpoints` next to `rocksdb_path`)
+    #[serde(default)]
+    pub dir: Option<String>,
+    /// Seconds between scheduled checkpoints (0 = only on demand)
+    #[serde(default)]
+    pub interval_secs: u64,
+    /// Newest checkpoints kept (0 = all)
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+}
+
+impl BackupConfig {
+    /// Where checkpoints of the database at `rocksdb_path` are written
+    pub fn dir(&self, rocksdb_path: &str) -> PathBuf {
+        match &self.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(rocksdb_path).with_file_name("checkpoints"),
+        }
+    }
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            interval_secs: 0,
+            keep: default_backup_keep(),
+        }
+    }
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+/// How long archived events are kept, per kind
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionConfig {
+    /// Seconds between pruning runs (0 = never prune)
+    #[serde(default = "default_retention_interval")]
+    pub interval_secs: u64,
+    /// Kinds with a limited lifetime; kinds not listed are kept forever
+    #[serde(default = "default_retention_kinds")]
+    pub kinds: Vec<KindRetention>,
+}
+
+/// Maximum age of archived events of one kind
+#[derive(Debug, Clone, Deserialize)]
+pub struct KindRetention {
+    pub kind: u16,
+    /// Seconds after `created_at` at which an event is pruned
+    pub max_age_secs: u64,
+}
+
+impl RetentionConfig {
+    /// Maximum age in seconds per kind
+    pub fn policy(&self) -> HashMap<u16, u64> {
+        self.kinds
+            .iter()
+            .map(|k| (k.kind, k.max_age_secs))
+            .collect()
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_retention_interval(),
+            kinds: default_retention_kinds(),
+        }
+    }
+}
+
+fn default_retention_interval() -> u64 {
+    300
+}
+
+/// Heartbeats for a day; everything else forever
+fn default_retention_kinds() -> Vec<KindRetention> {
+    vec![KindRetention {
+        kind: 30933,
+        max_age_secs: 86_400,
+    }]
+}
+
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct FilterConfig {
     #[serde(default = "default_allowed_kinds")]
     pub allowed_kinds: Vec<u16>,
+    /// Addressable kinds where only the newest event per (kind, author, `d` tag) counts
+    /// (default: `default_latest_wins_kinds`)
+    #[serde(default)]
+    pub latest_wins_kinds: Option<Vec<u16>>,
+}
+
+/// Heartbeats and agent registrations: a newer one replaces the older
+pub fn default_latest_wins_kinds() -> Vec<u16> {
+    vec![30933, 30935]
 }
 
 fn default_allowed_kinds() -> Vec<u16> {
@@ -149,6 +643,12 @@ pub struct AppConfig {
     #[serde(default)]
     pub filters: FilterConfig,
     #[serde(default)]
+    pub ingress: IngressConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
     pub postgres: Option<PostgresConfig>,
     #[serde(default)]
     pub nostr: Option<NostrConfig>,
@@ -156,6 +656,8 @@ pub struct AppConfig {
     pub settlement: Option<SettlementConfig>,
     #[serde(default)]
     pub subscriptions: Option<SubscriptionsConfig>,
+    #[serde(default)]
+    pub pipeline: PipelineConfig,
     pub monitoring: MonitoringConfig,
 }
 
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use nostr_sdk::Event;
+use serde::Serialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::warn;
+
+use crate::core::dedupe_engine::DeduplicationEngine;
+use crate::storage::event_index::EventQuery;
+use crate::storage::rocksdb_store::RocksDBStore;
+
+/// Events buffered between the store scan and the writer
+const EXPORT_BUFFER: usize = 1_024;
+
+/// Outcome of a JSONL import
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ImportStats {
+    /// Events archived by this import
+    pub imported: u64,
+    /// Events that were already stored
+    pub duplicates: u64,
+    /// Lines that were not valid, correctly signed events
+    pub invalid: u64,
+}
+
+/// Write the stored events matching `filter` as NIP-01 JSON, one per line
+/// Returns the number of events written.
+pub async fn export_jsonl<W>(
+    store: Arc<RocksDBStore>,
+    filter: EventQuery,
+    mut writer: W,
+) -> Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let (tx, rx) = flume::bounded(EXPORT_BUFFER);
+    let scan = tokio::spawn(async move { store.export_events(filter, tx).await });
+    let mut written = 0;
+    // Returning early drops `rx`, which stops the scan
+    while let Ok(event) = rx.recv_async().await {
+        let mut line = serde_json::to_vec(&event).context("Failed to serialize event")?;
+        line.push(b'\n');
+        writer
+            .write_all(&line)
+            .await
+            .context("Failed to write export")?;
+        written += 1;
+    }
+    writer.flush().await.context("Failed to write export")?;
+    scan.await.context("Export task panicked")??;
+    Ok(written)
+}
+
+/// Archive the events of a NIP-01 JSONL stream through the dedupe engine
+/// Events are signature-checked; malformed lines and bad signatures are
+/// counted as invalid and skipped. Imported events are never forwarded.
+pub async fn import_jsonl<R>(engine: &DeduplicationEngine, mut reader: R) -> Result<ImportStats>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut stats = ImportStats::default();
+    let mut line = Vec::new();
+    let mut line_no = 0u64;
+    loop {
+        line.clear();
+        // Raw bytes, so a line that is not UTF-8 only counts as invalid
+        if reader
+            .read_until(b'\n', &mut line)
+            .await
+            .context("Failed to read import")?
+            == 0
+        {
+            break;
+        }
+        line_no += 1;
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let event: Event = match serde_json::from_slice(&line) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Skipping malformed event on line {}: {}", line_no, e);
+                stats.invalid += 1;
+                continue;
+            }
+        };
+        if let Err(e) = event.verify() {
+            warn!(
+                "Skipping event {} on line {}: {}",
+                event.id.to_hex(),
+                line_no,
+                e
+            );
+            stats.invalid += 1;
+            continue;
+        }
+        if engine.import_event(&event).await? {
+            stats.imported += 1;
+        } else {
+            stats.duplicates += 1;
+        }
+    }
+    Ok(stats)
+}
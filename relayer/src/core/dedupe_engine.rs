@@ -1,41 +1,83 @@
+use crate::api::metrics::Metrics;
 use crate::storage::{
-    bloom_filter::BloomFilter, memory_cache::MemoryCache, rocksdb_store::RocksDBStore,
+    bloom_filter::BloomFilter, event_store::EventStore, hot_set::HotSet, memory_cache::MemoryCache,
+    snapshot::DedupeSnapshot,
 };
-// use anyhow::Result;
-use crate::api::metrics::Metrics;
-use dashmap::DashSet;
-use nostr_sdk::{Event, EventId};
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use lru::LruCache;
+use nostr_sdk::nips::nip01::Coordinate;
+use nostr_sdk::{Event, EventId, Timestamp};
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::{debug, trace};
 
+/// Addressable coordinates tracked per kind of tracking, unless configured
+const COORDINATE_CAPACITY: usize = 100_000;
+
 /// Multi-layer deduplication engine
+/// Layer 0: Bounded hot set (hot path for very recent events, evicted oldest first)
 /// Layer 1: Bloom filter (fast, in-memory, may have false positives)
 /// Layer 2: LRU cache (recent events, exact match)
-/// Layer 3: RocksDB (persistent storage, exact match)
-/// Layer 4: Concurrent hash set (hot path for very recent events)
+/// Layer 3: event store (RocksDB in production; persistent, exact match)
 pub struct DeduplicationEngine {
     bloom: Arc<BloomFilter>,
     lru_cache: Arc<MemoryCache>,
-    rocksdb: Arc<RocksDBStore>,
-    hot_set: Arc<DashSet<String>>,
+    store: Arc<dyn EventStore>,
+    hot_set: Arc<HotSet>,
     metrics: Option<Arc<Metrics>>,
+    /// After restoring a snapshot, Bloom misses are verified against the event store
+    /// until then, since events handled after the snapshot are not in the restored filter
+    verify_misses_until: OnceLock<Instant>,
+    /// Addressable kinds where a newer event replaces older ones per coordinate
+    latest_wins_kinds: Vec<u16>,
+    /// Newest event per recently used coordinate of a latest-wins kind
+    latest: Mutex<LruCache<Coordinate, (Timestamp, EventId)>>,
+    /// Recent coordinates of other addressable kinds, to notice reuse
+    seen_coordinates: HotSet,
+    /// Recent coordinates of other addressable kinds used by several events
+    shared_coordinates: HotSet,
+}
+
+/// How an event relates to others at its NIP-01 addressable coordinate
+/// (kind, author, `d` tag)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressableVerdict {
+    /// Kind outside the addressable range (30000-39999)
+    NotAddressable,
+    /// Newest event at its coordinate so far
+    Latest,
+    /// A newer event at the same coordinate of a latest-wins kind was already seen
+    Superseded,
+    /// Another event already used this coordinate of a kind where every event
+    /// matters. Relays keep only the newest, so backfills can miss the others.
+    /// `first` is set the first time the coordinate is found shared.
+    SharedCoordinate { first: bool },
 }
 
 impl DeduplicationEngine {
     /// Create a new deduplication engine
-    pub fn new(rocksdb: Arc<RocksDBStore>) -> Self {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
         Self {
             bloom: Arc::new(BloomFilter::new()),
             lru_cache: Arc::new(MemoryCache::new()),
-            rocksdb,
-            hot_set: Arc::new(DashSet::new()),
+            store,
+            hot_set: Arc::new(HotSet::default()),
             metrics: None,
+            verify_misses_until: OnceLock::new(),
+            latest_wins_kinds: Vec::new(),
+            latest: latest_cache(COORDINATE_CAPACITY),
+            seen_coordinates: HotSet::with_capacity(COORDINATE_CAPACITY),
+            shared_coordinates: HotSet::with_capacity(COORDINATE_CAPACITY),
         }
     }
 
     /// Create a new deduplication engine with custom capacities
+    /// `bloom_capacity` is per Bloom generation (two generations, 1% false positives)
     pub fn new_with_params(
-        rocksdb: Arc<RocksDBStore>,
+        store: Arc<dyn EventStore>,
         hot_set_size: usize,
         bloom_capacity: usize,
         lru_size: usize,
@@ -43,126 +85,360 @@ impl DeduplicationEngine {
         Self {
             bloom: Arc::new(BloomFilter::with_capacity(bloom_capacity, 0.01)),
             lru_cache: Arc::new(MemoryCache::with_capacity(lru_size)),
-            rocksdb,
-            hot_set: Arc::new(DashSet::with_capacity(hot_set_size)),
+            store,
+            hot_set: Arc::new(HotSet::with_capacity(hot_set_size)),
             metrics: None,
+            verify_misses_until: OnceLock::new(),
+            latest_wins_kinds: Vec::new(),
+            latest: latest_cache(COORDINATE_CAPACITY),
+            seen_coordinates: HotSet::with_capacity(COORDINATE_CAPACITY),
+            shared_coordinates: HotSet::with_capacity(COORDINATE_CAPACITY),
         }
     }
 
+    /// Replace the Bloom filter, e.g. with different rotation settings
+    pub fn with_bloom_filter(mut self, bloom: BloomFilter) -> Self {
+        self.bloom = Arc::new(bloom);
+        self
+    }
+
+    /// Addressable kinds where only the newest event per coordinate counts
+    /// (e.g. agent registrations, heartbeats); older arrivals are `Superseded`
+    pub fn with_latest_wins_kinds(mut self, kinds: Vec<u16>) -> Self {
+        self.latest_wins_kinds = kinds;
+        self
+    }
+
+    /// Coordinates remembered for addressable semantics, each for latest-wins
+    /// tracking, reuse detection and known reuses; least recent are forgotten first
+    pub fn with_coordinate_capacity(mut self, capacity: usize) -> Self {
+        self.latest = latest_cache(capacity);
+        self.seen_coordinates = HotSet::with_capacity(capacity);
+        self.shared_coordinates = HotSet::with_capacity(capacity);
+        self
+    }
+
     pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
         self.metrics = Some(metrics);
         self
     }
 
-    /// Warm in-memory structures from RocksDB successful-forward index.
+    /// Event archive behind the dedupe checks
+    pub fn store(&self) -> &Arc<dyn EventStore> {
+        &self.store
+    }
+
+    /// Warm in-memory structures from the event store success index.
     /// Loads up to `limit` most recent successfully forwarded events into bloom, hot_set and LRU.
     pub async fn warm_from_db(&self, limit: usize) {
         if limit == 0 {
             return;
         }
-        let ids = self.rocksdb.load_recent_success_ids(limit).await;
+        let ids = self.store.load_recent_success_ids(limit).await;
         for id in &ids {
             match EventId::from_hex(&id) {
                 Ok(event_id) => {
                     // Best-effort: insert into bloom, lru and hot_set
-                    self.bloom.insert(event_id.as_bytes()).await;
+                    self.insert_bloom(&event_id);
                 }
                 Err(err) => {
-                    tracing::warn!("Failed to parse event id {} from RocksDB: {}", id, err);
+                    tracing::warn!(
+                        "Failed to parse event id {} from the event store: {}",
+                        id,
+                        err
+                    );
                     // continue best-effort using the string forms for caches
                 }
             }
-            self.lru_cache.put(id.clone()).await;
-            self.hot_set.insert(id.to_string());
+            self.lru_cache.put(id.clone());
+            self.insert_hot(id.to_string());
         }
         tracing::info!(
-            "Deduplication engine warmed with {} IDs from RocksDB",
+            "Deduplication engine warmed with {} IDs from the event store",
             ids.len()
         );
     }
 
+    /// Track a non-duplicate event at its addressable coordinate, the way a NIP-01
+    /// relay would: newer `created_at` wins, ties go to the lower event id
+    pub fn check_coordinate(&self, event: &Event) -> AddressableVerdict {
+        if !event.kind.is_addressable() {
+            return AddressableVerdict::NotAddressable;
+        }
+        let Some(coordinate) = event.coordinate().map(|c| c.into_owned()) else {
+            return AddressableVerdict::NotAddressable;
+        };
+
+        if self.latest_wins_kinds.contains(&event.kind.as_u16()) {
+            let incoming = (event.created_at, event.id);
+            let mut latest = self
+                .latest
+                .lock()
+                .expect("latest coordinates lock poisoned");
+            let Some(current) = latest.get_mut(&coordinate) else {
+                latest.put(coordinate, incoming);
+                return AddressableVerdict::Latest;
+            };
+            let (created_at, id) = *current;
+            if created_at > incoming.0 || (created_at == incoming.0 && id < incoming.1) {
+                return AddressableVerdict::Superseded;
+            }
+            *current = incoming;
+            return AddressableVerdict::Latest;
+        }
+
+        let key = format!(
+            "{}:{}:{}",
+            coordinate.kind.as_u16(),
+            coordinate.public_key.to_hex(),
+            coordinate.identifier
+        );
+        if self.seen_coordinates.contains(&key) {
+            let first = self.shared_coordinates.try_insert(key).is_some();
+            AddressableVerdict::SharedCoordinate { first }
+        } else {
+            self.seen_coordinates.insert(key);
+            AddressableVerdict::Latest
+        }
+    }
+
+    /// Record that an event was fully processed (forwarded, fanned out, written)
+    /// Feeds the success index `warm_from_db` loads and stops it being replayed
+    pub async fn mark_forward_success(&self, event_id: &EventId) -> Result<()> {
+        self.store.mark_forward_success(&event_id.to_hex()).await
+    }
+
+    /// Remember the delivery steps a partly failed delivery completed
+    pub async fn record_deliveries(&self, event_id: &EventId, steps: &[String]) -> Result<()> {
+        self.store
+            .record_deliveries(&event_id.to_hex(), steps)
+            .await
+    }
+
+    /// Delivery steps completed by earlier attempts of an unfinished event
+    pub async fn completed_deliveries(&self, event_id: &EventId) -> Result<HashSet<String>> {
+        self.store.completed_deliveries(&event_id.to_hex()).await
+    }
+
+    /// Stored events that were never marked as forwarded, e.g. after a crash
+    /// They are added to the in-memory layers so relay re-deliveries stay duplicates.
+    pub async fn load_unfinished(&self, limit: usize) -> Vec<Event> {
+        let events = self.store.load_pending_events(limit).await;
+        for event in &events {
+            let event_id_hex = event.id.to_hex();
+            self.insert_bloom(&event.id);
+            self.lru_cache.put(event_id_hex.clone());
+            self.insert_hot(event_id_hex);
+        }
+        events
+    }
+
+    /// Archive an event from elsewhere (e.g. another relayer's export) without
+    /// forwarding it; returns false when it is already stored
+    /// It is added to the bloom filter and LRU so live re-deliveries stay duplicates.
+    pub async fn import_event(&self, event: &Event) -> Result<bool> {
+        let event_id_hex = event.id.to_hex();
+        if self.store.exists(&event_id_hex).await {
+            return Ok(false);
+        }
+        self.store.archive_event(event).await?;
+        self.insert_bloom(&event.id);
+        self.lru_cache.put(event_id_hex);
+        Ok(true)
+    }
+
+    /// Capture the in-memory layers for an on-disk snapshot
+    pub async fn snapshot(&self) -> DedupeSnapshot {
+        let to_bytes = |ids: Vec<String>| -> Vec<[u8; 32]> {
+            ids.iter()
+                .filter_map(|id| EventId::from_hex(id).ok())
+                .map(EventId::to_bytes)
+                .collect()
+        };
+        DedupeSnapshot {
+            taken_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            bloom: self.bloom.export(),
+            lru: to_bytes(self.lru_cache.keys()),
+            hot_set: to_bytes(self.hot_set.ids()),
+        }
+    }
+
+    /// Load the in-memory layers from a snapshot
+    /// For `verify_for` afterwards, Bloom misses are still checked against the event store
+    pub async fn restore(&self, snapshot: DedupeSnapshot, verify_for: Duration) -> Result<()> {
+        self.bloom.restore(snapshot.bloom)?;
+        for id in snapshot.lru {
+            self.lru_cache.put(EventId::from_byte_array(id).to_hex());
+        }
+        for id in snapshot.hot_set {
+            self.insert_hot(EventId::from_byte_array(id).to_hex());
+        }
+        let _ = self.verify_misses_until.set(Instant::now() + verify_for);
+        Ok(())
+    }
+
+    /// Write a snapshot of the in-memory layers to `path`
+    pub async fn save_snapshot(&self, path: &Path) -> Result<()> {
+        let snapshot = self.snapshot().await;
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || snapshot.save(&path))
+            .await
+            .context("Snapshot writer panicked")?
+    }
+
+    /// Restore from the snapshot at `path`
+    /// Returns false if there is none; fails if it is unreadable, corrupt or from
+    /// another snapshot version
+    pub async fn load_snapshot(&self, path: &Path, verify_for: Duration) -> Result<bool> {
+        let path = path.to_path_buf();
+        let snapshot = tokio::task::spawn_blocking(move || DedupeSnapshot::load(&path))
+            .await
+            .context("Snapshot reader panicked")??;
+        match snapshot {
+            Some(snapshot) => {
+                let (lru, hot) = (snapshot.lru.len(), snapshot.hot_set.len());
+                self.restore(snapshot, verify_for).await?;
+                tracing::info!(
+                    "Deduplication engine restored from snapshot ({} LRU, {} hot set IDs)",
+                    lru,
+                    hot
+                );
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     /// Check if an event is a duplicate
     /// Returns true if duplicate, false if new event
     pub async fn is_duplicate(&self, event: &Event) -> bool {
         let event_id_hex = event.id.to_hex();
 
-        // Layer 0: Hot set check (fastest, for very recent events)
-        if self.hot_set.contains(&event_id_hex) {
+        // Layer 0: Hot set (fastest, for very recent events). Claiming the ID is
+        // atomic, so an event arriving from several relays at once is new only once.
+        let Some(evicted) = self.hot_set.try_insert(event_id_hex.clone()) else {
             trace!("Event {} found in hot set (duplicate)", event_id_hex);
             return true;
-        }
+        };
+        self.record_hot_evictions(evicted);
 
         // Layer 1: Bloom filter check (fast, in-memory, may have false positives)
-        if self.bloom.contains(event.id.as_bytes()).await {
+        let maybe_seen = if self.bloom.contains(event.id.as_bytes()) {
             // Bloom filter says it might exist, need to verify
             trace!("Event {} might exist (bloom filter positive)", event_id_hex);
+            true
         } else {
-            // Bloom filter says it doesn't exist, definitely new
-            self.bloom.insert(event.id.as_bytes()).await;
-            self.hot_set.insert(event_id_hex.clone());
-            debug!("New event {} added to bloom filter", event_id_hex);
-            return false;
-        }
-
-        // Layer 2: LRU cache check (recent events, exact match)
-        if self.lru_cache.contains(&event_id_hex).await {
-            trace!("Event {} found in LRU cache (duplicate)", event_id_hex);
-            self.hot_set.insert(event_id_hex);
-            if let Some(m) = &self.metrics {
-                m.duplicates_filtered.inc();
+            // Bloom filter says it doesn't exist, definitely new unless the filter was
+            // restored from a snapshot moments ago. Still record it in the exact layers
+            // so it stays detectable once evicted from the hot set.
+            self.insert_bloom(&event.id);
+            self.verify_misses_until
+                .get()
+                .is_some_and(|until| Instant::now() < *until)
+        };
+
+        if maybe_seen {
+            // Layer 2: LRU cache check (recent events, exact match)
+            if self.lru_cache.contains(&event_id_hex) {
+                trace!("Event {} found in LRU cache (duplicate)", event_id_hex);
+                if let Some(m) = &self.metrics {
+                    m.duplicates_filtered.inc();
+                }
+                return true;
             }
-            return true;
-        }
 
-        // Layer 3: RocksDB check (persistent storage, exact match)
-        if self.rocksdb.exists(&event_id_hex).await {
-            // Found in persistent storage, add to cache layers
-            self.lru_cache.put(event_id_hex.clone()).await;
-            self.hot_set.insert(event_id_hex.clone());
-            trace!("Event {} found in RocksDB (duplicate)", event_id_hex);
-            if let Some(m) = &self.metrics {
-                m.duplicates_filtered.inc();
+            // Layer 3: event store check (persistent storage, exact match)
+            if self.store.exists(&event_id_hex).await {
+                // Found in persistent storage, add to cache layers
+                self.lru_cache.put(event_id_hex.clone());
+                trace!(
+                    "Event {} found in the event store (duplicate)",
+                    event_id_hex
+                );
+                if let Some(m) = &self.metrics {
+                    m.duplicates_filtered.inc();
+                }
+                return true;
             }
-            return true;
         }
 
         // New event - store in all layers
         debug!("New event {} detected, storing in all layers", event_id_hex);
 
+        // A false positive may come from an older generation only; record the ID
+        // in the current one so it is still caught after that generation rotates out
+        if maybe_seen {
+            self.insert_bloom(&event.id);
+        }
+
         // Store in persistent storage
-        if let Err(e) = self.rocksdb.store_event(event).await {
-            tracing::error!("Failed to store event {} in RocksDB: {}", event_id_hex, e);
-        }
-
-        // Store in cache layers
-        self.lru_cache.put(event_id_hex.clone()).await;
-        self.hot_set.insert(event_id_hex);
-
-        // Limit hot set size to prevent unbounded growth
-        let hot_set_capacity = self.hot_set.capacity();
-        if self.hot_set.len() >= hot_set_capacity {
-            // Clear half of the hot set (simple eviction strategy)
-            let keys: Vec<String> = self
-                .hot_set
-                .iter()
-                .take(hot_set_capacity / 2)
-                .map(|s| s.to_string())
-                .collect();
-            for key in keys {
-                self.hot_set.remove(&key);
-            }
+        if let Err(e) = self.store.store_event(event).await {
+            tracing::error!(
+                "Failed to store event {} in the event store: {}",
+                event_id_hex,
+                e
+            );
         }
 
+        // Store in cache layers (already claimed in the hot set)
+        self.lru_cache.put(event_id_hex);
+
         false
     }
 
+    /// Insert into the Bloom filter and report its fill and accuracy
+    fn insert_bloom(&self, event_id: &EventId) {
+        let rotated = self.bloom.insert(event_id.as_bytes());
+        if let Some(m) = &self.metrics {
+            if rotated {
+                m.bloom_rotations.inc();
+            }
+            let stats = self.bloom.stats();
+            m.bloom_fill_ratio.set(stats.fill_ratio);
+            m.bloom_estimated_fpr.set(stats.estimated_fpr);
+        }
+    }
+
+    /// Insert into the hot set, counting evictions of the oldest IDs
+    fn insert_hot(&self, event_id_hex: String) {
+        let evicted = self.hot_set.insert(event_id_hex);
+        self.record_hot_evictions(evicted);
+    }
+
+    fn record_hot_evictions(&self, evicted: usize) {
+        if evicted > 0
+            && let Some(m) = &self.metrics
+        {
+            m.hot_set_evictions.inc_by(evicted as u64);
+        }
+    }
+
     /// Get statistics about the deduplication engine
     pub async fn get_stats(&self) -> DedupeStats {
+        let bloom = self.bloom.stats();
+        let latest_coordinates = self
+            .latest
+            .lock()
+            .expect("latest coordinates lock poisoned")
+            .len();
         DedupeStats {
-            bloom_filter_size: 0, // Bloom filter doesn't expose size
-            lru_cache_size: self.lru_cache.len().await,
+            bloom_filter_size: bloom.items as usize,
+            bloom_generations: bloom.generations,
+            bloom_fill_ratio: bloom.fill_ratio,
+            bloom_estimated_fpr: bloom.estimated_fpr,
+            bloom_rotations: bloom.rotations,
+            lru_cache_size: self.lru_cache.len(),
             hot_set_size: self.hot_set.len(),
-            rocksdb_approximate_count: self.rocksdb.approximate_count().await,
+            hot_set_capacity: self.hot_set.capacity(),
+            hot_set_evictions: self.hot_set.evictions(),
+            latest_coordinates,
+            seen_coordinates: self.seen_coordinates.len(),
+            shared_coordinates: self.shared_coordinates.len(),
+            coordinate_capacity: self.seen_coordinates.capacity(),
+            rocksdb_approximate_count: self.store.approximate_count().await,
         }
     }
 }
@@ -170,8 +446,31 @@ impl DeduplicationEngine {
 /// Statistics about the deduplication engine
 #[derive(Debug, Clone)]
 pub struct DedupeStats {
+    /// Distinct IDs in the live Bloom generations
     pub bloom_filter_size: usize,
+    pub bloom_generations: usize,
+    /// Fraction of bits set in the newest Bloom generation
+    pub bloom_fill_ratio: f64,
+    /// Estimated false positive rate across live Bloom generations
+    pub bloom_estimated_fpr: f64,
+    pub bloom_rotations: u64,
     pub lru_cache_size: usize,
     pub hot_set_size: usize,
+    pub hot_set_capacity: usize,
+    pub hot_set_evictions: u64,
+    /// Coordinates of latest-wins kinds with their newest event
+    pub latest_coordinates: usize,
+    /// Coordinates of every-event kinds seen recently
+    pub seen_coordinates: usize,
+    /// Of those, coordinates used by several events
+    pub shared_coordinates: usize,
+    /// Bound of each of the coordinate counts above
+    pub coordinate_capacity: usize,
     pub rocksdb_approximate_count: u64,
 }
+
+fn latest_cache(capacity: usize) -> Mutex<LruCache<Coordinate, (Timestamp, EventId)>> {
+    Mutex::new(LruCache::new(
+        NonZeroUsize::new(capacity.max(1)).expect("capacity is positive"),
+    ))
+}
@@ -1,19 +1,26 @@
-use anyhow::Result;
-use flume::{Receiver, Sender};
+use anyhow::{Context, Result, anyhow};
+use flume::Receiver;
 use nostr_sdk::Event;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::api::metrics::Metrics;
-use crate::core::dedupe_engine::DeduplicationEngine;
-use crate::core::subscription::{FanoutMessage, SignalInsert, SubscriptionService};
+use crate::core::dedupe_engine::{AddressableVerdict, DeduplicationEngine};
+use crate::core::ingress::IngressValidator;
+use crate::core::pipeline::StageSender;
+use crate::core::relay_pool::{RelayEvent, RelayPool};
+use crate::core::semantic_dedupe::{SemanticDedupe, SignalKey};
+use crate::core::subscription::{
+    FanoutMessage, SignalInsert, SubscriptionRow, SubscriptionService,
+};
 use chrono::{DateTime, TimeZone, Utc};
 use nostr_sdk::Kind;
 use nostr_sdk::nips::nip04;
-use nostr_sdk::prelude::{Client, EventBuilder, Keys, PublicKey, Tag, Timestamp};
+use nostr_sdk::prelude::{Client, EventBuilder, Keys, PublicKey, Tag, TagKind, Timestamp};
 use serde_json::Value;
 use std::str::FromStr;
 
@@ -21,14 +28,19 @@ const KIND_TRADE_SIGNAL: u16 = 30931;
 const KIND_COPYTRADE_INTENT: u16 = 30932;
 const KIND_HEARTBEAT: u16 = 30933;
 const KIND_EXECUTION_REPORT: u16 = 30934;
-const KIND_AGENT_REGISTER: u16 = 30935;
-const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+pub(crate) const KIND_AGENT_REGISTER: u16 = 30935;
+/// Most unfinished events re-delivered on startup
+const REPLAY_LIMIT: usize = 10_000;
 
 /// Wrapper for Event to enable sorting by timestamp
 #[derive(Clone)]
 struct EventWrapper {
     event: Event,
     timestamp: u64,
+    /// Loaded by `replay_unfinished`, so earlier attempts may have left progress
+    replayed: bool,
+    /// Backfilled from a relay's store; may be older than `max_past_secs`
+    backfill: bool,
 }
 
 impl PartialEq for EventWrapper {
@@ -56,14 +68,17 @@ pub struct EventRouter {
     dedupe_engine: Arc<DeduplicationEngine>,
     batch_size: usize,
     max_latency: Duration,
-    downstream_tx: Sender<Event>,
+    downstream_tx: StageSender<Event>,
     allowed_kinds: Option<Vec<u16>>,
-    fanout_tx: Option<Sender<FanoutMessage>>,
+    fanout_tx: Option<StageSender<FanoutMessage>>,
     subscription_service: Option<Arc<SubscriptionService>>,
     nostr_keys: Option<Keys>,
     nostr_client: Option<Arc<Client>>,
     pending_events: Arc<RwLock<Vec<EventWrapper>>>,
     heartbeat_seen: Option<Arc<RwLock<HashMap<String, Instant>>>>,
+    relay_pool: Option<Arc<RelayPool>>,
+    ingress: IngressValidator,
+    semantic_dedupe: Option<SemanticDedupe>,
     metrics: Option<Arc<Metrics>>,
 }
 
@@ -73,9 +88,9 @@ impl EventRouter {
         dedupe_engine: Arc<DeduplicationEngine>,
         batch_size: usize,
         max_latency: Duration,
-        downstream_tx: Sender<Event>,
+        downstream_tx: StageSender<Event>,
         allowed_kinds: Option<Vec<u16>>,
-        fanout_tx: Option<Sender<FanoutMessage>>,
+        fanout_tx: Option<StageSender<FanoutMessage>>,
         subscription_service: Option<Arc<SubscriptionService>>,
         nostr_keys: Option<Keys>,
         nostr_client: Option<Arc<Client>>,
@@ -96,6 +111,9 @@ impl EventRouter {
             nostr_client,
             pending_events: Arc::new(RwLock::new(Vec::new())),
             heartbeat_seen,
+            relay_pool: None,
+            ingress: IngressValidator::default(),
+            semantic_dedupe: None,
             metrics: None,
         }
     }
@@ -106,8 +124,27 @@ impl EventRouter {
         self
     }
 
+    /// Report per-relay dedupe verdicts back to the relay pool
+    pub fn with_relay_pool(mut self, relay_pool: Arc<RelayPool>) -> Self {
+        self.relay_pool = Some(relay_pool);
+        self
+    }
+
+    /// Replace the default ingress checks (clock skew, size limits, PoW)
+    pub fn with_ingress_validator(mut self, ingress: IngressValidator) -> Self {
+        self.ingress = ingress;
+        self
+    }
+
+    /// Drop decrypted trade signals repeating one from the same author within `window`
+    pub fn with_semantic_dedupe(mut self, window: Duration) -> Self {
+        self.semantic_dedupe = Some(SemanticDedupe::new(window));
+        self
+    }
+
     /// Process incoming event stream, deduplicate, and route to downstream
-    pub async fn process_stream(self, input: Receiver<Event>) -> Result<()> {
+    pub async fn process_stream(self, input: Receiver<RelayEvent>) -> Result<()> {
+        self.replay_unfinished().await?;
         let mut last_flush = Instant::now();
 
         loop {
@@ -119,20 +156,46 @@ impl EventRouter {
                 // Receive new event
                 result = input.recv_async() => {
                     match result {
-                        Ok(event) => {
+                        Ok(RelayEvent { relay_url, event, backfill }) => {
                             // Kind filtering (drop events not in allowlist if configured)
                             if let Some(allowed) = &self.allowed_kinds {
                                 if !allowed.contains(&event.kind.as_u16()) {
                                     continue;
                                 }
                             }
+                            let verdict = if backfill {
+                                self.ingress.validate_stored(&event)
+                            } else {
+                                self.ingress.validate(&event)
+                            };
+                            if let Err(reason) = verdict {
+                                debug!(
+                                    "Reject event id={} kind={} from {}: {}",
+                                    event.id.to_hex(),
+                                    event.kind.as_u16(),
+                                    relay_url,
+                                    reason.as_str()
+                                );
+                                if let Some(m) = &self.metrics {
+                                    m.ingress_rejected
+                                        .with_label_values(&[reason.as_str()])
+                                        .inc();
+                                }
+                                continue;
+                            }
                             // Deduplication check
-                            if !self.dedupe_engine.is_duplicate(&event).await {
+                            let duplicate = self.dedupe_engine.is_duplicate(&event).await;
+                            if let Some(pool) = &self.relay_pool {
+                                pool.record_dedupe_result(&relay_url, duplicate);
+                            }
+                            if !duplicate && self.accept_addressable(&event).await {
                                 // Add to pending events (will be sorted before flushing)
                                 let timestamp = event.created_at.as_secs();
                                 let wrapper = EventWrapper {
                                     event,
                                     timestamp,
+                                    replayed: false,
+                                    backfill,
                                 };
 
                                 let mut pending = self.pending_events.write().await;
@@ -176,6 +239,53 @@ impl EventRouter {
         Ok(())
     }
 
+    /// Apply addressable-event semantics (kinds 30000-39999, see `docs/API.md`).
+    /// Returns false for latest-wins events that a newer one already replaced.
+    async fn accept_addressable(&self, event: &Event) -> bool {
+        let kind = event.kind.as_u16();
+        match self.dedupe_engine.check_coordinate(event) {
+            AddressableVerdict::NotAddressable | AddressableVerdict::Latest => true,
+            AddressableVerdict::Superseded => {
+                debug!(
+                    "Drop superseded event id={} kind={} author={}",
+                    event.id.to_hex(),
+                    kind,
+                    event.pubkey.to_hex()
+                );
+                if let Some(m) = &self.metrics {
+                    m.addressable_superseded
+                        .with_label_values(&[&kind.to_string()])
+                        .inc();
+                }
+                // Handled: nothing left to deliver, so it must not be replayed
+                if let Err(e) = self.dedupe_engine.mark_forward_success(&event.id).await {
+                    error!(
+                        "Failed to record superseded event {}: {}",
+                        event.id.to_hex(),
+                        e
+                    );
+                }
+                false
+            }
+            AddressableVerdict::SharedCoordinate { first } => {
+                if first {
+                    warn!(
+                        "Author {} reuses d tag {:?} for kind {}; relays keep only the newest such event, so backfills can miss earlier ones. Use a unique d tag per event.",
+                        event.pubkey.to_hex(),
+                        event.tags.identifier().unwrap_or_default(),
+                        kind
+                    );
+                }
+                if let Some(m) = &self.metrics {
+                    m.addressable_shared
+                        .with_label_values(&[&kind.to_string()])
+                        .inc();
+                }
+                true
+            }
+        }
+    }
+
     /// Flush a batch of events sorted by timestamp
     async fn flush_batch(&self) -> Result<()> {
         let mut pending = self.pending_events.write().await;
@@ -189,44 +299,127 @@ impl EventRouter {
         pending.sort();
 
         // Take the oldest events (first batch_size events)
-        let batch: Vec<Event> = pending
-            .drain(0..batch_size)
-            .map(|wrapper| wrapper.event)
-            .collect();
+        let batch: Vec<EventWrapper> = pending.drain(0..batch_size).collect();
 
         drop(pending);
 
         // Send events to downstream in timestamp order
-        for event in batch {
-            if self.is_stale(&event) {
-                debug!(
-                    "Skip stale event id={} kind={} age_secs={}",
-                    event.id.to_hex(),
-                    event.kind.as_u16(),
-                    Timestamp::now()
-                        .as_secs()
-                        .saturating_sub(event.created_at.as_secs())
-                );
-                continue;
+        for wrapper in batch {
+            self.deliver(wrapper).await;
+        }
+
+        debug!("Flushed batch of {} events", batch_size);
+        if let Some(m) = &self.metrics {
+            let remaining = self.pending_events.read().await.len();
+            m.events_in_queue.set(remaining as f64);
+        }
+        Ok(())
+    }
+
+    /// Re-deliver events stored by the dedupe engine but never marked as forwarded,
+    /// e.g. because the process stopped mid-batch or a delivery step failed. Steps
+    /// recorded as completed by the failed attempt are skipped; steps that completed
+    /// right before a crash may run again.
+    async fn replay_unfinished(&self) -> Result<()> {
+        let events = self.dedupe_engine.load_unfinished(REPLAY_LIMIT).await;
+        if events.is_empty() {
+            return Ok(());
+        }
+        info!("Re-delivering {} unfinished events", events.len());
+        if let Some(m) = &self.metrics {
+            m.events_replayed.inc_by(events.len() as u64);
+        }
+        self.pending_events
+            .write()
+            .await
+            .extend(events.into_iter().map(|event| EventWrapper {
+                timestamp: event.created_at.as_secs(),
+                event,
+                replayed: true,
+                backfill: false,
+            }));
+        while !self.pending_events.read().await.is_empty() {
+            self.flush_batch().await?;
+        }
+        Ok(())
+    }
+
+    /// Forward one event downstream and to followers, then record it as forwarded
+    /// if every step succeeded. Otherwise the event stays pending with the steps
+    /// that did complete, and a replay on restart only retries the others.
+    async fn deliver(&self, wrapper: EventWrapper) {
+        let EventWrapper {
+            event,
+            replayed,
+            backfill,
+            ..
+        } = wrapper;
+        let event_id = event.id;
+        let mut delivery = Delivery::default();
+        if replayed {
+            match self.dedupe_engine.completed_deliveries(&event_id).await {
+                Ok(done) => delivery = Delivery::resume(done),
+                Err(e) => warn!(
+                    "Failed to load delivery progress of {}, running every step: {:#}",
+                    event_id.to_hex(),
+                    e
+                ),
             }
+        }
+        // Backfilled and replayed events are expected to be old; only live ones
+        // that aged past the limit while queued are dropped
+        if !replayed && !backfill && self.ingress.is_stale(&event) {
+            debug!(
+                "Skip stale event id={} kind={} age_secs={}",
+                event.id.to_hex(),
+                event.kind.as_u16(),
+                Timestamp::now()
+                    .as_secs()
+                    .saturating_sub(event.created_at.as_secs())
+            );
+        } else {
             self.maybe_update_last_seen(&event).await;
-            if let Err(e) = self.handle_copytrade_fanout(&event).await {
-                error!("Fanout processing failed: {}", e);
+            if let Err(e) = self.handle_copytrade_fanout(&event, &mut delivery).await {
+                delivery.failed("fanout", e);
             }
-            if let Err(e) = self.downstream_tx.send_async(event).await {
-                error!("Failed to send event to downstream: {}", e);
+            if !delivery.duplicate {
+                delivery
+                    .run("downstream".to_string(), || async move {
+                        self.downstream_tx
+                            .send(event)
+                            .await
+                            .map_err(|e| anyhow!("downstream {}", e))
+                    })
+                    .await;
             }
             if let Some(m) = &self.metrics {
                 m.events_processed.inc();
             }
         }
 
-        debug!("Flushed batch of {} events", batch_size);
-        if let Some(m) = &self.metrics {
-            let remaining = self.pending_events.read().await.len();
-            m.events_in_queue.set(remaining as f64);
+        if delivery.is_complete() {
+            if let (Some(semantic), Some(key)) = (&self.semantic_dedupe, delivery.signal.take()) {
+                semantic.record(key);
+            }
+            if let Err(e) = self.dedupe_engine.mark_forward_success(&event_id).await {
+                error!(
+                    "Failed to record forward success for {}: {}",
+                    event_id.to_hex(),
+                    e
+                );
+            }
+        } else if !delivery.completed.is_empty()
+            && let Err(e) = self
+                .dedupe_engine
+                .record_deliveries(&event_id, &delivery.completed)
+                .await
+        {
+            error!(
+                "Failed to record completed deliveries of {}: {}",
+                event_id.to_hex(),
+                e
+            );
         }
-        Ok(())
     }
 
     /// Flush all remaining events
@@ -237,15 +430,10 @@ impl EventRouter {
         // Sort by timestamp before flushing
         pending.sort();
 
-        let events: Vec<Event> = pending.drain(..).map(|wrapper| wrapper.event).collect();
+        let events: Vec<EventWrapper> = pending.drain(..).collect();
 
-        for event in events {
-            if let Err(e) = self.downstream_tx.send_async(event).await {
-                error!("Failed to send event to downstream: {}", e);
-            }
-            if let Some(m) = &self.metrics {
-                m.events_processed.inc();
-            }
+        for wrapper in events {
+            self.deliver(wrapper).await;
         }
 
         info!("Flushed all remaining {} events", count);
@@ -255,7 +443,10 @@ impl EventRouter {
         Ok(())
     }
 
-    async fn handle_copytrade_fanout(&self, event: &Event) -> Result<()> {
+    /// Side effects of a bot event: registration, signal and trade records, and
+    /// follower fanout. Failed steps are tracked in `delivery`; an error means the
+    /// remaining steps could not even be determined.
+    async fn handle_copytrade_fanout(&self, event: &Event, delivery: &mut Delivery) -> Result<()> {
         // Short-circuit only heartbeats: execution reports must be processed for DB writes
         if event.kind.as_u16() == KIND_HEARTBEAT {
             return Ok(());
@@ -307,16 +498,25 @@ impl EventRouter {
                 return Ok(());
             }
 
-            if let Err(e) = subs
-                .register_bot(&bot_pubkey, &nostr_pubkey, &eth_address, &name)
-                .await
-            {
-                error!("Agent register upsert failed for {}: {}", bot_pubkey, e);
-            } else {
-                info!(
-                    "Registered bot via nostr: bot_pubkey={} eth={}",
-                    bot_pubkey, eth_address
-                );
+            delivery
+                .run("register".to_string(), || async {
+                    subs.register_bot(&bot_pubkey, &nostr_pubkey, &eth_address, &name)
+                        .await
+                        .with_context(|| {
+                            format!("Agent register upsert failed for {}", bot_pubkey)
+                        })?;
+                    info!(
+                        "Registered bot via nostr: bot_pubkey={} eth={}",
+                        bot_pubkey, eth_address
+                    );
+                    Ok(())
+                })
+                .await;
+            if let Some(pool) = &self.relay_pool {
+                pool.discover_author_relays(&nostr_pubkey);
+                if let Err(e) = pool.sync_authors(subs).await {
+                    warn!("Failed to update relay author filter: {}", e);
+                }
             }
 
             return Ok(());
@@ -350,7 +550,7 @@ impl EventRouter {
 
         if event.kind.as_u16() == KIND_TRADE_SIGNAL {
             return self
-                .process_trade_signal(event, &plaintext, subs, nostr_keys)
+                .process_trade_signal(event, &plaintext, subs, nostr_keys, delivery)
                 .await;
         }
 
@@ -367,9 +567,11 @@ impl EventRouter {
             preview,
         );
 
-        // Extract agent eth address from JSON payload
-        let agent_eth = extract_agent_eth(&plaintext)
-            .ok_or_else(|| anyhow::anyhow!("agent eth address missing"))?;
+        // Extract agent eth address from JSON payload; retrying cannot add it
+        let Some(agent_eth) = extract_agent_eth(&plaintext) else {
+            error!("agent eth address missing in event {}", event.id.to_hex());
+            return Ok(());
+        };
 
         // Find leader bot by eth address
         let bot = match subs.find_bot_by_eth(&agent_eth).await? {
@@ -380,34 +582,60 @@ impl EventRouter {
             }
         };
 
-        // Followers for this bot
-
         // Persist trade tx info if present in payload
         let event_id = event.id.to_hex();
-        self.maybe_record_trade(subs, &bot.bot_pubkey, &plaintext, &event_id)
+        delivery
+            .run("trade".to_string(), || {
+                self.maybe_record_trade(subs, &bot.bot_pubkey, &plaintext, &event_id)
+            })
             .await;
+
+        // Followers for this bot
         let followers = subs.list_subscriptions(&bot.bot_pubkey).await?;
-        if followers.is_empty() {
-            return Ok(());
-        }
+        self.fan_out(
+            event,
+            &bot.bot_pubkey,
+            &plaintext,
+            &followers,
+            nostr_keys,
+            delivery,
+        )
+        .await;
+        Ok(())
+    }
 
-        // Fanout over WebSocket (plaintext)
+    /// Send a decrypted bot event to each follower over the fanout WebSocket and,
+    /// if a client exists, as a nostr event encrypted to them; every follower and
+    /// channel is its own delivery step
+    async fn fan_out(
+        &self,
+        event: &Event,
+        bot_pubkey: &str,
+        plaintext: &str,
+        followers: &[SubscriptionRow],
+        nostr_keys: &Keys,
+        delivery: &mut Delivery,
+    ) {
         if let Some(fanout_tx) = &self.fanout_tx {
-            for follower in &followers {
+            for follower in followers {
                 let msg = FanoutMessage {
                     target_pubkey: follower.follower_pubkey.clone(),
-                    bot_pubkey: bot.bot_pubkey.clone(),
+                    bot_pubkey: bot_pubkey.to_string(),
                     kind: event.kind.as_u16(),
                     original_event_id: event.id.to_hex(),
-                    payload: plaintext.clone(),
+                    payload: plaintext.to_string(),
                 };
-                if let Err(e) = fanout_tx.send_async(msg).await {
-                    error!("Failed to send fanout ws payload: {}", e);
-                }
+                delivery
+                    .run(format!("ws:{}", follower.follower_pubkey), || async move {
+                        fanout_tx
+                            .send(msg)
+                            .await
+                            .map_err(|e| anyhow!("fanout {}", e))
+                    })
+                    .await;
             }
         }
 
-        // Publish encrypted nostr events to followers if client exists
         if let Some(client) = &self.nostr_client {
             for follower in followers {
                 let follower_pk_str = follower.shared_secret.as_str();
@@ -423,7 +651,7 @@ impl EventRouter {
                 };
 
                 let encrypted =
-                    match nip04::encrypt(nostr_keys.secret_key(), &follower_pk, &plaintext) {
+                    match nip04::encrypt(nostr_keys.secret_key(), &follower_pk, plaintext) {
                         Ok(ct) => ct,
                         Err(e) => {
                             error!("Encrypt for follower {} failed: {}", follower_pk_str, e);
@@ -434,13 +662,16 @@ impl EventRouter {
                 let mut builder = EventBuilder::new(Kind::Custom(event.kind.as_u16()), encrypted);
                 builder = builder.tag(Tag::public_key(follower_pk));
 
-                if let Err(e) = client.send_event_builder(builder).await {
-                    error!("Publish to follower {} failed: {}", follower_pk_str, e);
-                }
+                delivery
+                    .run(format!("nostr:{}", follower_pk_str), || async move {
+                        client.send_event_builder(builder).await.with_context(|| {
+                            format!("Publish to follower {} failed", follower_pk_str)
+                        })?;
+                        Ok(())
+                    })
+                    .await;
             }
         }
-
-        Ok(())
     }
 }
 
@@ -451,8 +682,17 @@ impl EventRouter {
         plaintext: &str,
         subs: &SubscriptionService,
         nostr_keys: &Keys,
+        delivery: &mut Delivery,
     ) -> Result<()> {
         let meta = extract_signal_meta(plaintext);
+        let key = self.signal_key(event, &meta);
+        if let Some(key) = &key
+            && self.is_semantic_duplicate(event, key)
+        {
+            delivery.duplicate = true;
+            return Ok(());
+        }
+        delivery.signal = key;
         let agent_eth = meta.agent_eth_address.clone();
         let event_created_at = to_event_datetime(event);
         let leader_pubkey = event.pubkey.to_hex();
@@ -495,9 +735,13 @@ impl EventRouter {
             event_created_at,
         };
 
-        if let Err(e) = subs.record_signal(signal_insert).await {
-            error!("Failed to record trade signal {}: {}", event.id.to_hex(), e);
-        }
+        delivery
+            .run("signal".to_string(), || async {
+                subs.record_signal(signal_insert)
+                    .await
+                    .with_context(|| format!("Failed to record trade signal {}", event.id.to_hex()))
+            })
+            .await;
 
         let bot = match bot {
             Some(b) => b,
@@ -505,69 +749,61 @@ impl EventRouter {
         };
 
         let event_id = event.id.to_hex();
-        self.maybe_record_trade(subs, &bot.bot_pubkey, plaintext, &event_id)
+        delivery
+            .run("trade".to_string(), || {
+                self.maybe_record_trade(subs, &bot.bot_pubkey, plaintext, &event_id)
+            })
             .await;
         let followers = subs.list_subscriptions(&bot.bot_pubkey).await?;
-        if followers.is_empty() {
-            return Ok(());
-        }
-
-        if let Some(fanout_tx) = &self.fanout_tx {
-            for follower in &followers {
-                let msg = FanoutMessage {
-                    target_pubkey: follower.follower_pubkey.clone(),
-                    bot_pubkey: bot.bot_pubkey.clone(),
-                    kind: event.kind.as_u16(),
-                    original_event_id: event.id.to_hex(),
-                    payload: plaintext.to_string(),
-                };
-                if let Err(e) = fanout_tx.send_async(msg).await {
-                    error!("Failed to send fanout ws payload: {}", e);
-                }
-            }
-        }
-
-        if let Some(client) = &self.nostr_client {
-            for follower in followers {
-                let follower_pk_str = follower.shared_secret.as_str();
-                let follower_pk = match PublicKey::from_str(follower_pk_str) {
-                    Ok(pk) => pk,
-                    Err(e) => {
-                        error!(
-                            "Invalid follower shared_secret pubkey {}: {}",
-                            follower_pk_str, e
-                        );
-                        continue;
-                    }
-                };
-
-                let encrypted =
-                    match nip04::encrypt(nostr_keys.secret_key(), &follower_pk, plaintext) {
-                        Ok(ct) => ct,
-                        Err(e) => {
-                            error!("Encrypt for follower {} failed: {}", follower_pk_str, e);
-                            continue;
-                        }
-                    };
-
-                let mut builder = EventBuilder::new(Kind::Custom(event.kind.as_u16()), encrypted);
-                builder = builder.tag(Tag::public_key(follower_pk));
-
-                if let Err(e) = client.send_event_builder(builder).await {
-                    error!("Publish to follower {} failed: {}", follower_pk_str, e);
-                }
-            }
-        }
-
+        self.fan_out(
+            event,
+            &bot.bot_pubkey,
+            plaintext,
+            &followers,
+            nostr_keys,
+            delivery,
+        )
+        .await;
         Ok(())
     }
 }
 
 impl EventRouter {
-    fn is_stale(&self, event: &Event) -> bool {
-        let now = Timestamp::now().as_secs();
-        let created = event.created_at.as_secs();
-        now.saturating_sub(created) > STALE_AFTER.as_secs()
+    /// Semantic dedupe key of a trade signal, if semantic dedupe is enabled
+    fn signal_key(&self, event: &Event, meta: &SignalMeta) -> Option<SignalKey> {
+        self.semantic_dedupe.as_ref()?;
+        Some(SignalKey::new(
+            event.pubkey.to_hex(),
+            event
+                .tags
+                .find(TagKind::custom("sid"))
+                .and_then(|tag| tag.content())
+                .map(str::to_string),
+            meta.symbol.clone(),
+            meta.side.clone().or_else(|| meta.signal.clone()),
+            meta.size,
+            meta.price,
+            meta.oid.clone().or_else(|| meta.tx_hash.clone()),
+        ))
+    }
+
+    /// Whether a trade signal repeats one already delivered, under a new event id
+    fn is_semantic_duplicate(&self, event: &Event, key: &SignalKey) -> bool {
+        let Some(semantic) = &self.semantic_dedupe else {
+            return false;
+        };
+        if !semantic.is_duplicate(key) {
+            return false;
+        }
+        debug!(
+            "Drop republished trade signal id={} author={}",
+            event.id.to_hex(),
+            event.pubkey.to_hex()
+        );
+        if let Some(m) = &self.metrics {
+            m.semantic_duplicates.inc();
+        }
+        true
     }
 
     async fn maybe_update_last_seen(&self, event: &Event) {
@@ -635,10 +871,13 @@ struct SignalMeta {
     symbol: Option<String>,
     strategy: Option<String>,
     side: Option<String>,
+    /// Trader signal action (`buy`, `sell`, ...), used when `side` is absent
+    signal: Option<String>,
     size: Option<f64>,
     price: Option<f64>,
     status: Option<String>,
     tx_hash: Option<String>,
+    oid: Option<String>,
     pnl: Option<f64>,
     pnl_usd: Option<f64>,
 }
@@ -650,14 +889,15 @@ impl EventRouter {
         bot_pubkey: &str,
         plaintext: &str,
         event_id: &str,
-    ) {
+    ) -> Result<()> {
         let meta = match extract_trade_meta(plaintext) {
             Some(m) => m,
-            None => return,
+            None => return Ok(()),
         };
 
         let oid_fallback = meta.oid.clone().or_else(|| Some(event_id.to_string()));
 
+        let mut result = Ok(());
         if let Err(e) = subs
             .record_trade_tx(
                 bot_pubkey,
@@ -674,7 +914,7 @@ impl EventRouter {
             )
             .await
         {
-            error!("Failed to record trade tx/oid: {}", e);
+            result = Err(e.context("Failed to record trade tx/oid"));
         }
 
         if meta.status.is_some() || meta.pnl.is_some() || meta.pnl_usd.is_some() {
@@ -688,9 +928,10 @@ impl EventRouter {
                 )
                 .await
             {
-                error!("Failed to update settlement for trade: {}", e);
+                result = Err(e.context("Failed to update settlement for trade"));
             }
         }
+        result
     }
 }
 
@@ -803,6 +1044,10 @@ fn extract_signal_meta(plaintext: &str) -> SignalMeta {
         .get("side")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
+    let signal = parsed
+        .get("signal")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
     let size = parsed.get("size").and_then(|v| v.as_f64());
     let price = parsed.get("price").and_then(|v| v.as_f64());
     let status = parsed
@@ -813,6 +1058,11 @@ fn extract_signal_meta(plaintext: &str) -> SignalMeta {
         .get("tx_hash")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
+    let oid = parsed
+        .get("oid")
+        .or_else(|| parsed.get("order_id"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
     let pnl = parsed.get("pnl").and_then(|v| v.as_f64());
     let pnl_usd = parsed.get("pnl_usd").and_then(|v| v.as_f64());
 
@@ -823,15 +1073,88 @@ fn extract_signal_meta(plaintext: &str) -> SignalMeta {
         symbol,
         strategy,
         side,
+        signal,
         size,
         price,
         status,
         tx_hash,
+        oid,
         pnl,
         pnl_usd,
     }
 }
 
+/// Steps of one event's delivery, e.g. `downstream`, `signal` or `ws:{follower}`.
+/// Steps an earlier attempt completed are skipped; only retryable failures leave
+/// the event pending.
+#[derive(Default)]
+struct Delivery {
+    /// Completed by earlier attempts
+    done: HashSet<String>,
+    /// Completed by this attempt, including permanent failures
+    completed: Vec<String>,
+    failures: usize,
+    /// Trade signal key to remember for semantic dedupe once delivered
+    signal: Option<SignalKey>,
+    /// Republished copy of a delivered trade signal; nothing is sent
+    duplicate: bool,
+}
+
+impl Delivery {
+    fn resume(done: HashSet<String>) -> Self {
+        Self {
+            done,
+            ..Default::default()
+        }
+    }
+
+    /// Run `op` as `step` unless an earlier attempt completed it
+    async fn run<F, Fut>(&mut self, step: String, op: F)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        if self.done.contains(&step) {
+            debug!("Skip delivery step {} completed earlier", step);
+            return;
+        }
+        match op().await {
+            Ok(()) => self.completed.push(step),
+            Err(e) => {
+                if self.failed(&step, e) {
+                    self.completed.push(step);
+                }
+            }
+        }
+    }
+
+    /// Count a failure; returns true if it is permanent and must not be retried
+    fn failed(&mut self, step: &str, e: anyhow::Error) -> bool {
+        if is_retryable(&e) {
+            error!("Delivery step {} failed: {:#}", step, e);
+            self.failures += 1;
+            false
+        } else {
+            error!("Delivery step {} failed permanently: {:#}", step, e);
+            true
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.failures == 0
+    }
+}
+
+/// Database errors caused by the data itself (invalid values, constraint
+/// violations) fail again on every retry; anything else may be transient
+fn is_retryable(e: &anyhow::Error) -> bool {
+    let code = e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<tokio_postgres::Error>())
+        .and_then(|e| e.code());
+    !code.is_some_and(|code| matches!(code.code().get(..2), Some("22" | "23")))
+}
+
 fn extract_agent_eth(plaintext: &str) -> Option<String> {
     extract_signal_meta(plaintext).agent_eth_address
 }
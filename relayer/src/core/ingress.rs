@@ -0,0 +1,96 @@
+use nostr_sdk::Event;
+use nostr_sdk::prelude::Timestamp;
+
+use crate::config::IngressConfig;
+use crate::core::event_router::KIND_AGENT_REGISTER;
+
+/// Why an event was refused at ingress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// `id` is not the hash of the serialized event
+    InvalidId,
+    InvalidSignature,
+    /// `created_at` further in the past than `max_past_secs`
+    TooOld,
+    /// `created_at` further in the future than `max_future_secs`
+    TooNew,
+    ContentTooLarge,
+    TooManyTags,
+    /// Agent registration without the required NIP-13 proof-of-work
+    InsufficientPow,
+}
+
+impl Rejection {
+    /// Metric label for the reason
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::InvalidId => "invalid_id",
+            Rejection::InvalidSignature => "invalid_signature",
+            Rejection::TooOld => "too_old",
+            Rejection::TooNew => "too_new",
+            Rejection::ContentTooLarge => "content_too_large",
+            Rejection::TooManyTags => "too_many_tags",
+            Rejection::InsufficientPow => "insufficient_pow",
+        }
+    }
+}
+
+/// Validates events from relays before they reach deduplication
+/// Cheap checks run first, so junk is refused before its signature is verified.
+#[derive(Debug, Clone, Default)]
+pub struct IngressValidator {
+    cfg: IngressConfig,
+}
+
+impl IngressValidator {
+    pub fn new(cfg: IngressConfig) -> Self {
+        Self { cfg }
+    }
+
+    /// Accept or reject a live event, checked against the current time
+    pub fn validate(&self, event: &Event) -> Result<(), Rejection> {
+        if self.is_stale(event) {
+            return Err(Rejection::TooOld);
+        }
+        self.validate_stored(event)
+    }
+
+    /// Accept or reject an event a relay backfilled from its store
+    /// Same checks as `validate`, but catching up after downtime needs older
+    /// events, so the age limit is `max_backfill_secs` instead of `max_past_secs`.
+    pub fn validate_stored(&self, event: &Event) -> Result<(), Rejection> {
+        let now = Timestamp::now().as_secs();
+        let created = event.created_at.as_secs();
+        if now.saturating_sub(created) > self.cfg.max_age_secs() {
+            return Err(Rejection::TooOld);
+        }
+        if created.saturating_sub(now) > self.cfg.max_future_secs {
+            return Err(Rejection::TooNew);
+        }
+        if event.content.len() > self.cfg.max_content_bytes {
+            return Err(Rejection::ContentTooLarge);
+        }
+        if event.tags.len() > self.cfg.max_tags {
+            return Err(Rejection::TooManyTags);
+        }
+        if event.kind.as_u16() == KIND_AGENT_REGISTER
+            && !event.check_pow(self.cfg.register_pow_difficulty)
+        {
+            return Err(Rejection::InsufficientPow);
+        }
+        if !event.verify_id() {
+            return Err(Rejection::InvalidId);
+        }
+        if !event.verify_signature() {
+            return Err(Rejection::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// Whether an event is older than `max_past_secs`, e.g. a live event that
+    /// aged while queued
+    pub fn is_stale(&self, event: &Event) -> bool {
+        let now = Timestamp::now().as_secs();
+        now.saturating_sub(event.created_at.as_secs()) > self.cfg.max_past_secs
+    }
+}
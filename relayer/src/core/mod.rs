@@ -1,5 +1,13 @@
+pub mod archive;
 pub mod dedupe_engine;
 pub mod event_router;
+pub mod ingress;
+pub mod pipeline;
+pub mod relay_discovery;
 pub mod relay_pool;
+pub mod relay_scoring;
+pub mod relay_stats;
+pub mod retention;
+pub mod semantic_dedupe;
 pub mod settlement_worker;
 pub mod subscription;
@@ -0,0 +1,181 @@
+use flume::{Receiver, Sender, TrySendError};
+use nostr_sdk::Event;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::api::metrics::Metrics;
+use crate::config::OverflowPolicy;
+use crate::core::relay_pool::RelayEvent;
+use crate::core::subscription::FanoutMessage;
+
+/// Items flowing through the pipeline expose their nostr kind for `drop_by_kind`
+pub trait PipelineItem {
+    fn kind(&self) -> u16;
+}
+
+impl PipelineItem for Event {
+    fn kind(&self) -> u16 {
+        self.kind.as_u16()
+    }
+}
+
+impl PipelineItem for RelayEvent {
+    fn kind(&self) -> u16 {
+        self.event.kind.as_u16()
+    }
+}
+
+impl PipelineItem for FanoutMessage {
+    fn kind(&self) -> u16 {
+        self.kind
+    }
+}
+
+/// Why a stage did not queue an item
+#[derive(Debug)]
+pub enum StageError<T> {
+    /// The consumer is gone
+    Closed(T),
+    /// The stage is full and its policy drops this item rather than wait
+    Dropped(T),
+}
+
+impl<T> fmt::Display for StageError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StageError::Closed(_) => f.write_str("stage closed"),
+            StageError::Dropped(_) => f.write_str("stage full, item dropped"),
+        }
+    }
+}
+
+/// Sending half of a bounded pipeline stage that applies an overflow policy when full
+pub struct StageSender<T> {
+    stage: &'static str,
+    tx: Sender<T>,
+    /// Receiver handle used to evict the oldest item under `drop_oldest`
+    evict_rx: Option<Receiver<T>>,
+    policy: OverflowPolicy,
+    shed_kinds: Arc<Vec<u16>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl<T> Clone for StageSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            stage: self.stage,
+            tx: self.tx.clone(),
+            evict_rx: self.evict_rx.clone(),
+            policy: self.policy,
+            shed_kinds: self.shed_kinds.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// Create a bounded stage named `stage` (used as the metrics label)
+pub fn stage<T>(
+    stage: &'static str,
+    capacity: usize,
+    policy: OverflowPolicy,
+    shed_kinds: Vec<u16>,
+    metrics: Option<Arc<Metrics>>,
+) -> (StageSender<T>, Receiver<T>) {
+    let (tx, rx) = flume::bounded(capacity.max(1));
+    let evict_rx = (policy == OverflowPolicy::DropOldest).then(|| rx.clone());
+    let sender = StageSender {
+        stage,
+        tx,
+        evict_rx,
+        policy,
+        shed_kinds: Arc::new(shed_kinds),
+        metrics,
+    };
+    (sender, rx)
+}
+
+impl<T: PipelineItem> StageSender<T> {
+    /// Whether the overflow policy allows dropping `item` silently, i.e. its
+    /// kind is in `shed_kinds` and the policy is not `block`
+    pub fn sheds(&self, item: &T) -> bool {
+        self.policy != OverflowPolicy::Block && self.shed_kinds.contains(&item.kind())
+    }
+
+    /// Queue an item, applying the overflow policy if the stage is full.
+    /// Shed kinds count as sent when dropped; any other item the policy drops is
+    /// returned as `Dropped`, so the caller can treat it as undelivered.
+    pub async fn send(&self, item: T) -> Result<(), StageError<T>> {
+        let mut item = item;
+        loop {
+            match self.tx.try_send(item) {
+                Ok(()) => {
+                    self.record_depth();
+                    return Ok(());
+                }
+                Err(TrySendError::Disconnected(item)) => return Err(StageError::Closed(item)),
+                Err(TrySendError::Full(returned)) => item = returned,
+            }
+
+            if self.sheds(&item) {
+                self.record_drop("kind");
+                return Ok(());
+            }
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    // Another sender may have raced us; retry either way
+                    let Some(oldest) = self.evict_rx.as_ref().and_then(|rx| rx.try_recv().ok())
+                    else {
+                        continue;
+                    };
+                    if self.sheds(&oldest) {
+                        self.record_drop("oldest");
+                        continue;
+                    }
+                    // The oldest was already reported as sent, so it is queued again
+                    // (behind newer items) and the new item is refused instead
+                    if let Err(e) = self.send_blocking(oldest).await {
+                        return Err(StageError::Closed(e.into_inner()));
+                    }
+                    self.record_drop("full");
+                    return Err(StageError::Dropped(item));
+                }
+                OverflowPolicy::DropByKind | OverflowPolicy::Block => {
+                    return self
+                        .send_blocking(item)
+                        .await
+                        .map_err(|e| StageError::Closed(e.into_inner()));
+                }
+            }
+        }
+    }
+
+    async fn send_blocking(&self, item: T) -> Result<(), flume::SendError<T>> {
+        let started = Instant::now();
+        let result = self.tx.send_async(item).await;
+        if let Some(m) = &self.metrics {
+            m.pipeline_blocked.with_label_values(&[self.stage]).inc();
+            m.pipeline_blocked_seconds
+                .with_label_values(&[self.stage])
+                .inc_by(started.elapsed().as_secs_f64());
+        }
+        self.record_depth();
+        result
+    }
+
+    fn record_drop(&self, reason: &str) {
+        if let Some(m) = &self.metrics {
+            m.pipeline_dropped
+                .with_label_values(&[self.stage, reason])
+                .inc();
+        }
+    }
+
+    fn record_depth(&self) {
+        if let Some(m) = &self.metrics {
+            m.pipeline_queue_depth
+                .with_label_values(&[self.stage])
+                .set(self.tx.len() as f64);
+        }
+    }
+}
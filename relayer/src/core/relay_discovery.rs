@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use nostr_sdk::nips::nip65::{self, RelayMetadata};
+use nostr_sdk::{Client, Filter, Keys, Kind, PublicKey};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::config::RelayDiscoveryConfig;
+
+/// Looks up NIP-65 relay lists (kind 10002) on a set of index relays
+pub struct RelayDiscovery {
+    client: Client,
+    fetch_timeout: Duration,
+    max_relays_per_author: usize,
+    refresh_interval: Duration,
+    last_fetched: DashMap<PublicKey, Instant>,
+}
+
+impl RelayDiscovery {
+    /// Connect a lookup client to `index_relays`
+    pub async fn new(cfg: &RelayDiscoveryConfig, index_relays: &[String]) -> Result<Self> {
+        let client = Client::new(Keys::generate());
+        for url in index_relays {
+            client
+                .add_relay(url)
+                .await
+                .context(format!("Failed to add index relay: {}", url))?;
+        }
+        client.connect().await;
+
+        Ok(Self {
+            client,
+            fetch_timeout: Duration::from_secs(cfg.fetch_timeout),
+            max_relays_per_author: cfg.max_relays_per_bot,
+            refresh_interval: Duration::from_secs(cfg.refresh_interval),
+            last_fetched: DashMap::new(),
+        })
+    }
+
+    /// Whether the author's relay list is due for a lookup; marks it as looked up
+    pub fn should_fetch(&self, author: &PublicKey) -> bool {
+        let now = Instant::now();
+        let mut due = true;
+        self.last_fetched
+            .entry(*author)
+            .and_modify(|at| {
+                if now.duration_since(*at) < self.refresh_interval {
+                    due = false;
+                } else {
+                    *at = now;
+                }
+            })
+            .or_insert(now);
+        due
+    }
+
+    /// Relays the author publishes to, from their latest NIP-65 relay list
+    pub async fn write_relays(&self, author: PublicKey) -> Result<Vec<String>> {
+        let filter = Filter::new().author(author).kind(Kind::RelayList).limit(1);
+        let events = self
+            .client
+            .fetch_events(filter, self.fetch_timeout)
+            .await
+            .context("Failed to fetch relay list")?;
+
+        let Some(relay_list) = events.into_iter().max_by_key(|e| e.created_at) else {
+            debug!("No NIP-65 relay list found for {}", author);
+            return Ok(Vec::new());
+        };
+
+        // Unmarked entries are both read and write relays
+        let mut urls: Vec<String> = Vec::new();
+        for (url, metadata) in nip65::extract_relay_list(&relay_list) {
+            if matches!(metadata, Some(RelayMetadata::Read)) {
+                continue;
+            }
+            let url = url.as_str().trim_end_matches('/').to_string();
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+            if urls.len() >= self.max_relays_per_author {
+                break;
+            }
+        }
+        Ok(urls)
+    }
+}
@@ -1,13 +1,42 @@
 use crate::api::metrics::Metrics;
+use crate::core::pipeline::{StageError, StageSender};
+use crate::core::relay_discovery::RelayDiscovery;
+use crate::core::relay_stats::{RelayStats, RelayStatsSnapshot};
+use crate::core::subscription::SubscriptionService;
+use crate::storage::rocksdb_store::{RelayRecord, RocksDBStore};
 use anyhow::{Context, Result};
 use dashmap::DashMap;
-use flume::{Receiver, Sender};
-use nostr_sdk::{Client, Event, Filter, Keys, Kind, RelayPoolNotification};
+use nostr_sdk::prelude::{MachineReadablePrefix, SubscribeAutoCloseOptions};
+use nostr_sdk::{
+    Client, ClientMessage, ClientOptions, Event, EventBuilder, EventId, Filter, Keys, Kind,
+    PublicKey, RelayMessage, RelayPoolNotification, RelayPoolOptions, RelayUrl, SubscriptionId,
+    Timestamp,
+};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Arc;
 use std::sync::Arc as StdArc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::{error, info, warn};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, warn};
+
+/// `added_by` of relays that come from `relay.bootstrap_relays`
+const CONFIG_ADDED_BY: &str = "config";
+
+/// Notification buffer per shard client; one buffer is shared by all relays of the shard
+const SHARD_NOTIFICATION_CHANNEL_SIZE: usize = 65_536;
+/// Events buffered per shard in front of the ingest stage, so a full stage
+/// never stalls the shard's notification listener
+const SHARD_EVENT_BUFFER: usize = 16_384;
+/// Longest the shard listener waits on a full buffer for an event the ingest
+/// policy may not shed; after that the event is dropped and refetched
+const SHARD_BLOCK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Pause before offering a refused event to the ingest stage again
+const STAGE_RETRY_DELAY: Duration = Duration::from_millis(10);
 
 /// Connection status for a relay
 #[derive(Debug, Clone, PartialEq)]
@@ -18,42 +47,318 @@ pub enum RelayStatus {
     Error(String),
 }
 
+/// Event tagged with the relay it was received from
+#[derive(Debug, Clone)]
+pub struct RelayEvent {
+    pub relay_url: Arc<str>,
+    pub event: Event,
+    /// Stored event a resumed subscription replayed before EOSE, rather than a live one
+    pub backfill: bool,
+}
+
+/// NIP-42 authentication state of a relay
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayAuth {
+    /// The relay has not sent an AUTH challenge
+    NotRequired,
+    /// AUTH event sent, waiting for the relay's OK
+    Pending(EventId),
+    Authenticated,
+    /// The relay rejected the AUTH event
+    Failed(String),
+    /// The relay asked for AUTH but no `[nostr]` key is configured
+    NoKey,
+}
+
+/// Liveness probing and reconnect backoff settings
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Idle time without any relay traffic before a liveness probe is sent
+    pub liveness_timeout: Duration,
+    /// Time a relay has to answer a liveness probe (or complete a reconnect)
+    pub probe_timeout: Duration,
+    /// Delay before the first reconnect attempt
+    pub base_delay: Duration,
+    /// Upper bound for the exponential backoff
+    pub max_delay: Duration,
+    /// How far before the persisted cursor a resumed subscription starts
+    pub backfill_overlap: Duration,
+    /// How far back a resumed subscription reaches at most, however old the cursor
+    pub max_backfill: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            liveness_timeout: Duration::from_secs(90),
+            probe_timeout: Duration::from_secs(10),
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(300),
+            backfill_overlap: Duration::from_secs(60),
+            max_backfill: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Jittered exponential backoff after `failures` consecutive failures.
+    /// The delay is drawn from `[d/2, d]` where `d = base * 2^(failures - 1)`, capped at `max_delay`.
+    fn backoff(&self, failures: u32) -> Duration {
+        let exp = failures.saturating_sub(1).min(16);
+        let delay = self
+            .base_delay
+            .saturating_mul(1u32 << exp)
+            .min(self.max_delay);
+        let half = delay / 2;
+        let jitter_ms = rand::rng().random_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter_ms)
+    }
+}
+
+/// Liveness bookkeeping for a single relay
+#[derive(Debug, Clone)]
+struct RelayLiveness {
+    connected_at: Instant,
+    last_event_at: Option<Instant>,
+    last_eose_at: Option<Instant>,
+    probe_sent_at: Option<Instant>,
+    consecutive_failures: u32,
+    next_retry_at: Option<Instant>,
+    /// Scheduled resubscribe after the relay CLOSED the stream subscription
+    resubscribe_at: Option<Instant>,
+    /// Consecutive CLOSED messages without a successful backfill in between
+    closed_count: u32,
+}
+
+impl RelayLiveness {
+    fn new() -> Self {
+        Self {
+            connected_at: Instant::now(),
+            last_event_at: None,
+            last_eose_at: None,
+            probe_sent_at: None,
+            consecutive_failures: 0,
+            next_retry_at: None,
+            resubscribe_at: None,
+            closed_count: 0,
+        }
+    }
+
+    /// Most recent sign of life from the relay
+    fn last_activity(&self) -> Instant {
+        [self.last_event_at, self.last_eose_at]
+            .into_iter()
+            .flatten()
+            .fold(self.connected_at, Instant::max)
+    }
+}
+
 /// Connection state for a single relay
 #[derive(Clone)]
 pub struct RelayConnection {
-    url: String,
+    /// Canonical relay URL, also the key in the pool
+    url: Arc<str>,
+    /// Shard client the relay is attached to
     client: Arc<Client>,
+    /// Stream filter shared by all relays; `None` while an author-scoped pool has no authors
+    filter: Arc<RwLock<Option<Filter>>>,
+    subscription_id: SubscriptionId,
+    /// Filter for kinds accepted from any author in an author-scoped pool
+    open_filter: Option<Filter>,
+    open_subscription_id: SubscriptionId,
     status: Arc<RwLock<RelayStatus>>,
-    event_tx: Sender<Event>,
+    liveness: Arc<RwLock<RelayLiveness>>,
+    /// Newest `created_at` handed to the ingest stage from this relay (0 = none yet)
+    cursor: Arc<AtomicU64>,
+    /// `created_at` of the oldest event dropped before the ingest stage since the
+    /// last subscribe (`u64::MAX` = none); the cursor stays below it until a
+    /// resubscribe refetches it
+    gap: Arc<AtomicU64>,
+    /// The current subscription resumed from a cursor, so its stored events
+    /// are a catch-up backfill rather than the relay's whole history
+    resumed: Arc<AtomicBool>,
+    /// Last cursor value written to RocksDB
+    persisted_cursor: Arc<AtomicU64>,
+    auth: Arc<RwLock<RelayAuth>>,
+    stats: Arc<RelayStats>,
+}
+
+impl RelayConnection {
+    /// `filter` resuming from the cursor, minus `overlap` to cover clock skew
+    /// and events relays accept late, but no further back than `max_backfill`.
+    /// Duplicates in the overlap are dropped by dedupe.
+    fn resume_filter(&self, filter: Filter, policy: &ReconnectPolicy) -> Filter {
+        match self.cursor.load(Ordering::Relaxed) {
+            0 => filter,
+            cursor => {
+                let horizon = Timestamp::now()
+                    .as_secs()
+                    .saturating_sub(policy.max_backfill.as_secs());
+                filter.since(Timestamp::from_secs(
+                    cursor
+                        .saturating_sub(policy.backfill_overlap.as_secs())
+                        .max(horizon),
+                ))
+            }
+        }
+    }
+
+    /// Whether an event from this relay is part of a resumed subscription's backfill
+    /// Without a cursor the relay sends its whole history, which is held to the live age limit.
+    fn is_backfill(&self) -> bool {
+        self.resumed.load(Ordering::Relaxed) && self.stats.is_backfilling()
+    }
+
+    /// Issue (or replace) the stream and open subscriptions, resuming from the cursor
+    async fn subscribe(&self, policy: &ReconnectPolicy) -> Result<()> {
+        self.gap.store(u64::MAX, Ordering::Relaxed);
+        self.resumed
+            .store(self.cursor.load(Ordering::Relaxed) > 0, Ordering::Relaxed);
+        let filter = self.filter.read().await.clone();
+        if let Some(filter) = filter {
+            self.client
+                .subscribe_with_id_to(
+                    [&*self.url],
+                    self.subscription_id.clone(),
+                    self.resume_filter(filter, policy),
+                    None,
+                )
+                .await?;
+        }
+        if let Some(open_filter) = &self.open_filter {
+            self.client
+                .subscribe_with_id_to(
+                    [&*self.url],
+                    self.open_subscription_id.clone(),
+                    self.resume_filter(open_filter.clone(), policy),
+                    None,
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Whether `id` is one of this relay's subscriptions (not a liveness probe)
+    fn owns_subscription(&self, id: &SubscriptionId) -> bool {
+        *id == self.subscription_id || *id == self.open_subscription_id
+    }
+
+    /// Advance the cursor, ignoring timestamps from the future and staying
+    /// below any event dropped since the last subscribe
+    fn advance_cursor(&self, created_at: Timestamp) {
+        let ts = created_at
+            .as_secs()
+            .min(Timestamp::now().as_secs())
+            .min(self.gap.load(Ordering::Relaxed).saturating_sub(1));
+        self.cursor.fetch_max(ts, Ordering::Relaxed);
+    }
+
+    /// Move the cursor back before an event that never reached the ingest
+    /// stage, so the next subscribe (or a restart) fetches it again
+    fn rewind_cursor(&self, created_at: Timestamp) {
+        let ts = created_at.as_secs();
+        self.gap.fetch_min(ts, Ordering::Relaxed);
+        self.cursor
+            .fetch_min(ts.saturating_sub(1), Ordering::Relaxed);
+    }
+
+    /// Write the cursor to RocksDB if it changed since the last write
+    async fn persist_cursor(&self, store: &RocksDBStore) {
+        let cursor = self.cursor.load(Ordering::Relaxed);
+        if cursor == self.persisted_cursor.load(Ordering::Relaxed) {
+            return;
+        }
+        match store.set_relay_cursor(&self.url, cursor).await {
+            Ok(()) => self.persisted_cursor.store(cursor, Ordering::Relaxed),
+            Err(e) => warn!("Failed to persist cursor for {}: {}", self.url, e),
+        }
+    }
 }
 
-/// Pool of relay connections with health checking and load balancing
+/// Pool of relay connections with health checking and load balancing.
+/// Relays are multiplexed over a small number of shard clients, each with a single
+/// notification task, instead of one client and task per relay.
 pub struct RelayPool {
     connections: Arc<DashMap<String, RelayConnection>>,
+    /// Relays pruned by scoring, with when discovery and standby promotion may add them again
+    cooldowns: Arc<DashMap<String, Instant>>,
+    shards: Arc<Vec<Arc<Client>>>,
     health_check_interval: Duration,
     max_connections: usize,
-    event_tx: Sender<Event>,
-    allowed_kinds: Option<Vec<u16>>,
+    /// Kinds filter every subscription starts from
+    base_filter: Filter,
+    filter: Arc<RwLock<Option<Filter>>>,
+    open_filter: Option<Filter>,
+    author_scoped: bool,
+    reconnect_policy: ReconnectPolicy,
+    store: Option<Arc<RocksDBStore>>,
+    discovery: Option<Arc<RelayDiscovery>>,
+    /// Key used to answer NIP-42 AUTH challenges
+    auth_keys: Option<Keys>,
+    event_tx: StageSender<RelayEvent>,
+    /// Events buffered per shard in front of the ingest stage
+    shard_buffer: usize,
     metrics: Option<StdArc<Metrics>>,
 }
 
 impl RelayPool {
-    /// Create a new relay pool
+    /// Create a new relay pool with `client_shards` clients whose received events are fed
+    /// into `event_tx` once the pool is started.
     pub fn new(
         health_check_interval: Duration,
         max_connections: usize,
+        client_shards: usize,
         allowed_kinds: Option<Vec<u16>>,
-    ) -> (Self, Receiver<Event>) {
-        let (tx, rx) = flume::unbounded();
-        let pool = Self {
-            connections: Arc::new(DashMap::new()),
+        event_tx: StageSender<RelayEvent>,
+    ) -> Self {
+        let connections = Arc::new(DashMap::new());
+        let shards: Vec<Arc<Client>> = (0..client_shards.max(1))
+            .map(|_| {
+                // AUTH challenges are answered by the shard listener with `auth_keys`
+                // (set after `pool()`, which would otherwise reset the flag)
+                let opts = ClientOptions::new()
+                    .pool(
+                        RelayPoolOptions::new()
+                            .notification_channel_size(SHARD_NOTIFICATION_CHANNEL_SIZE),
+                    )
+                    .automatic_authentication(false);
+                Arc::new(
+                    Client::builder()
+                        .signer(Keys::generate())
+                        .opts(opts)
+                        .build(),
+                )
+            })
+            .collect();
+
+        // Subscribe using allowed kinds if provided, otherwise subscribe to all events
+        let base_filter = match &allowed_kinds {
+            Some(kinds) if !kinds.is_empty() => {
+                let kinds: Vec<Kind> = kinds.iter().map(|k| Kind::Custom(*k)).collect();
+                Filter::new().kinds(kinds)
+            }
+            _ => Filter::new(),
+        };
+
+        Self {
+            connections,
+            cooldowns: Arc::new(DashMap::new()),
+            shards: Arc::new(shards),
             health_check_interval,
             max_connections,
-            event_tx: tx,
-            allowed_kinds,
+            filter: Arc::new(RwLock::new(Some(base_filter.clone()))),
+            base_filter,
+            open_filter: None,
+            author_scoped: false,
+            reconnect_policy: ReconnectPolicy::default(),
+            store: None,
+            discovery: None,
+            auth_keys: None,
+            event_tx,
+            shard_buffer: SHARD_EVENT_BUFFER,
             metrics: None,
-        };
-        (pool, rx)
+        }
     }
 
     pub fn with_metrics(mut self, metrics: StdArc<Metrics>) -> Self {
@@ -61,8 +366,84 @@ impl RelayPool {
         self
     }
 
+    /// Override liveness probing and reconnect backoff settings
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Persist per-relay cursors (so reconnects and restarts backfill the gap with `since`)
+    /// and the relay set
+    pub fn with_store(mut self, store: Arc<RocksDBStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// The RocksDB store relay state is persisted in; none on the memory backend
+    pub fn store(&self) -> Option<&Arc<RocksDBStore>> {
+        self.store.as_ref()
+    }
+
+    /// Follow registered bots to the relays listed in their NIP-65 relay lists
+    pub fn with_discovery(mut self, discovery: RelayDiscovery) -> Self {
+        self.discovery = Some(Arc::new(discovery));
+        self
+    }
+
+    /// Only stream events authored by the pubkeys passed to `set_authors`.
+    /// `open_kinds` are still subscribed from any author.
+    pub fn with_author_scope(mut self, open_kinds: Vec<u16>) -> Self {
+        self.author_scoped = true;
+        self.filter = Arc::new(RwLock::new(None));
+        self.open_filter = (!open_kinds.is_empty())
+            .then(|| Filter::new().kinds(open_kinds.into_iter().map(Kind::Custom)));
+        self
+    }
+
+    /// Events buffered per shard in front of the ingest stage
+    pub fn with_shard_buffer(mut self, capacity: usize) -> Self {
+        self.shard_buffer = capacity.max(1);
+        self
+    }
+
+    /// Answer NIP-42 AUTH challenges with the platform key
+    pub fn with_auth_keys(mut self, keys: Keys) -> Self {
+        self.auth_keys = Some(keys);
+        self
+    }
+
+    /// Start the shard notification tasks and health checking.
+    /// Must be called from within a Tokio runtime, before relays are added.
+    pub async fn start(&self) {
+        for (index, client) in self.shards.iter().enumerate() {
+            let (shard_tx, shard_rx) = flume::bounded(self.shard_buffer);
+            tokio::spawn(Self::forward_shard_events(
+                self.event_tx.clone(),
+                index,
+                shard_rx,
+            ));
+            tokio::spawn(Self::handle_shard_events(
+                self.clone(),
+                index,
+                client.clone(),
+                shard_tx,
+                self.event_tx.clone(),
+            ));
+        }
+        self.start_health_checks().await;
+    }
+
+    /// Shard client responsible for a relay, chosen by URL hash
+    fn shard_for(&self, relay_url: &str) -> Arc<Client> {
+        let mut hasher = DefaultHasher::new();
+        relay_url.hash(&mut hasher);
+        self.shards[hasher.finish() as usize % self.shards.len()].clone()
+    }
+
     /// Connect to a relay and subscribe to events
     pub async fn connect_and_subscribe(&self, relay_url: String) -> Result<()> {
+        let relay_url = relay_key(&relay_url)?;
+
         if self.connections.len() >= self.max_connections {
             warn!("Max connections reached, skipping {}", relay_url);
             return Ok(());
@@ -75,46 +456,58 @@ impl RelayPool {
 
         info!("Connecting to relay: {}", relay_url);
 
-        let client = Client::new(Keys::generate());
+        let client = self.shard_for(&relay_url);
 
-        // Add relay to client
+        // Add relay to the shard client and connect in the background
         client
             .add_relay(&relay_url)
             .await
             .context(format!("Failed to add relay: {}", relay_url))?;
-
-        // Connect to relay
-        // Connect to relay (no error returned in this version)
-        client.connect().await;
-
-        let status = Arc::new(RwLock::new(RelayStatus::Connected));
-        let event_tx = self.event_tx.clone();
-
-        // Subscribe using allowed kinds if provided, otherwise subscribe to all events
-        let filter = match &self.allowed_kinds {
-            Some(kinds) if !kinds.is_empty() => {
-                let kinds: Vec<Kind> = kinds.iter().map(|k| Kind::Custom(*k)).collect();
-                Filter::new().kinds(kinds)
-            }
-            _ => Filter::new(),
-        };
         client
-            .subscribe(filter, None)
+            .connect_relay(&relay_url)
             .await
-            .context("Failed to subscribe to relay")?;
+            .context(format!("Failed to connect relay: {}", relay_url))?;
+
+        // Resume from the persisted cursor so events published while we were away are backfilled
+        let cursor = match &self.store {
+            Some(store) => store.get_relay_cursor(&relay_url).await.unwrap_or(0),
+            None => 0,
+        };
 
         let connection = RelayConnection {
-            url: relay_url.clone(),
-            client: Arc::new(client),
-            status: status.clone(),
-            event_tx: event_tx.clone(),
+            url: Arc::from(relay_url.as_str()),
+            client,
+            filter: self.filter.clone(),
+            subscription_id: SubscriptionId::generate(),
+            open_filter: self.open_filter.clone(),
+            open_subscription_id: SubscriptionId::generate(),
+            status: Arc::new(RwLock::new(RelayStatus::Connected)),
+            liveness: Arc::new(RwLock::new(RelayLiveness::new())),
+            cursor: Arc::new(AtomicU64::new(cursor)),
+            gap: Arc::new(AtomicU64::new(u64::MAX)),
+            resumed: Arc::new(AtomicBool::new(false)),
+            persisted_cursor: Arc::new(AtomicU64::new(cursor)),
+            auth: Arc::new(RwLock::new(RelayAuth::NotRequired)),
+            stats: Arc::new(RelayStats::new(&relay_url, self.metrics.clone())),
         };
 
+        if cursor > 0 {
+            info!(
+                "Resuming {} from cursor {} (overlap {}s)",
+                relay_url,
+                cursor,
+                self.reconnect_policy.backfill_overlap.as_secs()
+            );
+        }
+
+        // Register before subscribing so the shard listener can route the first events
         self.connections
             .insert(relay_url.clone(), connection.clone());
-
-        // Spawn task to handle events from this relay
-        tokio::spawn(Self::handle_relay_events(connection, event_tx));
+        if let Err(e) = connection.subscribe(&self.reconnect_policy).await {
+            self.connections.remove(&relay_url);
+            let _ = connection.client.force_remove_relay(&relay_url).await;
+            return Err(e).context("Failed to subscribe to relay");
+        }
 
         info!(
             "Successfully connected and subscribed to relay: {}",
@@ -123,28 +516,295 @@ impl RelayPool {
         Ok(())
     }
 
-    /// Handle events from a single relay connection
-    async fn handle_relay_events(connection: RelayConnection, event_tx: Sender<Event>) {
-        let mut notifications = connection.client.notifications();
+    /// Move the events of one shard into the ingest stage; waiting on a full
+    /// stage (under `block`) only backs up the shard buffer
+    async fn forward_shard_events(
+        event_tx: StageSender<RelayEvent>,
+        shard: usize,
+        events: flume::Receiver<RelayEvent>,
+    ) {
+        while let Ok(mut relay_event) = events.recv_async().await {
+            // The cursor already moved past this event, so it is retried rather than lost
+            loop {
+                match event_tx.send(relay_event).await {
+                    Ok(()) => break,
+                    Err(StageError::Dropped(refused)) => {
+                        relay_event = refused;
+                        tokio::time::sleep(STAGE_RETRY_DELAY).await;
+                    }
+                    Err(StageError::Closed(_)) => {
+                        info!("Ingest stage closed, stopping shard {} forwarder", shard);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Route notifications of one shard client to the relays attached to it.
+    /// Events go to the shard buffer; when it is full, those the ingest policy may
+    /// shed are dropped at once and others wait up to `SHARD_BLOCK_TIMEOUT`, so
+    /// one slow stage cannot lag the notification stream of every relay on the
+    /// shard for long. The cursor only moves past events that were handed off;
+    /// a dropped one rewinds it and schedules a resubscribe to fetch it again.
+    async fn handle_shard_events(
+        pool: RelayPool,
+        shard: usize,
+        client: Arc<Client>,
+        events: flume::Sender<RelayEvent>,
+        ingest: StageSender<RelayEvent>,
+    ) {
+        let mut notifications = client.notifications();
+
+        loop {
+            let notification = match notifications.recv().await {
+                Ok(n) => n,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Notification stream for shard {} lagged, skipped {} messages",
+                        shard, skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
 
-        while let Ok(notification) = notifications.recv().await {
-            match notification {
-                RelayPoolNotification::Event { event, .. } => {
-                    if let Err(e) = event_tx.send_async(*event).await {
-                        error!("Failed to send event to pipeline: {}", e);
-                        break;
+            let (relay_url, message) = match notification {
+                RelayPoolNotification::Message { relay_url, message } => (relay_url, message),
+                // Only emitted for the first relay delivering an event; per-relay
+                // accounting uses the `Message` variant instead
+                RelayPoolNotification::Event { .. } => continue,
+                RelayPoolNotification::Shutdown => break,
+            };
+            let Some(connection) = pool
+                .connections
+                .get(relay_url.as_str_without_trailing_slash())
+                .map(|entry| entry.value().clone())
+            else {
+                continue;
+            };
+
+            match message {
+                RelayMessage::Event { event, .. } => {
+                    connection.liveness.write().await.last_event_at = Some(Instant::now());
+                    connection.stats.record_event();
+                    let (event_id, created_at) = (event.id, event.created_at);
+                    let relay_event = RelayEvent {
+                        relay_url: connection.url.clone(),
+                        event: event.into_owned(),
+                        backfill: connection.is_backfill(),
+                    };
+                    let sheds = ingest.sheds(&relay_event);
+                    let handed_off = match events.try_send(relay_event) {
+                        Ok(()) => true,
+                        Err(flume::TrySendError::Full(_)) if sheds => false,
+                        Err(flume::TrySendError::Full(relay_event)) => {
+                            match tokio::time::timeout(
+                                SHARD_BLOCK_TIMEOUT,
+                                events.send_async(relay_event),
+                            )
+                            .await
+                            {
+                                Ok(Ok(())) => true,
+                                Ok(Err(_)) => {
+                                    info!("Ingest stage closed, stopping shard {} listener", shard);
+                                    break;
+                                }
+                                Err(_) => false,
+                            }
+                        }
+                        Err(flume::TrySendError::Disconnected(_)) => {
+                            info!("Ingest stage closed, stopping shard {} listener", shard);
+                            break;
+                        }
+                    };
+                    if handed_off {
+                        connection.advance_cursor(created_at);
+                        continue;
+                    }
+
+                    debug!(
+                        "Shard {} buffer full, dropping event {} from {}",
+                        shard, event_id, connection.url
+                    );
+                    if let Some(m) = &pool.metrics {
+                        m.pipeline_dropped
+                            .with_label_values(&["relay_shard", "full"])
+                            .inc();
+                    }
+                    // Shed kinds are dropped by policy; anything else is fetched again
+                    if !sheds {
+                        connection.rewind_cursor(created_at);
+                        let mut liveness = connection.liveness.write().await;
+                        liveness
+                            .resubscribe_at
+                            .get_or_insert(Instant::now() + pool.reconnect_policy.base_delay);
+                    }
+                }
+                RelayMessage::EndOfStoredEvents(subscription_id) => {
+                    let mut liveness = connection.liveness.write().await;
+                    liveness.last_eose_at = Some(Instant::now());
+                    // Probes share the EOSE path but do not mark the backfill as done
+                    if connection.owns_subscription(&subscription_id) {
+                        liveness.closed_count = 0;
+                        connection.stats.record_backfill_complete();
+                        debug!("Backfill from {} complete", connection.url);
+                    }
+                }
+                RelayMessage::Notice(notice) => {
+                    warn!("NOTICE from {}: {}", connection.url, notice);
+                    connection.stats.record_message("notice", &notice).await;
+                }
+                RelayMessage::Closed {
+                    subscription_id,
+                    message,
+                } => {
+                    if !connection.owns_subscription(&subscription_id) {
+                        debug!(
+                            "CLOSED from {} for {}: {}",
+                            connection.url, subscription_id, message
+                        );
+                        continue;
                     }
+                    warn!("CLOSED from {}: {}", connection.url, message);
+                    connection.stats.record_message("closed", &message).await;
+                    pool.handle_closed(&connection, &message).await;
+                }
+                RelayMessage::Auth { challenge } => {
+                    pool.authenticate(&connection, &relay_url, &challenge).await;
                 }
-                RelayPoolNotification::Message { message, .. } => {
-                    // Handle other message types if needed
-                    info!("Received message from {}: {:?}", connection.url, message);
+                RelayMessage::Ok {
+                    event_id,
+                    status,
+                    message,
+                } => {
+                    pool.handle_auth_ok(&connection, event_id, status, &message)
+                        .await;
+                }
+                other => {
+                    debug!("Received message from {}: {:?}", connection.url, other);
                 }
-                _ => {}
             }
         }
 
-        warn!("Event stream ended for relay: {}", connection.url);
-        *connection.status.write().await = RelayStatus::Disconnected;
+        warn!("Event stream ended for shard {}", shard);
+        let attached: Vec<RelayConnection> = pool
+            .connections
+            .iter()
+            .filter(|entry| Arc::ptr_eq(&entry.value().client, &client))
+            .map(|entry| entry.value().clone())
+            .collect();
+        for connection in attached {
+            *connection.status.write().await = RelayStatus::Disconnected;
+        }
+    }
+
+    /// React to the relay closing the stream subscription
+    async fn handle_closed(&self, connection: &RelayConnection, message: &str) {
+        let authenticated = *connection.auth.read().await == RelayAuth::Authenticated;
+        match MachineReadablePrefix::parse(message) {
+            // Resubscribed once the AUTH challenge is answered
+            Some(MachineReadablePrefix::AuthRequired) if !authenticated => {}
+            Some(MachineReadablePrefix::AuthRequired) => {
+                Self::resubscribe(connection, &self.reconnect_policy).await;
+            }
+            _ => {
+                let delay = {
+                    let mut liveness = connection.liveness.write().await;
+                    liveness.closed_count += 1;
+                    let delay = self.reconnect_policy.backoff(liveness.closed_count);
+                    liveness.resubscribe_at = Some(Instant::now() + delay);
+                    delay
+                };
+                info!(
+                    "Resubscribing to {} in {:.1}s",
+                    connection.url,
+                    delay.as_secs_f64()
+                );
+            }
+        }
+    }
+
+    /// Answer a NIP-42 AUTH challenge with the platform key
+    async fn authenticate(
+        &self,
+        connection: &RelayConnection,
+        relay_url: &RelayUrl,
+        challenge: &str,
+    ) {
+        let Some(keys) = &self.auth_keys else {
+            warn!(
+                "Relay {} requires AUTH but no [nostr] key is configured",
+                connection.url
+            );
+            *connection.auth.write().await = RelayAuth::NoKey;
+            connection
+                .stats
+                .record_message("auth", "challenge received without a configured key")
+                .await;
+            return;
+        };
+
+        let event = match EventBuilder::auth(challenge, relay_url.clone()).sign_with_keys(keys) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Failed to sign AUTH event for {}: {}", connection.url, e);
+                return;
+            }
+        };
+        let event_id = event.id;
+        let sent = match connection.client.relay(relay_url).await {
+            Ok(relay) => relay
+                .send_msg(ClientMessage::auth(event))
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        match sent {
+            Ok(()) => {
+                debug!("Sent AUTH to {}", connection.url);
+                *connection.auth.write().await = RelayAuth::Pending(event_id);
+            }
+            Err(e) => warn!("Failed to send AUTH to {}: {}", connection.url, e),
+        }
+    }
+
+    /// Complete a pending AUTH; OKs for other events are ignored
+    async fn handle_auth_ok(
+        &self,
+        connection: &RelayConnection,
+        event_id: EventId,
+        accepted: bool,
+        message: &str,
+    ) {
+        {
+            let mut auth = connection.auth.write().await;
+            if *auth != RelayAuth::Pending(event_id) {
+                return;
+            }
+            *auth = if accepted {
+                RelayAuth::Authenticated
+            } else {
+                RelayAuth::Failed(message.to_string())
+            };
+        }
+
+        if accepted {
+            info!("Authenticated to relay {}", connection.url);
+            // Relays drop or refuse subscriptions made before AUTH
+            Self::resubscribe(connection, &self.reconnect_policy).await;
+        } else {
+            warn!("AUTH rejected by {}: {}", connection.url, message);
+            connection.stats.record_message("auth", message).await;
+        }
+    }
+
+    /// Re-issue the stream subscription, resuming from the cursor
+    async fn resubscribe(connection: &RelayConnection, policy: &ReconnectPolicy) {
+        connection.liveness.write().await.resubscribe_at = None;
+        connection.stats.record_backfill_started();
+        if let Err(e) = connection.subscribe(policy).await {
+            warn!("Failed to resubscribe to {}: {}", connection.url, e);
+        }
     }
 
     /// Connect to multiple relays in parallel
@@ -165,10 +825,96 @@ impl RelayPool {
         Ok(())
     }
 
+    /// Connect to the persisted relay set merged with the configured bootstrap relays
+    pub async fn restore_relays(&self, bootstrap: Vec<String>) -> Result<()> {
+        let bootstrap: Vec<String> = bootstrap
+            .iter()
+            .filter_map(|url| match relay_key(url) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    error!("Skipping bootstrap relay: {}", e);
+                    None
+                }
+            })
+            .collect();
+        let mut urls = bootstrap.clone();
+        if let Some(store) = &self.store {
+            let now = Timestamp::now().as_secs();
+            for record in store.load_relay_records().await {
+                // Relays dropped from the config are no longer part of the set
+                if record.added_by == CONFIG_ADDED_BY && !bootstrap.contains(&record.url) {
+                    store.delete_relay_record(&record.url).await?;
+                    continue;
+                }
+                if !urls.contains(&record.url) {
+                    urls.push(record.url);
+                }
+            }
+            for url in &bootstrap {
+                store
+                    .put_relay_record(&RelayRecord {
+                        url: url.clone(),
+                        added_by: CONFIG_ADDED_BY.to_string(),
+                        added_at: now,
+                    })
+                    .await?;
+            }
+        }
+        info!(
+            "Restoring {} relays ({} from config)",
+            urls.len(),
+            bootstrap.len()
+        );
+        self.subscribe_all(urls).await
+    }
+
+    /// Connect to a relay and add it to the persisted relay set
+    pub async fn add_relay(&self, relay_url: String, added_by: &str) -> Result<()> {
+        let relay_url = relay_key(&relay_url)?;
+        self.connect_and_subscribe(relay_url.clone()).await?;
+        if let Some(store) = &self.store
+            && self.connections.contains_key(&relay_url)
+        {
+            store
+                .put_relay_record(&RelayRecord {
+                    url: relay_url,
+                    added_by: added_by.to_string(),
+                    added_at: Timestamp::now().as_secs(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Disconnect a relay and drop it from the persisted relay set
+    pub async fn remove_relay(&self, relay_url: &str) -> Result<()> {
+        let relay_url = relay_key(relay_url)?;
+        self.disconnect_relay(&relay_url).await?;
+        if let Some(store) = &self.store {
+            store.delete_relay_record(&relay_url).await?;
+        }
+        Ok(())
+    }
+
+    /// Persisted relay set keyed by URL
+    pub async fn relay_records(&self) -> HashMap<String, RelayRecord> {
+        match &self.store {
+            Some(store) => store
+                .load_relay_records()
+                .await
+                .into_iter()
+                .map(|record| (record.url.clone(), record))
+                .collect(),
+            None => HashMap::new(),
+        }
+    }
+
     /// Start health checking for all connections
-    pub async fn start_health_checks(&self) {
+    async fn start_health_checks(&self) {
         let connections = self.connections.clone();
         let interval = self.health_check_interval;
+        let policy = self.reconnect_policy.clone();
+        let store = self.store.clone();
 
         let metrics = self.metrics.clone();
         tokio::spawn(async move {
@@ -176,75 +922,399 @@ impl RelayPool {
             loop {
                 interval_timer.tick().await;
 
-                for entry in connections.iter() {
-                    let connection = entry.value();
-                    let current_status = connection.status.read().await.clone();
+                // Snapshot connections so no DashMap guard is held across probes
+                let snapshot: Vec<RelayConnection> =
+                    connections.iter().map(|e| e.value().clone()).collect();
+                futures::future::join_all(
+                    snapshot
+                        .iter()
+                        .map(|connection| Self::check_connection(connection, &policy)),
+                )
+                .await;
 
-                    // Check if connection is still alive by trying to get status
-                    // In a real implementation, you might want to ping the relay
-                    if current_status == RelayStatus::Disconnected {
-                        warn!(
-                            "Relay {} is disconnected, attempting reconnect",
-                            connection.url
-                        );
-                        // Attempt reconnection
-                        connection.client.connect().await;
-                        *connection.status.write().await = RelayStatus::Connected;
+                if let Some(store) = &store {
+                    for connection in &snapshot {
+                        connection.persist_cursor(store).await;
+                    }
+                }
+
+                let mut connected = 0usize;
+                for connection in &snapshot {
+                    let healthy = *connection.status.read().await == RelayStatus::Connected;
+                    connection.stats.record_health_check(healthy);
+                    if healthy {
+                        connected += 1;
                     }
                 }
                 if let Some(m) = &metrics {
-                    m.active_connections.set(connections.len() as f64);
+                    m.active_connections.set(connected as f64);
                 }
             }
         });
     }
 
+    /// Run one liveness check for a relay, reconnecting with backoff if it is unhealthy
+    async fn check_connection(connection: &RelayConnection, policy: &ReconnectPolicy) {
+        let current_status = connection.status.read().await.clone();
+        match current_status {
+            RelayStatus::Connecting => {}
+            RelayStatus::Connected => {
+                let transport_connected = match connection.client.relay(&*connection.url).await {
+                    Ok(relay) => relay.is_connected(),
+                    Err(_) => false,
+                };
+                // The transport connects in the background; give it a probe window
+                let connecting =
+                    connection.liveness.read().await.connected_at.elapsed() < policy.probe_timeout;
+                if !transport_connected && !connecting {
+                    Self::record_failure(connection, policy, "transport disconnected".into()).await;
+                    return;
+                }
+
+                let now = Instant::now();
+                let (last_activity, probe_sent_at, resubscribe_at) = {
+                    let liveness = connection.liveness.read().await;
+                    (
+                        liveness.last_activity(),
+                        liveness.probe_sent_at,
+                        liveness.resubscribe_at,
+                    )
+                };
+                if resubscribe_at.is_some_and(|at| now >= at) {
+                    Self::resubscribe(connection, policy).await;
+                }
+
+                match probe_sent_at {
+                    // Relay answered (or sent anything) after the probe went out
+                    Some(sent) if last_activity >= sent => {
+                        connection.liveness.write().await.probe_sent_at = None;
+                    }
+                    Some(sent) if now.duration_since(sent) >= policy.probe_timeout => {
+                        Self::record_failure(
+                            connection,
+                            policy,
+                            format!(
+                                "no response to liveness probe within {}s",
+                                policy.probe_timeout.as_secs()
+                            ),
+                        )
+                        .await;
+                    }
+                    Some(_) => {}
+                    None if now.duration_since(last_activity) >= policy.liveness_timeout => {
+                        Self::send_probe(connection).await;
+                    }
+                    None => {}
+                }
+            }
+            RelayStatus::Disconnected | RelayStatus::Error(_) => {
+                let next_retry_at = connection.liveness.read().await.next_retry_at;
+                match next_retry_at {
+                    None => {
+                        // Listener ended on its own; schedule the first retry
+                        Self::record_failure(connection, policy, "event stream ended".into()).await;
+                    }
+                    Some(at) if Instant::now() >= at => {
+                        Self::reconnect(connection, policy).await;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    /// Send a `limit: 0` REQ that compliant relays answer with an immediate EOSE
+    async fn send_probe(connection: &RelayConnection) {
+        let filter = connection.filter.read().await.clone();
+        let probe = filter
+            .or_else(|| connection.open_filter.clone())
+            .unwrap_or_default()
+            .limit(0);
+        let opts = SubscribeAutoCloseOptions::default().timeout(Some(Duration::from_secs(30)));
+        connection.liveness.write().await.probe_sent_at = Some(Instant::now());
+        debug!("Sending liveness probe to {}", connection.url);
+        if let Err(e) = connection
+            .client
+            .subscribe_to([&*connection.url], probe, Some(opts))
+            .await
+        {
+            debug!("Liveness probe to {} failed to send: {}", connection.url, e);
+        }
+    }
+
+    /// Mark a relay as failed and schedule the next reconnect attempt
+    async fn record_failure(
+        connection: &RelayConnection,
+        policy: &ReconnectPolicy,
+        reason: String,
+    ) {
+        let delay = {
+            let mut liveness = connection.liveness.write().await;
+            liveness.consecutive_failures += 1;
+            liveness.probe_sent_at = None;
+            let delay = policy.backoff(liveness.consecutive_failures);
+            liveness.next_retry_at = Some(Instant::now() + delay);
+            delay
+        };
+        warn!(
+            "Relay {} unhealthy ({}), reconnecting in {:.1}s",
+            connection.url,
+            reason,
+            delay.as_secs_f64()
+        );
+        *connection.status.write().await = RelayStatus::Error(reason);
+    }
+
+    /// Reconnect a relay, re-issue its subscription and restart its notification task
+    async fn reconnect(connection: &RelayConnection, policy: &ReconnectPolicy) {
+        *connection.status.write().await = RelayStatus::Connecting;
+        info!("Reconnecting to relay {}", connection.url);
+
+        let _ = connection.client.disconnect_relay(&*connection.url).await;
+        // A new connection gets a new AUTH challenge
+        *connection.auth.write().await = RelayAuth::NotRequired;
+        if let Err(e) = connection
+            .client
+            .try_connect_relay(&*connection.url, policy.probe_timeout)
+            .await
+        {
+            Self::record_failure(connection, policy, format!("reconnect failed: {}", e)).await;
+            return;
+        }
+
+        // Re-use the subscription ids so the relay replaces rather than duplicates them,
+        // and resume from the cursor so the outage window is backfilled
+        if let Err(e) = connection.subscribe(policy).await {
+            Self::record_failure(connection, policy, format!("resubscribe failed: {}", e)).await;
+            return;
+        }
+
+        connection.stats.record_reconnect();
+        connection.stats.record_backfill_started();
+        *connection.liveness.write().await = RelayLiveness::new();
+        *connection.status.write().await = RelayStatus::Connected;
+        info!("Relay {} reconnected", connection.url);
+    }
+
     /// Get the number of active connections
     pub fn active_connections(&self) -> usize {
         self.connections.len()
     }
 
-    /// Get connection status for all relays
-    pub async fn get_connection_statuses(&self) -> Vec<(String, RelayStatus)> {
-        let mut statuses = Vec::new();
-        for entry in self.connections.iter() {
-            let status = entry.value().status.read().await.clone();
-            statuses.push((entry.key().clone(), status));
+    /// Get status and ingestion statistics for all relays
+    pub async fn get_relay_stats(&self) -> Vec<(String, RelayStatus, RelayStatsSnapshot)> {
+        let connections: Vec<RelayConnection> = self
+            .connections
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        let mut stats = Vec::with_capacity(connections.len());
+        for connection in connections {
+            let status = connection.status.read().await.clone();
+            let mut snapshot = connection.stats.snapshot().await;
+            if let Ok(relay) = connection.client.relay(&*connection.url).await {
+                snapshot.latency_ms = relay.stats().latency().map(|d| d.as_millis() as u64);
+            }
+            snapshot.auth = connection.auth.read().await.clone();
+            stats.push((connection.url.to_string(), status, snapshot));
+        }
+        stats
+    }
+
+    /// Maximum number of relay connections
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Whether a relay is currently part of the pool
+    pub fn contains_relay(&self, relay_url: &str) -> bool {
+        relay_key(relay_url)
+            .map(|key| self.connections.contains_key(&key))
+            .unwrap_or(false)
+    }
+
+    /// Keep discovery and standby promotion from adding a relay back for `cooldown`
+    pub fn cool_down_relay(&self, relay_url: &str, cooldown: Duration) {
+        if let Ok(key) = relay_key(relay_url) {
+            self.cooldowns.insert(key, Instant::now() + cooldown);
+        }
+    }
+
+    /// Whether a relay was pruned recently and should not be added automatically
+    pub fn is_cooling_down(&self, relay_url: &str) -> bool {
+        let Ok(key) = relay_key(relay_url) else {
+            return false;
+        };
+        let now = Instant::now();
+        self.cooldowns.retain(|_, until| *until > now);
+        self.cooldowns.contains_key(&key)
+    }
+
+    /// Publish the latest score computed by the relay scorer
+    pub fn set_relay_score(&self, relay_url: &str, score: f64) {
+        if let Some(connection) = self.connections.get(relay_url) {
+            connection.stats.set_score(score);
+        }
+    }
+
+    /// Replace the authors of an author-scoped pool and update the live subscriptions.
+    /// No-op for unscoped pools or when the author set is unchanged.
+    pub async fn set_authors(&self, authors: BTreeSet<PublicKey>) {
+        if !self.author_scoped {
+            return;
+        }
+        let count = authors.len();
+        let filter = (!authors.is_empty()).then(|| self.base_filter.clone().authors(authors));
+        {
+            let mut current = self.filter.write().await;
+            if *current == filter {
+                return;
+            }
+            *current = filter.clone();
+        }
+        info!("Author filter updated to {} pubkeys", count);
+        if let Some(m) = &self.metrics {
+            m.subscribed_authors.set(count as f64);
+        }
+
+        let connections: Vec<RelayConnection> = self
+            .connections
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        for connection in connections {
+            if filter.is_some() {
+                Self::resubscribe(&connection, &self.reconnect_policy).await;
+                continue;
+            }
+            // Nobody left to follow; an empty `authors` list is not portable across relays
+            if let Ok(relay) = connection.client.relay(&*connection.url).await
+                && let Err(e) = relay.unsubscribe(&connection.subscription_id).await
+            {
+                warn!("Failed to unsubscribe from {}: {}", connection.url, e);
+            }
+        }
+    }
+
+    /// Scope subscriptions to registered bots' nostr pubkeys plus their followers
+    pub async fn sync_authors(&self, subscriptions: &SubscriptionService) -> Result<()> {
+        if !self.author_scoped {
+            return Ok(());
+        }
+        let mut authors = BTreeSet::new();
+        for pubkey in subscriptions.list_author_pubkeys().await? {
+            match PublicKey::parse(&pubkey) {
+                Ok(pk) => {
+                    authors.insert(pk);
+                }
+                Err(e) => debug!("Skipping invalid author pubkey {}: {}", pubkey, e),
+            }
+        }
+        self.set_authors(authors).await;
+        Ok(())
+    }
+
+    /// Subscribe to the NIP-65 write relays of `author` in the background.
+    /// No-op when discovery is disabled or the author was looked up recently.
+    pub fn discover_author_relays(&self, author: &str) {
+        let Some(discovery) = self.discovery.clone() else {
+            return;
+        };
+        let author = match PublicKey::parse(author) {
+            Ok(pk) => pk,
+            Err(e) => {
+                warn!(
+                    "Skipping relay discovery for invalid pubkey {}: {}",
+                    author, e
+                );
+                return;
+            }
+        };
+        if !discovery.should_fetch(&author) {
+            return;
+        }
+
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let urls = match discovery.write_relays(author).await {
+                Ok(urls) => urls,
+                Err(e) => {
+                    warn!("Relay discovery failed for {}: {}", author, e);
+                    return;
+                }
+            };
+            for url in urls {
+                if pool.contains_relay(&url) || pool.is_cooling_down(&url) {
+                    continue;
+                }
+                let added_by = format!("nip65:{}", author.to_hex());
+                if let Err(e) = pool.add_relay(url.clone(), &added_by).await {
+                    warn!("Failed to add discovered relay {}: {}", url, e);
+                    continue;
+                }
+                if pool.contains_relay(&url) {
+                    info!("Subscribed to relay {} from NIP-65 list of {}", url, author);
+                    if let Some(m) = &pool.metrics {
+                        m.relays_discovered.inc();
+                    }
+                }
+            }
+        });
+    }
+
+    /// Record the dedupe verdict for an event delivered by `relay_url`
+    pub fn record_dedupe_result(&self, relay_url: &str, duplicate: bool) {
+        if let Some(connection) = self.connections.get(relay_url) {
+            connection.stats.record_dedupe(duplicate);
         }
-        statuses
     }
 
     /// Disconnect and remove a relay
     pub async fn disconnect_relay(&self, relay_url: &str) -> Result<()> {
-        if let Some((_, connection)) = self.connections.remove(relay_url) {
+        let relay_url = relay_key(relay_url)?;
+        if let Some((_, connection)) = self.connections.remove(&relay_url) {
             *connection.status.write().await = RelayStatus::Disconnected;
-            // Note: The client will be dropped when the connection is removed
-            // The handle_relay_events task will naturally terminate
+            if let Err(e) = connection.client.force_remove_relay(&relay_url).await {
+                warn!("Failed to remove relay {} from client: {}", relay_url, e);
+            }
+            connection.stats.remove_metrics();
+            if let Some(store) = &self.store {
+                connection.persist_cursor(store).await;
+            }
             info!("Disconnected and removed relay: {}", relay_url);
             Ok(())
         } else {
             anyhow::bail!("Relay {} not found", relay_url)
         }
     }
-
-    /// Get list of all relay URLs
-    pub fn list_relays(&self) -> Vec<String> {
-        self.connections
-            .iter()
-            .map(|entry| entry.key().clone())
-            .collect()
-    }
 }
 
 impl Clone for RelayPool {
     fn clone(&self) -> Self {
         Self {
             connections: self.connections.clone(),
+            cooldowns: self.cooldowns.clone(),
+            shards: self.shards.clone(),
             health_check_interval: self.health_check_interval,
             max_connections: self.max_connections,
+            base_filter: self.base_filter.clone(),
+            filter: self.filter.clone(),
+            open_filter: self.open_filter.clone(),
+            author_scoped: self.author_scoped,
+            reconnect_policy: self.reconnect_policy.clone(),
+            store: self.store.clone(),
+            discovery: self.discovery.clone(),
+            auth_keys: self.auth_keys.clone(),
             event_tx: self.event_tx.clone(),
-            allowed_kinds: self.allowed_kinds.clone(),
+            shard_buffer: self.shard_buffer,
             metrics: self.metrics.clone(),
         }
     }
 }
+
+/// Canonical pool key for a relay URL, so `wss://relay.example.com/` and
+/// `wss://relay.example.com` map to the same relay
+pub(crate) fn relay_key(relay_url: &str) -> Result<String> {
+    let url = RelayUrl::parse(relay_url).context(format!("Invalid relay URL: {}", relay_url))?;
+    Ok(url.as_str_without_trailing_slash().to_string())
+}
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use crate::api::metrics::Metrics;
+use crate::config::RelayScoringConfig;
+use crate::core::relay_pool::{RelayPool, relay_key};
+use crate::core::relay_stats::RelayStatsSnapshot;
+
+const WEIGHT_UNIQUENESS: f64 = 0.6;
+const WEIGHT_UPTIME: f64 = 0.25;
+const WEIGHT_LATENCY: f64 = 0.15;
+/// Latency at which the latency component drops to 0.5
+const LATENCY_HALF_SCORE_MS: f64 = 500.0;
+/// Smoothing factor for the per-round unique event contribution
+const CONTRIBUTION_ALPHA: f64 = 0.3;
+
+/// Periodically scores relays, prunes chronically useless ones and promotes standby relays
+pub struct RelayScorer {
+    pool: Arc<RelayPool>,
+    cfg: RelayScoringConfig,
+    protected: HashSet<String>,
+    metrics: Option<Arc<Metrics>>,
+    first_seen: HashMap<String, Instant>,
+    last_unique: HashMap<String, u64>,
+    contribution: HashMap<String, f64>,
+    low_rounds: HashMap<String, u32>,
+}
+
+impl RelayScorer {
+    /// `protected` relays are scored but never pruned
+    pub fn new(pool: Arc<RelayPool>, cfg: RelayScoringConfig, protected: Vec<String>) -> Self {
+        Self {
+            pool,
+            cfg,
+            protected: protected.iter().map(|url| normalize(url)).collect(),
+            metrics: None,
+            first_seen: HashMap::new(),
+            last_unique: HashMap::new(),
+            contribution: HashMap::new(),
+            low_rounds: HashMap::new(),
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub async fn run(mut self) {
+        let interval = Duration::from_secs(self.cfg.interval_secs);
+        loop {
+            sleep(interval).await;
+            self.tick().await;
+        }
+    }
+
+    /// Run one scoring round: score, prune and promote
+    pub async fn tick(&mut self) {
+        let stats = self.pool.get_relay_stats().await;
+        let now = Instant::now();
+
+        // Forget relays that left the pool (pruned or removed via the API)
+        let current: HashSet<&str> = stats.iter().map(|(url, _, _)| url.as_str()).collect();
+        self.first_seen
+            .retain(|url, _| current.contains(url.as_str()));
+        self.last_unique
+            .retain(|url, _| current.contains(url.as_str()));
+        self.contribution
+            .retain(|url, _| current.contains(url.as_str()));
+        self.low_rounds
+            .retain(|url, _| current.contains(url.as_str()));
+
+        // Smoothed number of first-seen events each relay contributed per round
+        for (url, _, snapshot) in &stats {
+            self.first_seen.entry(url.clone()).or_insert(now);
+            let previous = self
+                .last_unique
+                .insert(url.clone(), snapshot.unique_events)
+                .unwrap_or(snapshot.unique_events);
+            let delta = snapshot.unique_events.saturating_sub(previous) as f64;
+            let ewma = self.contribution.entry(url.clone()).or_insert(delta);
+            *ewma = CONTRIBUTION_ALPHA * delta + (1.0 - CONTRIBUTION_ALPHA) * *ewma;
+        }
+        let max_contribution = self.contribution.values().cloned().fold(0.0, f64::max);
+
+        let mut prune = Vec::new();
+        for (url, _, snapshot) in &stats {
+            let contribution = self.contribution.get(url).copied().unwrap_or(0.0);
+            let uniqueness = if max_contribution > 0.0 {
+                contribution / max_contribution
+            } else {
+                0.0
+            };
+            let score = relay_score(snapshot, uniqueness);
+            self.pool.set_relay_score(url, score);
+            debug!("Relay {} scored {:.3}", url, score);
+
+            // Without any unique traffic in the pool there is nothing to compare against
+            let eligible = max_contribution > 0.0
+                && !self.protected.contains(url)
+                && self
+                    .first_seen
+                    .get(url)
+                    .map(|t| now.duration_since(*t).as_secs() >= self.cfg.min_age_secs)
+                    .unwrap_or(false);
+            let rounds = self.low_rounds.entry(url.clone()).or_insert(0);
+            // A relay that is always up still adds nothing if every event it
+            // delivers arrived first elsewhere
+            let useless = score < self.cfg.min_score || uniqueness < self.cfg.min_uniqueness;
+            if eligible && useless {
+                *rounds += 1;
+                if *rounds >= self.cfg.prune_after_rounds {
+                    prune.push((url.clone(), score));
+                }
+            } else {
+                *rounds = 0;
+            }
+        }
+
+        // Lowest scores go first; keep at least `min_relays` connected
+        prune.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let cooldown = Duration::from_secs(self.cfg.prune_cooldown_secs);
+        for (url, score) in prune {
+            if self.pool.active_connections() <= self.cfg.min_relays {
+                break;
+            }
+            match self.pool.remove_relay(&url).await {
+                Ok(()) => {
+                    info!("Pruned relay {} (score {:.3})", url, score);
+                    self.pool.cool_down_relay(&url, cooldown);
+                    if let Some(m) = &self.metrics {
+                        m.relays_pruned.inc();
+                    }
+                }
+                Err(e) => warn!("Failed to prune relay {}: {}", url, e),
+            }
+        }
+
+        self.promote_standby(cooldown).await;
+    }
+
+    /// Connect standby relays while the pool is below `max_connections`
+    async fn promote_standby(&mut self, cooldown: Duration) {
+        let free = self
+            .pool
+            .max_connections()
+            .saturating_sub(self.pool.active_connections());
+        let candidates: Vec<String> = self
+            .cfg
+            .standby_relays
+            .iter()
+            .map(|url| normalize(url))
+            .filter(|url| !self.pool.contains_relay(url) && !self.pool.is_cooling_down(url))
+            .take(free.min(self.cfg.promote_per_round))
+            .collect();
+
+        for url in candidates {
+            match self.pool.add_relay(url.clone(), "standby").await {
+                Ok(()) => {
+                    info!("Promoted standby relay {}", url);
+                    if let Some(m) = &self.metrics {
+                        m.relays_promoted.inc();
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to promote standby relay {}: {}", url, e);
+                    // Treat like a prune so a broken candidate is not retried every round
+                    self.pool.cool_down_relay(&url, cooldown);
+                }
+            }
+        }
+    }
+}
+
+/// Pool key of a configured relay URL, so it matches the URLs in the relay stats;
+/// invalid URLs are kept as they are and fail when connected
+fn normalize(url: &str) -> String {
+    relay_key(url).unwrap_or_else(|_| url.to_string())
+}
+
+/// Weighted score in `[0, 1]` from relative contribution (`uniqueness`), uptime and latency
+fn relay_score(snapshot: &RelayStatsSnapshot, uniqueness: f64) -> f64 {
+    let latency = match snapshot.latency_ms {
+        Some(ms) => 1.0 / (1.0 + ms as f64 / LATENCY_HALF_SCORE_MS),
+        None => 0.5,
+    };
+    WEIGHT_UNIQUENESS * uniqueness
+        + WEIGHT_UPTIME * snapshot.uptime_ratio
+        + WEIGHT_LATENCY * latency
+}
@@ -0,0 +1,239 @@
+use crate::api::metrics::Metrics;
+use crate::core::relay_pool::RelayAuth;
+use nostr_sdk::Timestamp;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::RwLock;
+
+/// Number of NOTICE/CLOSED/AUTH messages kept per relay
+const MAX_RECENT_MESSAGES: usize = 20;
+
+/// A NOTICE, CLOSED or AUTH message received from (or about) a relay
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayMessageEntry {
+    pub kind: &'static str,
+    pub message: String,
+    pub received_at: u64,
+}
+
+/// Ingestion counters for a single relay
+pub struct RelayStats {
+    relay_url: String,
+    events_received: AtomicU64,
+    unique_events: AtomicU64,
+    duplicate_events: AtomicU64,
+    reconnects: AtomicU64,
+    last_event_at: AtomicU64,
+    health_checks: AtomicU64,
+    healthy_checks: AtomicU64,
+    /// When the relay last sent EOSE for the stream subscription (0 = backfill in progress)
+    backfill_completed_at: AtomicU64,
+    /// Latest score from the relay scorer, stored as `f64` bits (`u64::MAX` = unscored)
+    score: AtomicU64,
+    recent_messages: RwLock<VecDeque<RelayMessageEntry>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl RelayStats {
+    pub fn new(relay_url: &str, metrics: Option<Arc<Metrics>>) -> Self {
+        Self {
+            relay_url: relay_url.to_string(),
+            events_received: AtomicU64::new(0),
+            unique_events: AtomicU64::new(0),
+            duplicate_events: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            last_event_at: AtomicU64::new(0),
+            health_checks: AtomicU64::new(0),
+            healthy_checks: AtomicU64::new(0),
+            backfill_completed_at: AtomicU64::new(0),
+            score: AtomicU64::new(u64::MAX),
+            recent_messages: RwLock::new(VecDeque::with_capacity(MAX_RECENT_MESSAGES)),
+            metrics,
+        }
+    }
+
+    /// Count an event delivered by this relay
+    pub fn record_event(&self) {
+        let now = Timestamp::now().as_secs();
+        self.events_received.fetch_add(1, Ordering::Relaxed);
+        self.last_event_at.store(now, Ordering::Relaxed);
+        if let Some(m) = &self.metrics {
+            m.relay_events_received
+                .with_label_values(&[&self.relay_url])
+                .inc();
+            m.relay_last_event_timestamp
+                .with_label_values(&[&self.relay_url])
+                .set(now as f64);
+        }
+    }
+
+    /// Record the dedupe verdict for an event from this relay.
+    /// A unique event means this relay won the race to deliver it first.
+    pub fn record_dedupe(&self, duplicate: bool) {
+        if duplicate {
+            self.duplicate_events.fetch_add(1, Ordering::Relaxed);
+            if let Some(m) = &self.metrics {
+                m.relay_duplicate_events
+                    .with_label_values(&[&self.relay_url])
+                    .inc();
+            }
+        } else {
+            self.unique_events.fetch_add(1, Ordering::Relaxed);
+            if let Some(m) = &self.metrics {
+                m.relay_unique_events
+                    .with_label_values(&[&self.relay_url])
+                    .inc();
+            }
+        }
+    }
+
+    pub fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+        if let Some(m) = &self.metrics {
+            m.relay_reconnects
+                .with_label_values(&[&self.relay_url])
+                .inc();
+        }
+    }
+
+    /// Record the outcome of a periodic health check, used for uptime
+    pub fn record_health_check(&self, healthy: bool) {
+        self.health_checks.fetch_add(1, Ordering::Relaxed);
+        if healthy {
+            self.healthy_checks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The stream subscription was (re)issued; stored events are being replayed
+    pub fn record_backfill_started(&self) {
+        self.backfill_completed_at.store(0, Ordering::Relaxed);
+    }
+
+    /// Whether stored events are still being replayed (no EOSE since the last subscribe)
+    pub fn is_backfilling(&self) -> bool {
+        self.backfill_completed_at.load(Ordering::Relaxed) == 0
+    }
+
+    /// The relay sent EOSE for the stream subscription
+    pub fn record_backfill_complete(&self) {
+        self.backfill_completed_at
+            .store(Timestamp::now().as_secs(), Ordering::Relaxed);
+    }
+
+    pub fn set_score(&self, score: f64) {
+        self.score.store(score.to_bits(), Ordering::Relaxed);
+        if let Some(m) = &self.metrics {
+            m.relay_score
+                .with_label_values(&[&self.relay_url])
+                .set(score);
+        }
+    }
+
+    /// Keep a NOTICE, CLOSED or AUTH message for the status API
+    pub async fn record_message(&self, kind: &'static str, message: &str) {
+        if let Some(m) = &self.metrics {
+            m.relay_messages
+                .with_label_values(&[self.relay_url.as_str(), kind])
+                .inc();
+        }
+        let mut recent = self.recent_messages.write().await;
+        if recent.len() >= MAX_RECENT_MESSAGES {
+            recent.pop_front();
+        }
+        recent.push_back(RelayMessageEntry {
+            kind,
+            message: message.to_string(),
+            received_at: Timestamp::now().as_secs(),
+        });
+    }
+
+    pub async fn snapshot(&self) -> RelayStatsSnapshot {
+        let events_received = self.events_received.load(Ordering::Relaxed);
+        let unique_events = self.unique_events.load(Ordering::Relaxed);
+        let duplicate_events = self.duplicate_events.load(Ordering::Relaxed);
+        let judged = unique_events + duplicate_events;
+        let health_checks = self.health_checks.load(Ordering::Relaxed);
+        let recent_messages: Vec<RelayMessageEntry> =
+            self.recent_messages.read().await.iter().cloned().collect();
+        RelayStatsSnapshot {
+            events_received,
+            unique_events,
+            duplicate_events,
+            first_seen_ratio: if judged > 0 {
+                unique_events as f64 / judged as f64
+            } else {
+                0.0
+            },
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            last_event_at: match self.last_event_at.load(Ordering::Relaxed) {
+                0 => None,
+                ts => Some(ts),
+            },
+            uptime_ratio: if health_checks > 0 {
+                self.healthy_checks.load(Ordering::Relaxed) as f64 / health_checks as f64
+            } else {
+                1.0
+            },
+            latency_ms: None,
+            backfill_completed_at: match self.backfill_completed_at.load(Ordering::Relaxed) {
+                0 => None,
+                ts => Some(ts),
+            },
+            auth: RelayAuth::NotRequired,
+            last_notice: recent_messages
+                .iter()
+                .rev()
+                .find(|entry| entry.kind == "notice")
+                .map(|entry| entry.message.clone()),
+            score: match self.score.load(Ordering::Relaxed) {
+                u64::MAX => None,
+                bits => Some(f64::from_bits(bits)),
+            },
+            recent_messages,
+        }
+    }
+
+    /// Drop the labeled Prometheus series for this relay
+    pub fn remove_metrics(&self) {
+        if let Some(m) = &self.metrics {
+            let label = [self.relay_url.as_str()];
+            let _ = m.relay_events_received.remove_label_values(&label);
+            let _ = m.relay_unique_events.remove_label_values(&label);
+            let _ = m.relay_duplicate_events.remove_label_values(&label);
+            let _ = m.relay_reconnects.remove_label_values(&label);
+            let _ = m.relay_last_event_timestamp.remove_label_values(&label);
+            let _ = m.relay_score.remove_label_values(&label);
+            for kind in ["notice", "closed", "auth"] {
+                let _ = m
+                    .relay_messages
+                    .remove_label_values(&[self.relay_url.as_str(), kind]);
+            }
+        }
+    }
+}
+
+/// Point-in-time view of a relay's ingestion counters
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayStatsSnapshot {
+    pub events_received: u64,
+    /// Events this relay delivered first (first-seen wins)
+    pub unique_events: u64,
+    /// Events another relay had already delivered
+    pub duplicate_events: u64,
+    pub first_seen_ratio: f64,
+    pub reconnects: u64,
+    pub last_event_at: Option<u64>,
+    /// Share of health checks that found the relay connected
+    pub uptime_ratio: f64,
+    /// Round-trip latency measured by the nostr client's pings
+    pub latency_ms: Option<u64>,
+    /// When the current subscription finished replaying stored events (EOSE)
+    pub backfill_completed_at: Option<u64>,
+    /// NIP-42 authentication state
+    pub auth: RelayAuth,
+    pub last_notice: Option<String>,
+    pub score: Option<f64>,
+    pub recent_messages: Vec<RelayMessageEntry>,
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::api::metrics::Metrics;
+use crate::storage::rocksdb_store::{PruneStats, RocksDBStore};
+
+/// Events deleted per RocksDB write batch
+const PRUNE_BATCH: usize = 1_000;
+
+/// Periodically deletes archived events whose kind's retention has ended
+/// (see `RocksDBStore::with_retention`)
+pub struct RetentionPruner {
+    rocksdb: Arc<RocksDBStore>,
+    interval: Duration,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl RetentionPruner {
+    pub fn new(rocksdb: Arc<RocksDBStore>, interval: Duration) -> Self {
+        Self {
+            rocksdb,
+            interval,
+            metrics: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub async fn run(self) {
+        loop {
+            let now = chrono::Utc::now().timestamp().max(0) as u64;
+            match self.prune(now).await {
+                Ok(stats) if stats.events > 0 => info!(
+                    "Retention pruned {} events ({} bytes)",
+                    stats.events, stats.bytes
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("retention pruning failed: {}", e),
+            }
+            sleep(self.interval).await;
+        }
+    }
+
+    /// Delete every event expired at `now` (unix seconds), in batches
+    pub async fn prune(&self, now: u64) -> Result<PruneStats> {
+        let mut total = PruneStats::default();
+        loop {
+            let stats = self.rocksdb.prune_expired(now, PRUNE_BATCH).await?;
+            if let Some(metrics) = &self.metrics {
+                metrics.retention_pruned_events.inc_by(stats.events);
+                metrics.retention_reclaimed_bytes.inc_by(stats.bytes);
+            }
+            total.events += stats.events;
+            total.bytes += stats.bytes;
+            if (stats.events as usize) < PRUNE_BATCH {
+                return Ok(total);
+            }
+        }
+    }
+}
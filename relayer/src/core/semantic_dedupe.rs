@@ -0,0 +1,124 @@
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What identifies a trade signal independently of the event carrying it
+/// A bot republishing a signal with a new `created_at` gets a new event id but
+/// the same key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SignalKey {
+    author: String,
+    sid: Option<String>,
+    symbol: Option<String>,
+    side: Option<String>,
+    /// Raw `f64` bits, so the key stays `Eq` and `Hash`
+    size: Option<u64>,
+    price: Option<u64>,
+    /// Order id or transaction hash
+    order_ref: Option<String>,
+}
+
+impl SignalKey {
+    pub fn new(
+        author: String,
+        sid: Option<String>,
+        symbol: Option<String>,
+        side: Option<String>,
+        size: Option<f64>,
+        price: Option<f64>,
+        order_ref: Option<String>,
+    ) -> Self {
+        Self {
+            author,
+            sid,
+            symbol,
+            side,
+            size: size.map(f64::to_bits),
+            price: price.map(f64::to_bits),
+            order_ref,
+        }
+    }
+
+    /// Whether the key says anything about the trade; payloads that cannot be
+    /// parsed would otherwise all collapse into one key per author
+    fn is_identifying(&self) -> bool {
+        self.symbol.is_some() || self.order_ref.is_some()
+    }
+}
+
+/// Second dedupe stage for decrypted trade signals
+/// Remembers each delivered [`SignalKey`] for `window`, so a republished signal
+/// is not fanned out or recorded again. Keys are only remembered once delivered,
+/// so a failed delivery can still be retried.
+pub struct SemanticDedupe {
+    window: Duration,
+    seen: DashMap<SignalKey, Instant>,
+    last_prune: Mutex<Instant>,
+}
+
+impl SemanticDedupe {
+    /// Treat repeats of a signal within `window` of its first sighting as duplicates
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: DashMap::new(),
+            last_prune: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Whether the same signal was delivered within the window
+    pub fn is_duplicate(&self, key: &SignalKey) -> bool {
+        if !key.is_identifying() {
+            return false;
+        }
+        let now = Instant::now();
+        self.prune(now);
+        self.seen
+            .get(key)
+            .is_some_and(|delivered| now.duration_since(*delivered) < self.window)
+    }
+
+    /// Remember a delivered signal; repeats within the window are duplicates
+    pub fn record(&self, key: SignalKey) {
+        if !key.is_identifying() {
+            return;
+        }
+        let now = Instant::now();
+        match self.seen.entry(key) {
+            // Keep the first delivery, so the window does not slide with repeats
+            Entry::Occupied(mut entry) => {
+                if now.duration_since(*entry.get()) >= self.window {
+                    entry.insert(now);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(now);
+            }
+        }
+    }
+
+    /// Signals currently remembered
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Whether no signals are remembered
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Forget expired signals, at most once per window
+    fn prune(&self, now: Instant) {
+        let Ok(mut last_prune) = self.last_prune.try_lock() else {
+            return;
+        };
+        if now.duration_since(*last_prune) < self.window {
+            return;
+        }
+        *last_prune = now;
+        drop(last_prune);
+        self.seen
+            .retain(|_, first_seen| now.duration_since(*first_seen) < self.window);
+    }
+}
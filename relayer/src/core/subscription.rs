@@ -470,6 +470,31 @@ impl SubscriptionService {
         Ok(())
     }
 
+    /// Delete a bot together with its subscriptions; returns whether it existed
+    pub async fn remove_bot(&self, bot_pubkey: &str) -> Result<bool> {
+        let client = self.pool.get().await.context("Failed to get PG client")?;
+        let removed = client
+            .execute("DELETE FROM bots WHERE bot_pubkey = $1", &[&bot_pubkey])
+            .await
+            .context("Failed to delete bot")?;
+        self.invalidate_dashboard_summary_cache().await;
+        Ok(removed > 0)
+    }
+
+    /// Nostr pubkeys of all registered bots and their followers
+    pub async fn list_author_pubkeys(&self) -> Result<Vec<String>> {
+        let client = self.pool.get().await.context("Failed to get PG client")?;
+        let rows = client
+            .query(
+                "SELECT nostr_pubkey FROM bots WHERE nostr_pubkey <> ''
+                 UNION SELECT follower_pubkey FROM subscriptions",
+                &[],
+            )
+            .await
+            .context("Failed to query author pubkeys")?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
     /// Add or update a subscription for a follower
     pub async fn add_subscription(
         &self,
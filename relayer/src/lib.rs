@@ -0,0 +1,7 @@
+//! Moltrade relayer: ingests nostr events from a relay pool, deduplicates them and routes
+//! them to downstream consumers and subscribed followers.
+
+pub mod api;
+pub mod config;
+pub mod core;
+pub mod storage;
@@ -1,25 +1,36 @@
-mod api;
-mod config;
-mod core;
-mod storage;
-
 use anyhow::{Context, Result};
-use api::{metrics::Metrics, rest_api, websocket};
-use clap::Parser;
-use config::AppConfig;
-use core::{
-    dedupe_engine::DeduplicationEngine, event_router::EventRouter, relay_pool::RelayPool,
-    settlement_worker::SettlementWorker, subscription::FanoutMessage,
+use clap::{Parser, Subcommand};
+use flume::Receiver;
+use moltrade_relayer::api::{metrics::Metrics, rest_api, websocket};
+use moltrade_relayer::config::{
+    AppConfig, IngressConfig, RetentionConfig, StorageBackend, default_latest_wins_kinds,
+};
+use moltrade_relayer::core::{
+    archive,
+    dedupe_engine::DeduplicationEngine,
+    event_router::EventRouter,
+    ingress::IngressValidator,
+    pipeline,
+    relay_discovery::RelayDiscovery,
+    relay_pool::{ReconnectPolicy, RelayPool},
+    relay_scoring::RelayScorer,
+    retention::RetentionPruner,
+    settlement_worker::SettlementWorker,
+    subscription::FanoutMessage,
     subscription::SubscriptionService,
 };
-use flume::Receiver;
+use moltrade_relayer::storage::{
+    bloom_filter::BloomFilter, event_index::EventQuery, event_store::EventStore,
+    memory_store::MemoryEventStore, rocksdb_store::RocksDBStore,
+};
 use nostr_sdk::Event;
+use nostr_sdk::PublicKey;
 use nostr_sdk::ToBech32;
 use nostr_sdk::prelude::{Client, Keys};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use storage::rocksdb_store::RocksDBStore;
 use tokio::signal;
 use tower_http::cors::CorsLayer;
 use tracing::{error, info, warn};
@@ -30,8 +41,58 @@ use tracing_subscriber;
 #[command(about = "Moltrade Relayer service", version)]
 struct Cli {
     /// Path to configuration TOML file
-    #[arg(long)]
+    #[arg(long, global = true)]
     config: Option<std::path::PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write a RocksDB checkpoint of `deduplication.rocksdb_path`
+    Backup {
+        /// Checkpoint directory to create (default: a timestamped one in the backup dir)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// REST base URL of a running relayer, which then writes the checkpoint
+        /// to its backup dir (e.g. http://127.0.0.1:8080)
+        #[arg(long)]
+        server: Option<String>,
+    },
+    /// Verify a checkpoint and restore it to `deduplication.rocksdb_path`
+    /// (the relayer must be stopped)
+    Restore {
+        /// Checkpoint directory
+        checkpoint: PathBuf,
+        /// Only run the integrity check
+        #[arg(long)]
+        verify_only: bool,
+    },
+    /// Export archived events as NIP-01 JSONL (the relayer must be stopped;
+    /// a running one serves GET /api/admin/export)
+    Export {
+        /// JSONL file to write
+        #[arg(long)]
+        out: PathBuf,
+        /// Only these kinds (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        kinds: Vec<u16>,
+        /// Only these authors, hex or npub (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        authors: Vec<String>,
+        /// Only events created at or after this unix time
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only events created at or before this unix time
+        #[arg(long)]
+        until: Option<u64>,
+    },
+    /// Import NIP-01 JSONL events, verifying signatures and skipping stored ones
+    /// (the relayer must be stopped; a running one serves POST /api/admin/import)
+    Import {
+        /// JSONL file to read
+        input: PathBuf,
+    },
 }
 
 #[tokio::main]
@@ -45,28 +106,123 @@ async fn main() -> Result<()> {
     // Initialize tracing - prefer config log level if provided, else env, else default
     init_tracing(&cfg);
 
+    match cli.command {
+        Some(Command::Backup { out, server }) => return backup(&cfg, out, server).await,
+        Some(Command::Restore {
+            checkpoint,
+            verify_only,
+        }) => return restore(&cfg, &checkpoint, verify_only).await,
+        Some(Command::Export {
+            out,
+            kinds,
+            authors,
+            since,
+            until,
+        }) => {
+            let authors = authors
+                .iter()
+                .map(|a| PublicKey::parse(a).map(|pk| pk.to_hex()))
+                .collect::<Result<Vec<_>, _>>()
+                .context("Invalid --authors")?;
+            let filter = EventQuery {
+                authors,
+                kinds,
+                since,
+                until,
+                ..Default::default()
+            };
+            return export(&cfg, &out, filter).await;
+        }
+        Some(Command::Import { input }) => return import(&cfg, &input).await,
+        None => {}
+    }
+
     info!("Starting Moltrade Relayer...");
 
     // Initialize metrics
     let metrics = Arc::new(Metrics::new().context("Failed to initialize metrics")?);
 
     // Initialize RocksDB storage
-    let rocksdb = init_rocksdb(&cfg)?;
-    info!("RocksDB storage initialized");
+    let backend = cfg
+        .as_ref()
+        .map(|c| c.deduplication.storage)
+        .unwrap_or_default();
+    let rocksdb = match backend {
+        StorageBackend::Rocksdb => {
+            let rocksdb = init_rocksdb(&cfg)?;
+            info!("RocksDB storage initialized");
+            Some(rocksdb)
+        }
+        StorageBackend::Memory => {
+            warn!("Using in-memory event storage; nothing is kept across restarts");
+            None
+        }
+    };
+    let store: Arc<dyn EventStore> = match &rocksdb {
+        Some(rocksdb) => rocksdb.clone(),
+        None => Arc::new(MemoryEventStore::new()),
+    };
 
     // Initialize deduplication engine
-    let dedupe_engine = init_dedupe_engine(&cfg, rocksdb.clone(), metrics.clone());
+    let dedupe_engine = init_dedupe_engine(&cfg, store, metrics.clone());
     info!("Deduplication engine initialized");
 
-    // Warm dedup engine from RocksDB successful-forward index to avoid duplicate downstream sends after restart
-    let warm_limit = cfg
+    // Restore the in-memory dedupe layers from the last snapshot, falling back to warming
+    // from the RocksDB successful-forward index to avoid duplicate downstream sends after restart
+    let (snapshot_path, snapshot_interval, snapshot_verify) = dedupe_snapshot_settings(&cfg);
+    let restored = match &snapshot_path {
+        Some(path) => match dedupe_engine.load_snapshot(path, snapshot_verify).await {
+            Ok(restored) => restored,
+            Err(e) => {
+                warn!("Ignoring dedupe snapshot: {:#}", e);
+                false
+            }
+        },
+        None => false,
+    };
+    if !restored {
+        let warm_limit = cfg
+            .as_ref()
+            .map(|c| c.deduplication.hotset_size)
+            .unwrap_or(10_000);
+        dedupe_engine.warm_from_db(warm_limit).await;
+    }
+    if let Some(path) = &snapshot_path {
+        spawn_dedupe_snapshots(dedupe_engine.clone(), path.clone(), snapshot_interval);
+    }
+
+    // Scheduled RocksDB checkpoints (optional)
+    let backup_interval = cfg.as_ref().map(|c| c.backup.interval_secs).unwrap_or(0);
+    if let Some(rocksdb) = &rocksdb
+        && backup_interval > 0
+    {
+        spawn_checkpoints(rocksdb.clone(), Duration::from_secs(backup_interval));
+        info!(
+            "Scheduled checkpoints started (interval={}s)",
+            backup_interval
+        );
+    }
+
+    // Prune archived events past their kind's retention
+    let retention_interval = cfg
         .as_ref()
-        .map(|c| c.deduplication.hotset_size)
-        .unwrap_or(10_000);
-    dedupe_engine.warm_from_db(warm_limit).await;
+        .map(|c| c.retention.interval_secs)
+        .unwrap_or_else(|| RetentionConfig::default().interval_secs);
+    if let Some(rocksdb) = &rocksdb
+        && retention_interval > 0
+        && !retention_policy(&cfg).is_empty()
+    {
+        let pruner = RetentionPruner::new(rocksdb.clone(), Duration::from_secs(retention_interval))
+            .with_metrics(metrics.clone());
+        tokio::spawn(async move { pruner.run().await });
+        info!(
+            "Retention pruner started (interval={}s)",
+            retention_interval
+        );
+    }
 
     // Initialize relay pool
-    let (health_check_interval, max_connections) = relay_settings(&cfg);
+    let (health_check_interval, max_connections, client_shards) = relay_settings(&cfg);
     let allowed_kinds = resolve_allowed_kinds(&cfg);
     let nostr_keys = load_nostr_keys(&cfg, cfg_path.as_deref())?;
     let platform_pubkey = nostr_keys.as_ref().map(|k| {
@@ -75,33 +231,91 @@ async fn main() -> Result<()> {
             .unwrap_or_else(|_| k.public_key().to_hex())
     });
     let nostr_client = init_nostr_publisher(&cfg, nostr_keys.as_ref()).await?;
-    let (relay_pool, relay_event_rx) = RelayPool::new(
+    let relay_urls = bootstrap_relays(&cfg).await?;
+    let discovery = init_relay_discovery(&cfg, &relay_urls).await;
+    let pipeline_cfg = cfg.as_ref().map(|c| c.pipeline.clone()).unwrap_or_default();
+    let (ingest_tx, relay_event_rx) = pipeline::stage(
+        "ingest",
+        pipeline_cfg.ingest_capacity,
+        pipeline_cfg.ingest_policy,
+        pipeline_cfg.shed_kinds.clone(),
+        Some(metrics.clone()),
+    );
+    let mut relay_pool = RelayPool::new(
         health_check_interval,
         max_connections,
+        client_shards,
         allowed_kinds.clone(),
-    );
-    let relay_pool = Arc::new(relay_pool.with_metrics(metrics.clone()));
+        ingest_tx,
+    )
+    .with_metrics(metrics.clone())
+    .with_reconnect_policy(reconnect_policy(&cfg));
+    if let Some(rocksdb) = &rocksdb {
+        relay_pool = relay_pool.with_store(rocksdb.clone());
+    }
+    if let Some(discovery) = discovery {
+        relay_pool = relay_pool.with_discovery(discovery);
+    }
+    if let Some(keys) = &nostr_keys {
+        relay_pool = relay_pool.with_auth_keys(keys.clone());
+    }
+    if let Some(relay_cfg) = cfg.as_ref().map(|c| &c.relay)
+        && relay_cfg.author_scoped
+    {
+        relay_pool = relay_pool.with_author_scope(relay_cfg.open_kinds.clone());
+        info!("Relay subscriptions scoped to registered bots and followers");
+    }
+    let relay_pool = Arc::new(relay_pool);
     info!("Relay pool initialized");
 
-    // Start health checks
-    relay_pool.start_health_checks().await;
-    info!("Health checks started");
+    // Start notification handling and health checks
+    relay_pool.start().await;
+    info!("Relay pool started");
 
-    // Connect to relays (example - load from config file or environment)
-    let relay_urls = bootstrap_relays(&cfg).await?;
-    info!("Loading {} relay URLs", relay_urls.len());
+    // Optional Postgres-backed subscription service for fanout
+    let subscription_service = init_subscription_service(&cfg).await?;
+
+    // Known authors go into the first subscription instead of a resubscribe right after it
+    if let Some(subs) = &subscription_service {
+        relay_pool
+            .sync_authors(subs)
+            .await
+            .context("Failed to load relay authors")?;
+    } else if cfg.as_ref().is_some_and(|c| c.relay.author_scoped) {
+        warn!("relay.author_scoped requires [postgres]; only open kinds will be received");
+    }
+
+    // Connect to the persisted relay set plus bootstrap relays (config file or environment)
+    info!("Loading {} bootstrap relay URLs", relay_urls.len());
 
     relay_pool
-        .subscribe_all(relay_urls)
+        .restore_relays(relay_urls.clone())
         .await
         .context("Failed to subscribe to relays")?;
     info!("Subscribed to all relays");
 
-    // Create downstream event channel
-    let (downstream_tx, downstream_rx) = flume::unbounded();
+    // Score relays and prune/promote them (optional)
+    if let Some(scoring_cfg) = cfg.as_ref().and_then(|c| c.relay.scoring.clone()) {
+        let protected = if scoring_cfg.prune_bootstrap {
+            Vec::new()
+        } else {
+            relay_urls
+        };
+        let interval_secs = scoring_cfg.interval_secs;
+        let scorer = RelayScorer::new(relay_pool.clone(), scoring_cfg, protected)
+            .with_metrics(metrics.clone());
+        tokio::spawn(async move { scorer.run().await });
+        info!("Relay scorer started (interval={}s)", interval_secs);
+    }
 
-    // Optional Postgres-backed subscription service for fanout
-    let subscription_service = init_subscription_service(&cfg).await?;
+    // Create downstream event channel
+    let (downstream_tx, downstream_rx) = pipeline::stage(
+        "downstream",
+        pipeline_cfg.downstream_capacity,
+        pipeline_cfg.downstream_policy,
+        pipeline_cfg.shed_kinds.clone(),
+        Some(metrics.clone()),
+    );
 
     // Start settlement worker (Hyperliquid tx hash polling)
     if let Some(subs) = subscription_service.clone() {
@@ -144,14 +358,20 @@ async fn main() -> Result<()> {
 
     // Fanout channel (only if subscription service is enabled)
     let (fanout_tx, fanout_rx) = if subscription_service.is_some() {
-        let (tx, rx) = flume::unbounded();
+        let (tx, rx) = pipeline::stage(
+            "fanout",
+            pipeline_cfg.fanout_capacity,
+            pipeline_cfg.fanout_policy,
+            pipeline_cfg.shed_kinds.clone(),
+            Some(metrics.clone()),
+        );
         (Some(tx), Some(rx))
     } else {
         (None, None)
     };
 
     // Initialize event router
-    let event_router = EventRouter::new(
+    let mut event_router = EventRouter::new(
         dedupe_engine.clone(),
         cfg.as_ref().map(|c| c.output.batch_size).unwrap_or(100), // batch size
         Duration::from_millis(cfg.as_ref().map(|c| c.output.max_latency_ms).unwrap_or(100) as u64), // max latency
@@ -162,7 +382,22 @@ async fn main() -> Result<()> {
         nostr_keys,
         nostr_client.clone(),
     )
-    .with_metrics(metrics.clone());
+    .with_metrics(metrics.clone())
+    .with_relay_pool(relay_pool.clone())
+    .with_ingress_validator(IngressValidator::new(
+        cfg.as_ref().map(|c| c.ingress.clone()).unwrap_or_default(),
+    ));
+    let semantic_window_secs = cfg
+        .as_ref()
+        .map(|c| c.deduplication.semantic_window_secs)
+        .unwrap_or(0);
+    if semantic_window_secs > 0 {
+        event_router = event_router.with_semantic_dedupe(Duration::from_secs(semantic_window_secs));
+        info!(
+            "Semantic trade-signal dedupe enabled (window={}s)",
+            semantic_window_secs
+        );
+    }
 
     // Spawn event router task
     let router_handle = tokio::spawn(async move {
@@ -232,6 +467,13 @@ async fn main() -> Result<()> {
     router_handle.abort();
     server_handle.abort();
 
+    if let Some(path) = &snapshot_path {
+        match dedupe_engine.save_snapshot(path).await {
+            Ok(()) => info!("Dedupe snapshot written to {}", path.display()),
+            Err(e) => error!("Failed to write dedupe snapshot: {:#}", e),
+        }
+    }
+
     info!("Shutdown complete");
     Ok(())
 }
@@ -277,42 +519,211 @@ fn init_tracing(cfg: &Option<AppConfig>) {
         .init();
 }
 
-fn init_rocksdb(cfg: &Option<AppConfig>) -> Result<Arc<RocksDBStore>> {
-    let rocks_path = cfg
-        .as_ref()
+fn rocksdb_path(cfg: &Option<AppConfig>) -> &str {
+    cfg.as_ref()
         .map(|c| c.deduplication.rocksdb_path.as_str())
-        .unwrap_or("./data/rocksdb");
+        .unwrap_or("./data/rocksdb")
+}
+
+fn init_rocksdb(cfg: &Option<AppConfig>) -> Result<Arc<RocksDBStore>> {
+    let rocks_path = rocksdb_path(cfg);
+    let backup = cfg.as_ref().map(|c| c.backup.clone()).unwrap_or_default();
     Ok(Arc::new(
-        RocksDBStore::new(rocks_path).context("Failed to initialize RocksDB storage")?,
+        RocksDBStore::new(rocks_path)
+            .and_then(|store| store.with_retention(retention_policy(cfg)))
+            .context("Failed to initialize RocksDB storage")?
+            .with_checkpoints(backup.dir(rocks_path), backup.keep),
     ))
 }
 
+/// `backup` subcommand: checkpoint the database directly, or ask a running
+/// relayer to (its RocksDB lock keeps other processes out)
+async fn backup(
+    cfg: &Option<AppConfig>,
+    out: Option<PathBuf>,
+    server: Option<String>,
+) -> Result<()> {
+    if let Some(server) = server {
+        let mut request = reqwest::Client::new().post(format!(
+            "{}/api/admin/checkpoint",
+            server.trim_end_matches('/')
+        ));
+        if let Some(token) = cfg
+            .as_ref()
+            .and_then(|c| c.settlement.as_ref())
+            .and_then(|s| s.token.as_deref())
+        {
+            request = request.header("X-Settlement-Token", token);
+        }
+        let response: serde_json::Value = request
+            .send()
+            .await
+            .context("Failed to reach the relayer")?
+            .error_for_status()
+            .context("Relayer refused the checkpoint")?
+            .json()
+            .await
+            .context("Malformed checkpoint response")?;
+        println!("Checkpoint written by the relayer to {}", response["path"]);
+        return Ok(());
+    }
+
+    let store = init_rocksdb(cfg)
+        .context("RocksDB is unavailable; if the relayer is running, pass --server")?;
+    let path = match out {
+        Some(path) => {
+            store.checkpoint(&path).await?;
+            path
+        }
+        None => store.create_checkpoint().await?,
+    };
+    let report = RocksDBStore::verify_checkpoint(&path).await?;
+    println!(
+        "Checkpoint written to {} ({} events, {} keys)",
+        path.display(),
+        report.events,
+        report.keys
+    );
+    Ok(())
+}
+
+/// `restore` subcommand: integrity check, then swap the checkpoint in
+async fn restore(cfg: &Option<AppConfig>, checkpoint: &Path, verify_only: bool) -> Result<()> {
+    let report = if verify_only {
+        RocksDBStore::verify_checkpoint(checkpoint).await?
+    } else {
+        RocksDBStore::restore_checkpoint(checkpoint, Path::new(rocksdb_path(cfg))).await?
+    };
+    println!(
+        "Checkpoint {} is intact: schema version {}, {} events, {} keys",
+        checkpoint.display(),
+        report.schema_version,
+        report.events,
+        report.keys
+    );
+    if !verify_only {
+        println!("Restored to {}", rocksdb_path(cfg));
+    }
+    Ok(())
+}
+
+/// `export` subcommand: write the matching archived events to `out`
+async fn export(cfg: &Option<AppConfig>, out: &Path, filter: EventQuery) -> Result<()> {
+    let store = init_rocksdb(cfg)
+        .context("RocksDB is unavailable; if the relayer is running, use GET /api/admin/export")?;
+    let file = tokio::fs::File::create(out)
+        .await
+        .with_context(|| format!("Failed to create {}", out.display()))?;
+    let written = archive::export_jsonl(store, filter, tokio::io::BufWriter::new(file)).await?;
+    println!("Exported {} events to {}", written, out.display());
+    Ok(())
+}
+
+/// `import` subcommand: archive the valid, not yet stored events of `input`
+/// Imported events are never forwarded.
+async fn import(cfg: &Option<AppConfig>, input: &Path) -> Result<()> {
+    let store = init_rocksdb(cfg)
+        .context("RocksDB is unavailable; if the relayer is running, use POST /api/admin/import")?;
+    let file = tokio::fs::File::open(input)
+        .await
+        .with_context(|| format!("Failed to open {}", input.display()))?;
+    let engine = DeduplicationEngine::new(store);
+    let stats = archive::import_jsonl(&engine, tokio::io::BufReader::new(file)).await?;
+    println!(
+        "Imported {} events from {} ({} already stored, {} invalid)",
+        stats.imported,
+        input.display(),
+        stats.duplicates,
+        stats.invalid
+    );
+    Ok(())
+}
+
+/// Maximum age per kind, never below the oldest event ingress accepts (live or
+/// backfilled): older deliveries are rejected, so pruned events cannot reappear as new
+fn retention_policy(cfg: &Option<AppConfig>) -> HashMap<u16, u64> {
+    let retention = cfg
+        .as_ref()
+        .map(|c| c.retention.clone())
+        .unwrap_or_default();
+    let oldest = cfg
+        .as_ref()
+        .map(|c| c.ingress.max_age_secs())
+        .unwrap_or_else(|| IngressConfig::default().max_age_secs());
+    let mut policy = retention.policy();
+    for (kind, max_age) in policy.iter_mut() {
+        if *max_age < oldest {
+            warn!(
+                "Retention of kind {} raised from {}s to the ingress age limit ({}s)",
+                kind, max_age, oldest
+            );
+            *max_age = oldest;
+        }
+    }
+    policy
+}
+
 fn init_dedupe_engine(
     cfg: &Option<AppConfig>,
-    rocksdb: Arc<RocksDBStore>,
+    store: Arc<dyn EventStore>,
     metrics: Arc<Metrics>,
 ) -> Arc<DeduplicationEngine> {
+    let latest_wins_kinds = cfg
+        .as_ref()
+        .and_then(|c| c.filters.latest_wins_kinds.clone())
+        .unwrap_or_else(default_latest_wins_kinds);
     match cfg {
         Some(c) => Arc::new(
             DeduplicationEngine::new_with_params(
-                rocksdb.clone(),
+                store.clone(),
                 c.deduplication.hotset_size,
                 c.deduplication.bloom_capacity,
                 c.deduplication.lru_size,
             )
+            .with_bloom_filter(
+                BloomFilter::with_capacity(
+                    c.deduplication.bloom_capacity,
+                    c.deduplication.bloom_false_positive_rate,
+                )
+                .with_rotation(
+                    c.deduplication.bloom_generations,
+                    Some(Duration::from_secs(c.deduplication.bloom_rotate_secs)),
+                ),
+            )
+            .with_latest_wins_kinds(latest_wins_kinds)
+            .with_coordinate_capacity(c.deduplication.coordinate_capacity)
             .with_metrics(metrics),
         ),
-        None => Arc::new(DeduplicationEngine::new(rocksdb).with_metrics(metrics)),
+        None => Arc::new(
+            DeduplicationEngine::new(store)
+                .with_latest_wins_kinds(latest_wins_kinds)
+                .with_metrics(metrics),
+        ),
     }
 }
 
-fn relay_settings(cfg: &Option<AppConfig>) -> (Duration, usize) {
+fn relay_settings(cfg: &Option<AppConfig>) -> (Duration, usize, usize) {
     match cfg {
         Some(c) => (
             Duration::from_secs(c.relay.health_check_interval),
             c.relay.max_connections,
+            c.relay.client_shards,
         ),
-        None => (Duration::from_secs(30), 10_000),
+        None => (Duration::from_secs(30), 10_000, 1),
+    }
+}
+
+fn reconnect_policy(cfg: &Option<AppConfig>) -> ReconnectPolicy {
+    match cfg {
+        Some(c) => ReconnectPolicy {
+            liveness_timeout: Duration::from_secs(c.relay.liveness_timeout),
+            probe_timeout: Duration::from_secs(c.relay.probe_timeout),
+            base_delay: Duration::from_secs(c.relay.reconnect_base_delay),
+            max_delay: Duration::from_secs(c.relay.reconnect_max_delay),
+            backfill_overlap: Duration::from_secs(c.relay.backfill_overlap),
+            max_backfill: Duration::from_secs(c.ingress.max_age_secs()),
+        },
+        None => ReconnectPolicy::default(),
     }
 }
 
@@ -412,6 +823,33 @@ async fn bootstrap_relays(cfg: &Option<AppConfig>) -> Result<Vec<String>> {
     }
 }
 
+/// Lookup client for registered bots' NIP-65 relay lists, unless opted out
+async fn init_relay_discovery(
+    cfg: &Option<AppConfig>,
+    bootstrap: &[String],
+) -> Option<RelayDiscovery> {
+    let discovery_cfg = cfg
+        .as_ref()
+        .map(|c| c.relay.discovery.clone())
+        .unwrap_or_default();
+    if !discovery_cfg.enabled {
+        info!("NIP-65 relay discovery disabled");
+        return None;
+    }
+    let index_relays = if discovery_cfg.index_relays.is_empty() {
+        bootstrap
+    } else {
+        &discovery_cfg.index_relays
+    };
+    match RelayDiscovery::new(&discovery_cfg, index_relays).await {
+        Ok(discovery) => Some(discovery),
+        Err(e) => {
+            warn!("NIP-65 relay discovery unavailable: {}", e);
+            None
+        }
+    }
+}
+
 async fn init_subscription_service(
     cfg: &Option<AppConfig>,
 ) -> Result<Option<Arc<SubscriptionService>>> {
@@ -462,6 +900,49 @@ fn build_app(
     app.layer(cors)
 }
 
+/// Snapshot file (None when snapshots are disabled), interval, and post-restore verify window
+fn dedupe_snapshot_settings(cfg: &Option<AppConfig>) -> (Option<PathBuf>, Duration, Duration) {
+    match cfg {
+        Some(c) => (
+            (c.deduplication.snapshot_interval_secs > 0).then(|| c.deduplication.snapshot_path()),
+            Duration::from_secs(c.deduplication.snapshot_interval_secs),
+            Duration::from_secs(c.deduplication.snapshot_verify_secs),
+        ),
+        None => (
+            Some(PathBuf::from("./data/dedupe.snapshot")),
+            Duration::from_secs(300),
+            Duration::from_secs(600),
+        ),
+    }
+}
+
+fn spawn_dedupe_snapshots(dedupe: Arc<DeduplicationEngine>, path: PathBuf, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = dedupe.save_snapshot(&path).await {
+                warn!("Failed to write dedupe snapshot: {:#}", e);
+            }
+        }
+    });
+}
+
+fn spawn_checkpoints(rocksdb: Arc<RocksDBStore>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match rocksdb.create_checkpoint().await {
+                Ok(path) => info!("Checkpoint written to {}", path.display()),
+                Err(e) => warn!("Failed to write checkpoint: {:#}", e),
+            }
+        }
+    });
+}
+
 fn spawn_memory_metrics(metrics: Arc<Metrics>) {
     tokio::spawn(async move {
         use sysinfo::{ProcessesToUpdate, System};
@@ -1,47 +1,289 @@
-use bloom::{ASMS, BloomFilter as BloomFilterLib};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use anyhow::{Result, bail};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
 
-/// In-memory Bloom filter for fast duplicate detection
-/// Capacity: 10 million events with ~1% false positive rate
+/// Fixed-size Bloom filter over event IDs, one generation of [`BloomFilter`]
+/// Bits are set with atomic `fetch_or`, so inserts only need shared access.
+struct Generation {
+    bits: Vec<AtomicU64>,
+    num_bits: u64,
+    num_hashes: u32,
+    set_bits: AtomicU64,
+    items: AtomicU64,
+    created_at: Instant,
+}
+
+impl Generation {
+    fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let n = capacity.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-n * false_positive_rate.ln() / (ln2 * ln2))
+            .ceil()
+            .max(64.0) as u64;
+        let num_hashes = (num_bits as f64 / n * ln2).round().max(1.0) as u32;
+        Self {
+            bits: (0..num_bits.div_ceil(64))
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            num_bits,
+            num_hashes,
+            set_bits: AtomicU64::new(0),
+            items: AtomicU64::new(0),
+            created_at: Instant::now(),
+        }
+    }
+
+    /// Bit positions for an event ID. IDs are SHA-256 digests, so their own bytes
+    /// serve as the two hashes for double hashing.
+    fn positions(&self, event_id: &[u8; 32]) -> impl Iterator<Item = u64> + use<> {
+        let h1 = u64::from_le_bytes(event_id[0..8].try_into().expect("8 bytes"));
+        let h2 = u64::from_le_bytes(event_id[8..16].try_into().expect("8 bytes")) | 1;
+        let num_bits = self.num_bits;
+        (0..u64::from(self.num_hashes)).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    fn contains(&self, event_id: &[u8; 32]) -> bool {
+        self.positions(event_id).all(|pos| {
+            self.bits[(pos / 64) as usize].load(Ordering::Relaxed) & (1 << (pos % 64)) != 0
+        })
+    }
+
+    fn insert(&self, event_id: &[u8; 32]) {
+        let mut new_bits = 0;
+        for pos in self.positions(event_id) {
+            let mask = 1 << (pos % 64);
+            if self.bits[(pos / 64) as usize].fetch_or(mask, Ordering::Relaxed) & mask == 0 {
+                new_bits += 1;
+            }
+        }
+        if new_bits > 0 {
+            self.set_bits.fetch_add(new_bits, Ordering::Relaxed);
+            self.items.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn items(&self) -> u64 {
+        self.items.load(Ordering::Relaxed)
+    }
+
+    fn fill_ratio(&self) -> f64 {
+        self.set_bits.load(Ordering::Relaxed) as f64 / self.num_bits as f64
+    }
+
+    /// Whether inserts should move on to a fresh generation
+    /// Counting items rather than set bits means a generation is never dropped
+    /// before it held `capacity` IDs, even when its bits fill up early.
+    fn is_full(&self, capacity: usize, max_age: Option<Duration>) -> bool {
+        self.items() >= capacity as u64
+            || max_age.is_some_and(|age| self.created_at.elapsed() >= age)
+    }
+
+    /// False positive rate implied by the current fill ratio
+    fn estimated_fpr(&self) -> f64 {
+        self.fill_ratio().powi(self.num_hashes as i32)
+    }
+}
+
+/// Generations, newest last
+struct Generations {
+    generations: VecDeque<Generation>,
+    rotations: u64,
+}
+
+/// In-memory rotating Bloom filter for fast duplicate detection
+/// Inserts go to the newest generation; once it holds `capacity` IDs (or is older than
+/// `max_age`) a fresh one is started and the oldest beyond `max_generations` is
+/// dropped, so the false positive rate stays bounded on a long-running relayer.
+/// With two or more generations and no `max_age`, the last `capacity` IDs are
+/// always remembered. Lookups and inserts share a read lock; only rotation
+/// takes the write lock.
 pub struct BloomFilter {
-    filter: Arc<RwLock<BloomFilterLib>>,
+    generations: RwLock<Generations>,
+    capacity: usize,
+    false_positive_rate: f64,
+    max_generations: usize,
+    max_age: Option<Duration>,
 }
 
 impl BloomFilter {
-    /// Create a new Bloom filter with custom capacity and false positive rate
+    /// Create a filter whose generations hold `capacity` IDs each, keeping two
+    /// generations and an overall false positive rate of `false_positive_rate`
     pub fn with_capacity(capacity: usize, false_positive_rate: f64) -> Self {
-        let filter = BloomFilterLib::with_rate(false_positive_rate as f32, capacity as u32);
-        Self {
-            filter: Arc::new(RwLock::new(filter)),
-        }
+        let mut filter = Self {
+            generations: RwLock::new(Generations {
+                generations: VecDeque::new(),
+                rotations: 0,
+            }),
+            capacity: capacity.max(1),
+            false_positive_rate: false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5),
+            max_generations: 2,
+            max_age: None,
+        };
+        filter.reset();
+        filter
     }
 
-    /// Create a new Bloom filter with capacity for 10 million items
+    /// Create a new Bloom filter with capacity for 10 million items per generation
     pub fn new() -> Self {
-        // Create bloom filter with 10M capacity and 1% false positive rate
-        let filter = BloomFilterLib::with_rate(0.01, 10_000_000);
-        Self {
-            filter: Arc::new(RwLock::new(filter)),
-        }
+        Self::with_capacity(10_000_000, 0.01)
+    }
+
+    /// Keep `generations` generations and also rotate once the newest is older than `max_age`
+    pub fn with_rotation(mut self, generations: usize, max_age: Option<Duration>) -> Self {
+        self.max_generations = generations.max(1);
+        self.max_age = max_age.filter(|age| !age.is_zero());
+        self.reset();
+        self
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Generations> {
+        self.generations.read().expect("Bloom filter lock poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Generations> {
+        self.generations
+            .write()
+            .expect("Bloom filter lock poisoned")
     }
 
     /// Check if an event ID might exist (fast check, may have false positives)
-    pub async fn contains(&self, event_id: &[u8; 32]) -> bool {
-        let filter = self.filter.read().await;
-        filter.contains(event_id)
+    pub fn contains(&self, event_id: &[u8; 32]) -> bool {
+        self.read().generations.iter().any(|g| g.contains(event_id))
     }
 
     /// Insert an event ID into the bloom filter
-    pub async fn insert(&self, event_id: &[u8; 32]) {
-        let mut filter = self.filter.write().await;
-        filter.insert(event_id);
+    /// Returns true if the insert started a new generation
+    pub fn insert(&self, event_id: &[u8; 32]) -> bool {
+        {
+            let state = self.read();
+            if let Some(current) = state.generations.back()
+                && !current.is_full(self.capacity, self.max_age)
+            {
+                current.insert(event_id);
+                return false;
+            }
+        }
+
+        let mut state = self.write();
+        // Another insert may have rotated while we waited for the write lock
+        let rotate = state
+            .generations
+            .back()
+            .is_none_or(|current| current.is_full(self.capacity, self.max_age));
+        if rotate {
+            state.generations.push_back(self.new_generation());
+            while state.generations.len() > self.max_generations {
+                state.generations.pop_front();
+            }
+            state.rotations += 1;
+        }
+        if let Some(current) = state.generations.back() {
+            current.insert(event_id);
+        }
+        rotate
     }
 
     /// Clear the bloom filter (useful for testing or reset)
-    pub async fn clear(&self) {
-        let mut filter = self.filter.write().await;
-        *filter = BloomFilterLib::with_rate(0.01, 10_000_000);
+    pub fn clear(&self) {
+        let mut state = self.write();
+        state.generations.clear();
+        state.generations.push_back(self.new_generation());
+    }
+
+    /// Current size and accuracy of the filter
+    pub fn stats(&self) -> BloomStats {
+        let state = self.read();
+        let miss_all: f64 = state
+            .generations
+            .iter()
+            .map(|g| 1.0 - g.estimated_fpr())
+            .product();
+        BloomStats {
+            generations: state.generations.len(),
+            items: state.generations.iter().map(Generation::items).sum(),
+            fill_ratio: state
+                .generations
+                .back()
+                .map(Generation::fill_ratio)
+                .unwrap_or(0.0),
+            estimated_fpr: 1.0 - miss_all,
+            rotations: state.rotations,
+        }
+    }
+
+    /// Copy of the generations for an on-disk snapshot
+    pub fn export(&self) -> BloomSnapshot {
+        let state = self.read();
+        BloomSnapshot {
+            rotations: state.rotations,
+            generations: state
+                .generations
+                .iter()
+                .map(|g| GenerationSnapshot {
+                    num_bits: g.num_bits,
+                    num_hashes: g.num_hashes,
+                    set_bits: g.set_bits.load(Ordering::Relaxed),
+                    items: g.items(),
+                    age: g.created_at.elapsed(),
+                    bits: g.bits.iter().map(|w| w.load(Ordering::Relaxed)).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Replace the generations with ones from a snapshot, keeping the newest
+    /// `max_generations`. Fails if a generation's bit count does not match its size.
+    pub fn restore(&self, snapshot: BloomSnapshot) -> Result<()> {
+        let mut restored = VecDeque::with_capacity(snapshot.generations.len());
+        for g in snapshot.generations {
+            if g.num_bits == 0
+                || g.num_hashes == 0
+                || g.bits.len() as u64 != g.num_bits.div_ceil(64)
+            {
+                bail!("malformed Bloom filter generation");
+            }
+            let now = Instant::now();
+            restored.push_back(Generation {
+                bits: g.bits.into_iter().map(AtomicU64::new).collect(),
+                num_bits: g.num_bits,
+                num_hashes: g.num_hashes,
+                set_bits: AtomicU64::new(g.set_bits),
+                items: AtomicU64::new(g.items),
+                created_at: now.checked_sub(g.age).unwrap_or(now),
+            });
+        }
+        while restored.len() > self.max_generations {
+            restored.pop_front();
+        }
+        if restored.is_empty() {
+            restored.push_back(self.new_generation());
+        }
+
+        let mut state = self.write();
+        state.generations = restored;
+        state.rotations = snapshot.rotations;
+        Ok(())
+    }
+
+    /// Each generation gets an equal share of the overall false positive rate
+    fn new_generation(&self) -> Generation {
+        Generation::new(
+            self.capacity,
+            self.false_positive_rate / self.max_generations as f64,
+        )
+    }
+
+    /// Replace the generations with a single empty one
+    fn reset(&mut self) {
+        let first = self.new_generation();
+        let state = self
+            .generations
+            .get_mut()
+            .expect("Bloom filter lock poisoned");
+        state.generations.clear();
+        state.generations.push_back(first);
     }
 }
 
@@ -50,3 +292,38 @@ impl Default for BloomFilter {
         Self::new()
     }
 }
+
+/// Snapshot of the Bloom filter's size and accuracy
+#[derive(Debug, Clone, Default)]
+pub struct BloomStats {
+    /// Live generations, newest included
+    pub generations: usize,
+    /// Distinct IDs inserted across live generations
+    pub items: u64,
+    /// Fraction of bits set in the newest generation
+    pub fill_ratio: f64,
+    /// Probability that an unseen ID matches any live generation
+    pub estimated_fpr: f64,
+    /// Generations started since creation
+    pub rotations: u64,
+}
+
+/// Bloom filter state as stored in a dedupe snapshot
+#[derive(Debug, Clone)]
+pub struct BloomSnapshot {
+    pub rotations: u64,
+    /// Oldest first
+    pub generations: Vec<GenerationSnapshot>,
+}
+
+/// One Bloom filter generation in a snapshot
+#[derive(Debug, Clone)]
+pub struct GenerationSnapshot {
+    pub num_bits: u64,
+    pub num_hashes: u32,
+    pub set_bits: u64,
+    pub items: u64,
+    /// Age when the snapshot was taken, so time-based rotation carries over
+    pub age: Duration,
+    pub bits: Vec<u64>,
+}
@@ -0,0 +1,195 @@
+use anyhow::{Context, Result, anyhow};
+use nostr_sdk::Event;
+use std::fmt;
+use std::str::FromStr;
+
+/// Events by author: `{pubkey hex}\0{016x created_at}:{event_id}`
+pub(crate) const CF_BY_AUTHOR: &str = "by_author";
+/// Events by kind: `{kind}\0{016x created_at}:{event_id}`
+pub(crate) const CF_BY_KIND: &str = "by_kind";
+/// Events by indexed tag: `{tag}:{value}\0{016x created_at}:{event_id}`
+pub(crate) const CF_BY_TAG: &str = "by_tag";
+pub(crate) const INDEX_FAMILIES: [&str; 3] = [CF_BY_AUTHOR, CF_BY_KIND, CF_BY_TAG];
+
+/// Tags whose values are indexed; other tags can only narrow an indexed query
+pub const INDEXED_TAGS: [&str; 3] = ["symbol", "strategy", "sid"];
+
+/// Position of an event in archive order: newest `created_at` first, ties by id
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventCursor {
+    pub created_at: u64,
+    pub id: String,
+}
+
+impl EventCursor {
+    pub fn of(event: &Event) -> Self {
+        Self {
+            created_at: event.created_at.as_secs(),
+            id: event.id.to_hex(),
+        }
+    }
+}
+
+/// `{created_at}:{event_id}`
+impl fmt::Display for EventCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.created_at, self.id)
+    }
+}
+
+impl FromStr for EventCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (created_at, id) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Malformed cursor"))?;
+        if id.len() != 64 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(anyhow!("Malformed cursor event id"));
+        }
+        Ok(Self {
+            created_at: created_at.parse().context("Malformed cursor timestamp")?,
+            id: id.to_ascii_lowercase(),
+        })
+    }
+}
+
+/// NIP-01-like filter over the archived events; an event matches when it
+/// satisfies every field that is set. Results are newest first.
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    /// Hex pubkeys
+    pub authors: Vec<String>,
+    pub kinds: Vec<u16>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Tag name with the accepted values
+    pub tags: Vec<(String, Vec<String>)>,
+    pub limit: usize,
+    /// Continue after this event, the last one of the previous page
+    pub cursor: Option<EventCursor>,
+}
+
+impl EventQuery {
+    /// Whether the query can be answered from an index (it has authors, kinds
+    /// or an indexed tag); unindexed queries would scan the whole archive
+    pub fn is_indexed(&self) -> bool {
+        self.scopes().is_some()
+    }
+
+    /// Index family and key prefixes to scan, from the most selective filter
+    pub(crate) fn scopes(&self) -> Option<(&'static str, Vec<Vec<u8>>)> {
+        if let Some((name, values)) = self
+            .tags
+            .iter()
+            .find(|(name, values)| INDEXED_TAGS.contains(&name.as_str()) && !values.is_empty())
+        {
+            let scopes = values.iter().map(|v| tag_scope(name, v)).collect();
+            return Some((CF_BY_TAG, scopes));
+        }
+        if !self.authors.is_empty() {
+            let scopes = self.authors.iter().map(|a| scope(a)).collect();
+            return Some((CF_BY_AUTHOR, scopes));
+        }
+        if !self.kinds.is_empty() {
+            let scopes = self.kinds.iter().map(|k| scope(&k.to_string())).collect();
+            return Some((CF_BY_KIND, scopes));
+        }
+        None
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        let created_at = event.created_at.as_secs();
+        (self.authors.is_empty() || self.authors.contains(&event.pubkey.to_hex()))
+            && (self.kinds.is_empty() || self.kinds.contains(&event.kind.as_u16()))
+            && self.since.is_none_or(|since| created_at >= since)
+            && self.until.is_none_or(|until| created_at <= until)
+            && self
+                .cursor
+                .as_ref()
+                .is_none_or(|c| EventCursor::of(event) < *c)
+            && self.tags.iter().all(|(name, values)| {
+                event.tags.iter().any(|tag| {
+                    tag.kind().to_string() == *name
+                        && tag.content().is_some_and(|v| values.iter().any(|x| x == v))
+                })
+            })
+    }
+
+    /// Key at or after the newest entry of `scope` the query can return
+    pub(crate) fn upper_bound(&self, scope: &[u8]) -> Vec<u8> {
+        match (&self.cursor, self.until) {
+            (Some(cursor), until) if until.is_none_or(|u| u >= cursor.created_at) => {
+                index_key(scope, cursor.created_at, &cursor.id)
+            }
+            (_, Some(until)) => {
+                let mut key = index_key(scope, until, "");
+                key.push(0xff);
+                key
+            }
+            (_, None) => {
+                let mut key = scope.to_vec();
+                key.push(0xff);
+                key
+            }
+        }
+    }
+}
+
+/// Index entries of an event as (family, key)
+pub(crate) fn index_entries(event: &Event) -> Vec<(&'static str, Vec<u8>)> {
+    let created_at = event.created_at.as_secs();
+    let id = event.id.to_hex();
+    let mut entries = vec![
+        (
+            CF_BY_AUTHOR,
+            index_key(&scope(&event.pubkey.to_hex()), created_at, &id),
+        ),
+        (
+            CF_BY_KIND,
+            index_key(&scope(&event.kind.as_u16().to_string()), created_at, &id),
+        ),
+    ];
+    for tag in event.tags.iter() {
+        let name = tag.kind().to_string();
+        if let Some(value) = tag.content()
+            && INDEXED_TAGS.contains(&name.as_str())
+        {
+            let entry = (
+                CF_BY_TAG,
+                index_key(&tag_scope(&name, value), created_at, &id),
+            );
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+    }
+    entries
+}
+
+/// Archive position of an index key, if it belongs to `scope`
+pub(crate) fn position(key: &[u8], scope: &[u8]) -> Option<EventCursor> {
+    let suffix = std::str::from_utf8(key.strip_prefix(scope)?).ok()?;
+    let (created_at, id) = suffix.split_once(':')?;
+    Some(EventCursor {
+        created_at: u64::from_str_radix(created_at, 16).ok()?,
+        id: id.to_string(),
+    })
+}
+
+/// Scope prefix; the terminator keeps one value from prefixing another
+fn scope(value: &str) -> Vec<u8> {
+    let mut key = value.as_bytes().to_vec();
+    key.push(0);
+    key
+}
+
+fn tag_scope(name: &str, value: &str) -> Vec<u8> {
+    scope(&format!("{name}:{value}"))
+}
+
+fn index_key(scope: &[u8], created_at: u64, event_id: &str) -> Vec<u8> {
+    let mut key = scope.to_vec();
+    key.extend_from_slice(format!("{created_at:016x}:{event_id}").as_bytes());
+    key
+}
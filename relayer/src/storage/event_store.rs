@@ -0,0 +1,47 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use nostr_sdk::Event;
+use std::collections::HashSet;
+
+/// Persistent layer of deduplication: the event archive with forwarding status
+/// `RocksDBStore` is the production backend; `MemoryEventStore` keeps everything
+/// in process memory for tests and lightweight dev deployments.
+#[async_trait]
+pub trait EventStore: Send + Sync {
+    /// Check if an event ID is stored
+    async fn exists(&self, event_id: &str) -> bool;
+
+    /// Store an event, pending until `mark_forward_success`
+    async fn store_event(&self, event: &Event) -> Result<()>;
+
+    /// Store an event that needs no forwarding, such as an imported one
+    async fn archive_event(&self, event: &Event) -> Result<()>;
+
+    /// Retrieve an event by ID
+    async fn get_event(&self, event_id: &str) -> Result<Option<Event>>;
+
+    /// Delete an event by ID, with its forwarding status and pending entry
+    async fn delete_event(&self, event_id: &str) -> Result<()>;
+
+    /// Mark an event as successfully forwarded to downstream(s)
+    async fn mark_forward_success(&self, event_id: &str) -> Result<()>;
+
+    /// Record delivery steps that completed for a still pending event, so a
+    /// replay can skip them; `mark_forward_success` clears them
+    async fn record_deliveries(&self, event_id: &str, steps: &[String]) -> Result<()>;
+
+    /// Delivery steps recorded for a pending event
+    async fn completed_deliveries(&self, event_id: &str) -> Result<HashSet<String>>;
+
+    /// Check whether an event has been marked as successfully forwarded
+    async fn is_forward_success(&self, event_id: &str) -> bool;
+
+    /// Up to `limit` most recently forwarded event IDs, most recent first
+    async fn load_recent_success_ids(&self, limit: usize) -> Vec<String>;
+
+    /// Up to `limit` stored events that were never marked as forwarded
+    async fn load_pending_events(&self, limit: usize) -> Vec<Event>;
+
+    /// Number of stored events; may be an estimate
+    async fn approximate_count(&self) -> u64;
+}
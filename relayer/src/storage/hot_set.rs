@@ -0,0 +1,118 @@
+use crate::storage::sharding::{shard_count, shard_of};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// IDs of one hot set shard with their arrival order
+#[derive(Default)]
+struct Shard {
+    ids: HashSet<String>,
+    /// Arrival order of `ids`, oldest first
+    order: VecDeque<String>,
+}
+
+/// Bounded set of the most recently seen event IDs
+/// Split into shards with their own lock; each evicts in arrival order, so the
+/// newest IDs are always kept. Sets below 2048 IDs are a single exact shard.
+pub struct HotSet {
+    shards: Vec<Mutex<Shard>>,
+    shard_capacity: usize,
+    capacity: usize,
+    evictions: AtomicU64,
+}
+
+impl HotSet {
+    /// Create a hot set holding at most `capacity` IDs
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let shards = shard_count(capacity);
+        Self {
+            shards: (0..shards).map(|_| Mutex::default()).collect(),
+            shard_capacity: capacity.div_ceil(shards),
+            capacity,
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn shard(&self, event_id: &str) -> MutexGuard<'_, Shard> {
+        self.shards[shard_of(event_id, self.shards.len())]
+            .lock()
+            .expect("hot set shard lock poisoned")
+    }
+
+    /// Check if an event ID is in the hot set
+    pub fn contains(&self, event_id: &str) -> bool {
+        self.shard(event_id).ids.contains(event_id)
+    }
+
+    /// Insert an event ID, evicting the oldest IDs beyond capacity
+    /// Returns the number of IDs evicted
+    pub fn insert(&self, event_id: String) -> usize {
+        self.try_insert(event_id).unwrap_or(0)
+    }
+
+    /// Insert an event ID unless it is already present (keeping its original
+    /// arrival position). Returns `None` if it was present, otherwise the number
+    /// of IDs evicted. Check and insert are atomic, so of several concurrent
+    /// callers with the same ID exactly one gets `Some`.
+    pub fn try_insert(&self, event_id: String) -> Option<usize> {
+        let mut shard = self.shard(&event_id);
+        if !shard.ids.insert(event_id.clone()) {
+            return None;
+        }
+        shard.order.push_back(event_id);
+        let mut evicted = 0;
+        while shard.order.len() > self.shard_capacity {
+            if let Some(oldest) = shard.order.pop_front() {
+                shard.ids.remove(&oldest);
+                evicted += 1;
+            }
+        }
+        drop(shard);
+        if evicted > 0 {
+            self.evictions.fetch_add(evicted as u64, Ordering::Relaxed);
+        }
+        Some(evicted)
+    }
+
+    /// IDs in arrival order, oldest first within each shard
+    pub fn ids(&self) -> Vec<String> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.lock().expect("hot set shard lock poisoned");
+                shard.order.iter().cloned().collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Number of IDs currently held
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().expect("hot set shard lock poisoned").ids.len())
+            .sum()
+    }
+
+    /// Whether the hot set holds no IDs
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Configured maximum number of IDs
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Total IDs evicted since creation
+    pub fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for HotSet {
+    /// Hot set with capacity for 10,000 IDs
+    fn default() -> Self {
+        Self::with_capacity(10_000)
+    }
+}
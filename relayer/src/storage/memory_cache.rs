@@ -1,49 +1,76 @@
+use crate::storage::sharding::{shard_count, shard_of};
 use lru::LruCache;
 use std::num::NonZeroUsize;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Mutex, MutexGuard};
 
 /// LRU cache for recent event IDs
 /// Capacity: 100,000 recent events
+/// Split into shards with their own lock and LRU order, so concurrent lookups
+/// rarely contend. Caches below 2048 IDs are a single exact LRU.
 pub struct MemoryCache {
-    cache: Arc<RwLock<LruCache<String, ()>>>,
+    shards: Vec<Mutex<LruCache<String, ()>>>,
 }
 
 impl MemoryCache {
     /// Create a new LRU cache with custom capacity
     pub fn with_capacity(size: usize) -> Self {
-        let capacity = NonZeroUsize::new(size.max(1)).unwrap();
-        let cache = LruCache::new(capacity);
+        let size = size.max(1);
+        let shards = shard_count(size);
+        let shard_capacity = NonZeroUsize::new(size.div_ceil(shards)).unwrap();
         Self {
-            cache: Arc::new(RwLock::new(cache)),
+            shards: (0..shards)
+                .map(|_| Mutex::new(LruCache::new(shard_capacity)))
+                .collect(),
         }
     }
 
     /// Create a new LRU cache with capacity for 100,000 items
     pub fn new() -> Self {
-        let capacity = NonZeroUsize::new(100_000).unwrap();
-        let cache = LruCache::new(capacity);
-        Self {
-            cache: Arc::new(RwLock::new(cache)),
-        }
+        Self::with_capacity(100_000)
+    }
+
+    fn shard(&self, event_id: &str) -> MutexGuard<'_, LruCache<String, ()>> {
+        self.shards[shard_of(event_id, self.shards.len())]
+            .lock()
+            .expect("LRU shard lock poisoned")
     }
 
     /// Check if an event ID exists in the cache
-    pub async fn contains(&self, event_id: &str) -> bool {
-        let cache = self.cache.read().await;
-        cache.contains(event_id)
+    pub fn contains(&self, event_id: &str) -> bool {
+        self.shard(event_id).contains(event_id)
     }
 
     /// Insert an event ID into the cache
-    pub async fn put(&self, event_id: String) {
-        let mut cache = self.cache.write().await;
-        cache.put(event_id, ());
+    pub fn put(&self, event_id: String) {
+        self.shard(&event_id).put(event_id, ());
     }
 
     /// Get the current size of the cache
-    pub async fn len(&self) -> usize {
-        let cache = self.cache.read().await;
-        cache.len()
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().expect("LRU shard lock poisoned").len())
+            .sum()
+    }
+
+    /// Event IDs from least to most recently used within each shard
+    pub fn keys(&self) -> Vec<String> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.lock().expect("LRU shard lock poisoned");
+                shard
+                    .iter()
+                    .rev()
+                    .map(|(k, _)| k.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Whether the cache holds no event IDs
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use nostr_sdk::Event;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::storage::event_store::EventStore;
+
+/// Records of the in-memory store, mirroring the RocksDB column families
+#[derive(Default)]
+struct Records {
+    events: HashMap<String, Event>,
+    /// Forwarded event IDs with their position in `success`
+    forwarded: HashMap<String, u64>,
+    /// Stored but not yet forwarded, in ID order like the RocksDB replay
+    pending: BTreeSet<String>,
+    /// Delivery steps completed for pending events
+    deliveries: HashMap<String, HashSet<String>>,
+    /// Forwarded event IDs in forwarding order
+    success: BTreeMap<u64, String>,
+    /// Next `success` position
+    next_success: u64,
+}
+
+/// Event store kept in process memory; nothing survives a restart
+/// Deterministic: ordering follows call order, not wall-clock time.
+#[derive(Default)]
+pub struct MemoryEventStore {
+    records: Mutex<Records>,
+}
+
+impl MemoryEventStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn records(&self) -> MutexGuard<'_, Records> {
+        self.records.lock().expect("memory store lock poisoned")
+    }
+}
+
+#[async_trait]
+impl EventStore for MemoryEventStore {
+    async fn exists(&self, event_id: &str) -> bool {
+        self.records().events.contains_key(event_id)
+    }
+
+    async fn store_event(&self, event: &Event) -> Result<()> {
+        let event_id = event.id.to_hex();
+        let mut records = self.records();
+        records.pending.insert(event_id.clone());
+        records.events.insert(event_id, event.clone());
+        Ok(())
+    }
+
+    async fn archive_event(&self, event: &Event) -> Result<()> {
+        self.records()
+            .events
+            .insert(event.id.to_hex(), event.clone());
+        Ok(())
+    }
+
+    async fn get_event(&self, event_id: &str) -> Result<Option<Event>> {
+        Ok(self.records().events.get(event_id).cloned())
+    }
+
+    async fn delete_event(&self, event_id: &str) -> Result<()> {
+        let mut records = self.records();
+        records.events.remove(event_id);
+        records.pending.remove(event_id);
+        records.deliveries.remove(event_id);
+        if let Some(position) = records.forwarded.remove(event_id) {
+            records.success.remove(&position);
+        }
+        Ok(())
+    }
+
+    async fn mark_forward_success(&self, event_id: &str) -> Result<()> {
+        let mut records = self.records();
+        let position = records.next_success;
+        records.next_success += 1;
+        if let Some(previous) = records.forwarded.insert(event_id.to_string(), position) {
+            records.success.remove(&previous);
+        }
+        records.success.insert(position, event_id.to_string());
+        records.pending.remove(event_id);
+        records.deliveries.remove(event_id);
+        Ok(())
+    }
+
+    async fn record_deliveries(&self, event_id: &str, steps: &[String]) -> Result<()> {
+        self.records()
+            .deliveries
+            .entry(event_id.to_string())
+            .or_default()
+            .extend(steps.iter().cloned());
+        Ok(())
+    }
+
+    async fn completed_deliveries(&self, event_id: &str) -> Result<HashSet<String>> {
+        Ok(self
+            .records()
+            .deliveries
+            .get(event_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn is_forward_success(&self, event_id: &str) -> bool {
+        self.records().forwarded.contains_key(event_id)
+    }
+
+    async fn load_recent_success_ids(&self, limit: usize) -> Vec<String> {
+        self.records()
+            .success
+            .values()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    async fn load_pending_events(&self, limit: usize) -> Vec<Event> {
+        let records = self.records();
+        records
+            .pending
+            .iter()
+            .filter_map(|id| records.events.get(id).cloned())
+            .take(limit)
+            .collect()
+    }
+
+    async fn approximate_count(&self) -> u64 {
+        self.records().events.len() as u64
+    }
+}
@@ -1,3 +1,9 @@
 pub mod bloom_filter;
+pub mod event_index;
+pub mod event_store;
+pub mod hot_set;
 pub mod memory_cache;
+pub mod memory_store;
 pub mod rocksdb_store;
+mod sharding;
+pub mod snapshot;
@@ -1,18 +1,125 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
 use nostr_sdk::Event;
-use rocksdb::{DB, IteratorMode, Options};
+use rocksdb::{
+    BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, DB, Direction, IteratorMode, Options,
+    ReadOptions, SliceTransform, WriteBatch, checkpoint::Checkpoint,
+};
+use serde::{Deserialize, Serialize};
 use serde_json;
-use std::path::Path;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::storage::event_index::{self, EventCursor, EventQuery, INDEX_FAMILIES};
+use crate::storage::event_store::EventStore;
+
+/// Event payloads, keyed by event id (hex)
+const CF_EVENTS: &str = "events";
+/// Forwarding status, keyed by event id; the value is the event's success index
+/// key (older databases: the forwarding time, epoch ms u64 BE)
+const CF_FORWARDED: &str = "forwarded";
+/// Stored but not yet fully processed events, replayed on startup; keyed by event id
+const CF_PENDING: &str = "pending";
+/// Delivery steps completed for pending events: `{event_id}{step}`
+const CF_DELIVERIES: &str = "deliveries";
+/// Time-ordered index of successful deliveries: `{016x epoch_ms}:{016x seq}:{event_id}`
+/// (older databases: `{016x epoch_ms}:{event_id}`)
+const CF_SUCCESS: &str = "success";
+/// Newest `created_at` seen per relay, keyed by relay URL
+const CF_CURSORS: &str = "cursors";
+/// Next success sequence number, in the cursors family (no URL starts with NUL)
+const SUCCESS_SEQ_KEY: &[u8] = b"\0success_seq";
+/// Dynamic relay set restored on startup, keyed by relay URL
+const CF_RELAYS: &str = "relays";
+/// Events of kinds with limited retention, by expiry: `{016x expires_at}:{event_id}`
+const CF_EXPIRY: &str = "expiry";
+
+/// Hex event ids are the whole key of the id-keyed families
+const EVENT_ID_HEX_LEN: usize = 64;
+
+/// Layout version stored in the default column family
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+/// 1: everything in the default family behind `evt:`/`fwd:`/`pnd:`/`succ:`/`cur:`/`rly:`
+/// prefixes. 2: one column family per record type. 3: secondary event indexes.
+const SCHEMA_VERSION: u64 = 3;
+/// Legacy key prefixes and the column families they move to
+const LEGACY_PREFIXES: [(&[u8], &str); 6] = [
+    (b"evt:", CF_EVENTS),
+    (b"fwd:", CF_FORWARDED),
+    (b"pnd:", CF_PENDING),
+    (b"succ:", CF_SUCCESS),
+    (b"cur:", CF_CURSORS),
+    (b"rly:", CF_RELAYS),
+];
+/// Keys moved per write batch during migration
+const MIGRATION_BATCH: usize = 10_000;
+/// Retention policy the expiry index was built for, in the default column family
+const RETENTION_POLICY_KEY: &[u8] = b"retention_policy";
+
+/// A relay in the persisted relay set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayRecord {
+    pub url: String,
+    /// Origin of the relay, e.g. `config`, `api`, `nip65:<pubkey>` or `standby`
+    pub added_by: String,
+    /// Unix seconds when the relay joined the set
+    pub added_at: u64,
+}
+
+/// Fields of a stored event needed to place it in the expiry index
+#[derive(Deserialize)]
+struct EventHeader {
+    created_at: u64,
+    kind: u16,
+}
+
+/// Outcome of a `prune_expired` pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    /// Events deleted
+    pub events: u64,
+    /// Key and value bytes deleted across all column families; disk space is
+    /// released as RocksDB compacts the affected files
+    pub bytes: u64,
+}
+
+/// Summary of a verified checkpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CheckpointReport {
+    pub schema_version: u64,
+    /// Stored events, each with a valid id
+    pub events: u64,
+    /// Keys read across all column families
+    pub keys: u64,
+}
+
+/// Where `create_checkpoint` writes and how many checkpoints it keeps
+struct CheckpointPolicy {
+    dir: PathBuf,
+    /// Newest checkpoints kept (0 = all)
+    keep: usize,
+}
 
 /// Persistent storage using RocksDB for event deduplication and archival
+/// Each record type has its own column family, so scans and counts only touch
+/// their own keys. `DB` is thread-safe on its own; every operation runs on the
+/// blocking pool so disk I/O never stalls the async runtime.
 pub struct RocksDBStore {
-    db: Arc<RwLock<DB>>,
+    db: Arc<DB>,
+    /// Serializes read-modify-write updates of relay records
+    relay_records: Arc<Mutex<()>>,
+    /// Maximum age in seconds per kind; kinds not listed are kept forever
+    retention: HashMap<u16, u64>,
+    checkpoints: Option<Arc<CheckpointPolicy>>,
+    /// Orders success index entries written in the same millisecond
+    success_seq: Arc<AtomicU64>,
 }
 
 impl RocksDBStore {
     /// Open or create a RocksDB database at the specified path
+    /// Databases from before column families are migrated in place.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
@@ -26,146 +133,931 @@ impl RocksDBStore {
         // Enable compression
         opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
 
-        let db = DB::open(&opts, path).context("Failed to open RocksDB database")?;
+        let families = [CF_EVENTS, CF_FORWARDED, CF_PENDING, CF_DELIVERIES]
+            .map(|name| ColumnFamilyDescriptor::new(name, Self::id_keyed_options(&opts)))
+            .into_iter()
+            .chain(
+                [CF_SUCCESS, CF_CURSORS, CF_RELAYS, CF_EXPIRY]
+                    .into_iter()
+                    .chain(INDEX_FAMILIES)
+                    .map(|name| ColumnFamilyDescriptor::new(name, opts.clone())),
+            );
+        let db = DB::open_cf_descriptors(&opts, path, families)
+            .context("Failed to open RocksDB database")?;
+        Self::migrate(&db)?;
+        let success_seq = Self::next_success_seq(&db)?;
 
         Ok(Self {
-            db: Arc::new(RwLock::new(db)),
+            db: Arc::new(db),
+            relay_records: Arc::default(),
+            retention: HashMap::new(),
+            checkpoints: None,
+            success_seq: Arc::new(AtomicU64::new(success_seq)),
         })
     }
 
-    #[inline]
-    fn key_event(event_id: &str) -> Vec<u8> {
-        // Event payload storage
-        let mut key = Vec::with_capacity(4 + event_id.len());
-        key.extend_from_slice(b"evt:");
-        key.extend_from_slice(event_id.as_bytes());
-        key
+    /// Write `create_checkpoint` checkpoints to `dir`, keeping the newest `keep` (0 = all)
+    pub fn with_checkpoints(mut self, dir: impl Into<PathBuf>, keep: usize) -> Self {
+        self.checkpoints = Some(Arc::new(CheckpointPolicy {
+            dir: dir.into(),
+            keep,
+        }));
+        self
     }
 
-    #[inline]
-    fn key_forward_status(event_id: &str) -> Vec<u8> {
-        // Forwarding status for quick lookup
-        let mut key = Vec::with_capacity(4 + event_id.len());
-        key.extend_from_slice(b"fwd:");
-        key.extend_from_slice(event_id.as_bytes());
-        key
+    /// Prune events of the given kinds once they are older than their maximum
+    /// age in seconds (see `prune_expired`). When the policy differs from the
+    /// one the expiry index was built for, it is rebuilt from the stored events.
+    pub fn with_retention(mut self, retention: HashMap<u16, u64>) -> Result<Self> {
+        let mut policy: Vec<_> = retention
+            .iter()
+            .map(|(kind, max_age)| format!("{kind}={max_age}"))
+            .collect();
+        policy.sort();
+        let policy = policy.join(",");
+        let built_for = self
+            .db
+            .get(RETENTION_POLICY_KEY)
+            .context("Failed to read retention policy")?;
+        if built_for.as_deref() != Some(policy.as_bytes()) {
+            Self::rebuild_expiry_index(&self.db, &retention)?;
+            self.db
+                .put(RETENTION_POLICY_KEY, policy)
+                .context("Failed to store retention policy")?;
+        }
+        self.retention = retention;
+        Ok(self)
+    }
+
+    /// Replace the expiry index with one entry per stored event of a kind in `retention`
+    fn rebuild_expiry_index(db: &DB, retention: &HashMap<u16, u64>) -> Result<()> {
+        let expiry = cf(db, CF_EXPIRY)?;
+        let mut batch = WriteBatch::default();
+        for item in db.iterator_cf(expiry, IteratorMode::Start) {
+            let (key, _) = item.context("Failed to read expiry index")?;
+            batch.delete_cf(expiry, key);
+        }
+
+        let mut indexed = 0usize;
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_total_order_seek(true);
+        for item in db.iterator_cf_opt(cf(db, CF_EVENTS)?, read_opts, IteratorMode::Start) {
+            let (key, value) = item.context("Failed to read stored event")?;
+            let (Ok(event_id), Ok(header)) = (
+                std::str::from_utf8(&key),
+                serde_json::from_slice::<EventHeader>(&value),
+            ) else {
+                continue;
+            };
+            let Some(max_age) = retention.get(&header.kind) else {
+                continue;
+            };
+            let expires_at = header.created_at.saturating_add(*max_age);
+            batch.put_cf(expiry, Self::key_time_ordered(expires_at, event_id), []);
+            indexed += 1;
+            if indexed.is_multiple_of(MIGRATION_BATCH) {
+                db.write(std::mem::take(&mut batch))
+                    .context("Failed to rebuild expiry index")?;
+            }
+        }
+        db.write(batch).context("Failed to rebuild expiry index")?;
+        tracing::info!("Rebuilt retention index with {} expiring events", indexed);
+        Ok(())
+    }
+
+    /// Options for families keyed by event id: the id is the prefix, so point
+    /// lookups (`exists`, the dedupe hot path) are answered by prefix Bloom
+    /// filters in the memtable and SST files without touching data blocks
+    fn id_keyed_options(base: &Options) -> Options {
+        let mut opts = base.clone();
+        opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(EVENT_ID_HEX_LEN));
+        opts.set_memtable_prefix_bloom_ratio(0.1);
+        let mut table = BlockBasedOptions::default();
+        table.set_bloom_filter(10.0, false);
+        opts.set_block_based_table_factory(&table);
+        opts
+    }
+
+    /// Sequence number to resume from: past both the persisted counter and the
+    /// newest success entry, which concurrent marks may have written out of order
+    fn next_success_seq(db: &DB) -> Result<u64> {
+        let persisted = db
+            .get_cf(cf(db, CF_CURSORS)?, SUCCESS_SEQ_KEY)
+            .context("Failed to read success sequence")?
+            .and_then(|v| v.as_slice().try_into().ok().map(u64::from_be_bytes))
+            .unwrap_or(0);
+        let newest = db
+            .iterator_cf(cf(db, CF_SUCCESS)?, IteratorMode::End)
+            .next()
+            .transpose()
+            .context("Failed to read success index")?
+            .and_then(|(key, _)| {
+                let key = std::str::from_utf8(&key).ok()?;
+                let mut parts = key.split(':');
+                let (_, seq, _) = (parts.next()?, parts.next()?, parts.next()?);
+                u64::from_str_radix(seq, 16).ok()
+            })
+            .map_or(0, |seq| seq + 1);
+        Ok(persisted.max(newest))
+    }
+
+    /// Bring databases written by older versions to the current layout
+    fn migrate(db: &DB) -> Result<()> {
+        let version = db
+            .get(SCHEMA_VERSION_KEY)
+            .context("Failed to read schema version")?
+            .and_then(|v| v.as_slice().try_into().ok().map(u64::from_be_bytes))
+            .unwrap_or(1);
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+        if version < 2 {
+            Self::migrate_legacy_keys(db)?;
+        }
+        Self::build_event_indexes(db)?;
+        db.put(SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_be_bytes())
+            .context("Failed to store schema version")
+    }
+
+    /// Move keys from the single-family layout into their column families
+    fn migrate_legacy_keys(db: &DB) -> Result<()> {
+        let mut moved = 0usize;
+        let mut batch = WriteBatch::default();
+        for item in db.iterator(IteratorMode::Start) {
+            let (key, value) = item.context("Failed to read legacy key")?;
+            let Some((prefix, family)) = LEGACY_PREFIXES
+                .iter()
+                .find(|(prefix, _)| key.starts_with(prefix))
+            else {
+                continue;
+            };
+            batch.put_cf(cf(db, family)?, &key[prefix.len()..], value);
+            batch.delete(&key);
+            moved += 1;
+            if moved.is_multiple_of(MIGRATION_BATCH) {
+                db.write(std::mem::take(&mut batch))
+                    .context("Failed to migrate legacy keys")?;
+            }
+        }
+        db.write(batch).context("Failed to migrate legacy keys")?;
+        if moved > 0 {
+            tracing::info!("Migrated {} RocksDB keys into column families", moved);
+        }
+        Ok(())
+    }
+
+    /// Add the secondary index entries of every stored event
+    fn build_event_indexes(db: &DB) -> Result<()> {
+        let mut indexed = 0usize;
+        let mut batch = WriteBatch::default();
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_total_order_seek(true);
+        for item in db.iterator_cf_opt(cf(db, CF_EVENTS)?, read_opts, IteratorMode::Start) {
+            let (key, value) = item.context("Failed to read stored event")?;
+            let Ok(event) = serde_json::from_slice::<Event>(&value) else {
+                tracing::warn!(
+                    "Not indexing corrupt event {}",
+                    String::from_utf8_lossy(&key)
+                );
+                continue;
+            };
+            for (family, index_key) in event_index::index_entries(&event) {
+                batch.put_cf(cf(db, family)?, index_key, []);
+            }
+            indexed += 1;
+            if indexed.is_multiple_of(MIGRATION_BATCH) {
+                db.write(std::mem::take(&mut batch))
+                    .context("Failed to build event indexes")?;
+            }
+        }
+        db.write(batch).context("Failed to build event indexes")?;
+        if indexed > 0 {
+            tracing::info!("Indexed {} stored events", indexed);
+        }
+        Ok(())
+    }
+
+    /// Write an event with its index and expiry entries in one batch
+    async fn put_event(&self, event: &Event, pending: bool) -> Result<()> {
+        let event_id = event.id.to_string();
+        let serialized = serde_json::to_vec(event).context("Failed to serialize event")?;
+        let expires_at = self
+            .retention
+            .get(&event.kind.as_u16())
+            .map(|max_age| event.created_at.as_secs().saturating_add(*max_age));
+        let index_entries = event_index::index_entries(event);
+        self.blocking(move |db| {
+            let mut batch = WriteBatch::default();
+            batch.put_cf(cf(db, CF_EVENTS)?, &event_id, serialized);
+            if pending {
+                batch.put_cf(cf(db, CF_PENDING)?, &event_id, []);
+            }
+            for (family, index_key) in index_entries {
+                batch.put_cf(cf(db, family)?, index_key, []);
+            }
+            if let Some(expires_at) = expires_at {
+                batch.put_cf(
+                    cf(db, CF_EXPIRY)?,
+                    Self::key_time_ordered(expires_at, &event_id),
+                    [],
+                );
+            }
+            db.write(batch).context("Failed to store event in RocksDB")
+        })
+        .await
+    }
+
+    /// Run a RocksDB operation on the blocking thread pool
+    async fn blocking<T, F>(&self, op: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&DB) -> Result<T> + Send + 'static,
+    {
+        let db = self.db.clone();
+        run_blocking(move || op(&db)).await
     }
 
     #[inline]
-    fn key_success_index(epoch_ms: i64, event_id: &str) -> Vec<u8> {
-        // Time-ordered index for recent successful deliveries
-        // Format: "succ:{016x}:{event_id}" where time is hex, zero-padded for lexical sort
+    fn key_time_ordered(ts: u64, event_id: &str) -> Vec<u8> {
+        // Time-ordered index for successful deliveries and expiry
+        // Format: "{016x}:{event_id}" where time is hex, zero-padded for lexical sort
         // Using hex keeps keys ASCII and sorted lexicographically in time order.
-        let mut key = Vec::with_capacity(5 + 16 + 1 + event_id.len());
-        key.extend_from_slice(b"succ:");
-        let ts_hex = format!("{:016x}", epoch_ms as u64);
+        let mut key = Vec::with_capacity(16 + 1 + event_id.len());
+        let ts_hex = format!("{:016x}", ts);
         key.extend_from_slice(ts_hex.as_bytes());
         key.push(b':');
         key.extend_from_slice(event_id.as_bytes());
         key
     }
 
-    /// Check if an event ID exists in the database
-    pub async fn exists(&self, event_id: &str) -> bool {
-        let db = self.db.read().await;
-        match db.get(Self::key_event(event_id)) {
-            Ok(Some(_)) => true,
-            _ => false,
+    fn success_key(now_ms: u64, seq: u64, event_id: &str) -> Vec<u8> {
+        format!("{now_ms:016x}:{seq:016x}:{event_id}").into_bytes()
+    }
+
+    /// Success index key recorded in an event's `forwarded` value
+    fn forwarded_success_key(forwarded: &[u8], event_id: &str) -> Option<Vec<u8>> {
+        match forwarded.try_into().map(u64::from_be_bytes) {
+            // Written before success keys carried a sequence number
+            Ok(forwarded_ms) => Some(Self::key_time_ordered(forwarded_ms, event_id)),
+            Err(_) if forwarded.ends_with(event_id.as_bytes()) => Some(forwarded.to_vec()),
+            Err(_) => None,
         }
     }
 
-    /// Store an event in the database
-    pub async fn store_event(&self, event: &Event) -> Result<()> {
-        let event_id = event.id.to_string();
-        let serialized = serde_json::to_vec(event).context("Failed to serialize event")?;
+    /// Queue deletion of the delivery steps recorded for an event
+    fn delete_deliveries(db: &DB, batch: &mut WriteBatch, event_id: &str) -> Result<()> {
+        let mut end = event_id.as_bytes().to_vec();
+        end.push(0xff);
+        batch.delete_range_cf(cf(db, CF_DELIVERIES)?, event_id.as_bytes(), &end);
+        Ok(())
+    }
 
-        let db = self.db.write().await;
-        db.put(Self::key_event(&event_id), serialized)
-            .context("Failed to store event in RocksDB")?;
+    /// Queue deletion of the index entries of a stored event; returns their key bytes
+    fn delete_index_entries(db: &DB, batch: &mut WriteBatch, data: &[u8]) -> Result<u64> {
+        let Ok(event) = serde_json::from_slice::<Event>(data) else {
+            return Ok(0);
+        };
+        let mut bytes = 0;
+        for (family, index_key) in event_index::index_entries(&event) {
+            bytes += index_key.len() as u64;
+            batch.delete_cf(cf(db, family)?, index_key);
+        }
+        Ok(bytes)
+    }
 
-        Ok(())
+    /// Queue deletion of an event and every record kept for it: index, expiry,
+    /// forwarding status, success index, pending and delivery entries
+    fn delete_records(
+        db: &DB,
+        batch: &mut WriteBatch,
+        event_id: &str,
+        retention: &HashMap<u16, u64>,
+    ) -> Result<PruneStats> {
+        let mut stats = PruneStats::default();
+        Self::delete_deliveries(db, batch, event_id)?;
+        for family in [CF_EVENTS, CF_FORWARDED, CF_PENDING] {
+            let handle = cf(db, family)?;
+            let Some(value) = db
+                .get_pinned_cf(handle, event_id)
+                .context("Failed to read stored event")?
+            else {
+                continue;
+            };
+            stats.bytes += (event_id.len() + value.len()) as u64;
+            if family == CF_EVENTS {
+                stats.bytes += Self::delete_index_entries(db, batch, &value)?;
+                stats.events += 1;
+                if let Ok(header) = serde_json::from_slice::<EventHeader>(&value)
+                    && let Some(max_age) = retention.get(&header.kind)
+                {
+                    let expires_at = header.created_at.saturating_add(*max_age);
+                    batch.delete_cf(
+                        cf(db, CF_EXPIRY)?,
+                        Self::key_time_ordered(expires_at, event_id),
+                    );
+                }
+            }
+            if family == CF_FORWARDED
+                && let Some(success_key) = Self::forwarded_success_key(&value, event_id)
+            {
+                stats.bytes += success_key.len() as u64;
+                batch.delete_cf(cf(db, CF_SUCCESS)?, success_key);
+            }
+            batch.delete_cf(handle, event_id);
+        }
+        Ok(stats)
+    }
+
+    /// Delete up to `limit` events whose retention ended at or before `now`
+    /// (unix seconds), along with their forwarding status and success index.
+    /// Events still pending delivery are kept until they have been forwarded.
+    /// Duplicates of a pruned event are no longer detected.
+    pub async fn prune_expired(&self, now: u64, limit: usize) -> Result<PruneStats> {
+        let retention = self.retention.clone();
+        self.blocking(move |db| {
+            let expiry = cf(db, CF_EXPIRY)?;
+            let mut stats = PruneStats::default();
+            let mut batch = WriteBatch::default();
+            for item in db.iterator_cf(expiry, IteratorMode::Start) {
+                if stats.events as usize >= limit {
+                    break;
+                }
+                let (key, _) = item.context("Failed to read expiry index")?;
+                let Some((expires_at, event_id)) = std::str::from_utf8(&key)
+                    .ok()
+                    .and_then(|k| k.split_once(':'))
+                    .and_then(|(ts, id)| Some((u64::from_str_radix(ts, 16).ok()?, id)))
+                else {
+                    continue;
+                };
+                if expires_at > now {
+                    break;
+                }
+                if db
+                    .get_pinned_cf(cf(db, CF_PENDING)?, event_id)
+                    .context("Failed to read pending entry")?
+                    .is_some()
+                {
+                    continue;
+                }
+                batch.delete_cf(expiry, &key);
+                stats.bytes += key.len() as u64;
+                let deleted = Self::delete_records(db, &mut batch, event_id, &retention)?;
+                stats.events += deleted.events;
+                stats.bytes += deleted.bytes;
+            }
+            db.write(batch).context("Failed to prune expired events")?;
+            Ok(stats)
+        })
+        .await
+    }
+
+    /// Write a consistent copy of the database to `path` (which must not exist)
+    /// while it stays open for writes. Files are hard-linked where possible.
+    pub async fn checkpoint(&self, path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        self.blocking(move |db| write_checkpoint(db, &path)).await
+    }
+
+    /// Read every key of a checkpoint (or stopped database) without opening it
+    /// for writes: block checksums are verified on read, every event must
+    /// deserialize under its own id, and the layout must not be newer than
+    /// this version understands
+    pub async fn verify_checkpoint(path: &Path) -> Result<CheckpointReport> {
+        let path = path.to_path_buf();
+        run_blocking(move || Self::verify_checkpoint_at(&path)).await
+    }
+
+    fn verify_checkpoint_at(path: &Path) -> Result<CheckpointReport> {
+        let families = DB::list_cf(&Options::default(), path)
+            .with_context(|| format!("No RocksDB database at {}", path.display()))?;
+        let db = DB::open_cf_for_read_only(&Options::default(), path, &families, false)
+            .context("Failed to open checkpoint")?;
+
+        let schema_version = db
+            .get(SCHEMA_VERSION_KEY)
+            .context("Failed to read schema version")?
+            .and_then(|v| v.as_slice().try_into().ok().map(u64::from_be_bytes))
+            .unwrap_or(1);
+        if schema_version > SCHEMA_VERSION {
+            return Err(anyhow!(
+                "Checkpoint schema version {} is newer than supported ({})",
+                schema_version,
+                SCHEMA_VERSION
+            ));
+        }
+
+        let mut report = CheckpointReport {
+            schema_version,
+            ..Default::default()
+        };
+        for family in &families {
+            let mut read_opts = ReadOptions::default();
+            read_opts.set_verify_checksums(true);
+            read_opts.set_total_order_seek(true);
+            for item in db.iterator_cf_opt(cf(&db, family)?, read_opts, IteratorMode::Start) {
+                let (key, value) =
+                    item.with_context(|| format!("Corrupt data in column family {}", family))?;
+                report.keys += 1;
+                if family != CF_EVENTS {
+                    continue;
+                }
+                let event: Event = serde_json::from_slice(&value)
+                    .with_context(|| format!("Corrupt event {}", String::from_utf8_lossy(&key)))?;
+                if !event.verify_id() || event.id.to_hex().as_bytes() != key.as_ref() {
+                    return Err(anyhow!(
+                        "Event {} does not match its id",
+                        String::from_utf8_lossy(&key)
+                    ));
+                }
+                report.events += 1;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Replace the database at `target` with a verified copy of `checkpoint`.
+    /// The relayer must be stopped; an existing database is kept next to it as
+    /// `{target}.pre-restore-{timestamp}`.
+    pub async fn restore_checkpoint(checkpoint: &Path, target: &Path) -> Result<CheckpointReport> {
+        let (checkpoint, target) = (checkpoint.to_path_buf(), target.to_path_buf());
+        run_blocking(move || Self::restore_checkpoint_to(&checkpoint, &target)).await
+    }
+
+    fn restore_checkpoint_to(checkpoint: &Path, target: &Path) -> Result<CheckpointReport> {
+        let report = Self::verify_checkpoint_at(checkpoint)?;
+        if target.exists() {
+            // Fails while a running relayer holds the database lock
+            let families = DB::list_cf(&Options::default(), target).unwrap_or_default();
+            drop(
+                DB::open_cf(&Options::default(), target, &families)
+                    .context("Database is in use or unreadable; stop the relayer first")?,
+            );
+        }
+
+        let staging = sibling(target, "restoring");
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging).context("Failed to clear an interrupted restore")?;
+        }
+        std::fs::create_dir_all(&staging).context("Failed to create restore directory")?;
+        for entry in std::fs::read_dir(checkpoint).context("Failed to list checkpoint")? {
+            let entry = entry.context("Failed to list checkpoint")?;
+            if entry.file_type().is_ok_and(|t| t.is_file()) {
+                std::fs::copy(entry.path(), staging.join(entry.file_name()))
+                    .context("Failed to copy checkpoint file")?;
+            }
+        }
+
+        if target.exists() {
+            let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+            let previous = sibling(target, &format!("pre-restore-{timestamp}"));
+            std::fs::rename(target, &previous)
+                .context("Failed to move the existing database aside")?;
+            tracing::info!("Previous database kept at {}", previous.display());
+        }
+        std::fs::rename(&staging, target).context("Failed to move restored database in place")?;
+        Ok(report)
+    }
+
+    /// Load the persisted `created_at` cursor for a relay
+    pub async fn get_relay_cursor(&self, relay_url: &str) -> Option<u64> {
+        let key = relay_url.to_string();
+        self.blocking(move |db| {
+            Ok(match db.get_pinned_cf(cf(db, CF_CURSORS)?, key) {
+                Ok(Some(bytes)) => bytes.as_ref().try_into().ok().map(u64::from_be_bytes),
+                _ => None,
+            })
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Persist the `created_at` cursor for a relay
+    pub async fn set_relay_cursor(&self, relay_url: &str, created_at: u64) -> Result<()> {
+        let key = relay_url.to_string();
+        self.blocking(move |db| {
+            db.put_cf(cf(db, CF_CURSORS)?, key, created_at.to_be_bytes())
+                .context("Failed to store relay cursor")
+        })
+        .await
+    }
+
+    /// Load the persisted relay set
+    pub async fn load_relay_records(&self) -> Vec<RelayRecord> {
+        self.blocking(|db| {
+            let mut records = Vec::new();
+            for item in db.iterator_cf(cf(db, CF_RELAYS)?, IteratorMode::Start) {
+                let Ok((k, v)) = item else { break };
+                match serde_json::from_slice::<RelayRecord>(&v) {
+                    Ok(record) => records.push(record),
+                    Err(e) => tracing::warn!(
+                        "Skipping corrupt relay record {}: {}",
+                        String::from_utf8_lossy(&k),
+                        e
+                    ),
+                }
+            }
+            Ok(records)
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Add a relay to the persisted relay set, keeping an existing record as is
+    pub async fn put_relay_record(&self, record: &RelayRecord) -> Result<()> {
+        let key = record.url.clone();
+        let serialized = serde_json::to_vec(record).context("Failed to serialize relay record")?;
+        let relay_records = self.relay_records.clone();
+        self.blocking(move |db| {
+            let _guard = relay_records.lock().expect("relay record lock poisoned");
+            let relays = cf(db, CF_RELAYS)?;
+            if db
+                .get_pinned_cf(relays, &key)
+                .context("Failed to read relay record")?
+                .is_some()
+            {
+                return Ok(());
+            }
+            db.put_cf(relays, key, serialized)
+                .context("Failed to store relay record")
+        })
+        .await
+    }
+
+    /// Drop a relay from the persisted relay set
+    pub async fn delete_relay_record(&self, relay_url: &str) -> Result<()> {
+        let key = relay_url.to_string();
+        self.blocking(move |db| {
+            db.delete_cf(cf(db, CF_RELAYS)?, key)
+                .context("Failed to delete relay record")
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl EventStore for RocksDBStore {
+    /// Check if an event ID exists in the database
+    async fn exists(&self, event_id: &str) -> bool {
+        let key = event_id.to_string();
+        self.blocking(move |db| {
+            Ok(matches!(
+                db.get_pinned_cf(cf(db, CF_EVENTS)?, key),
+                Ok(Some(_))
+            ))
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    /// Store an event in the database, pending until `mark_forward_success`
+    async fn store_event(&self, event: &Event) -> Result<()> {
+        self.put_event(event, true).await
+    }
+
+    /// Store an event without the pending marker, so it is never replayed
+    async fn archive_event(&self, event: &Event) -> Result<()> {
+        self.put_event(event, false).await
     }
 
     /// Retrieve an event by ID
-    pub async fn get_event(&self, event_id: &str) -> Result<Option<Event>> {
-        let db = self.db.read().await;
-        match db.get(Self::key_event(event_id)) {
+    async fn get_event(&self, event_id: &str) -> Result<Option<Event>> {
+        let key = event_id.to_string();
+        self.blocking(move |db| match db.get_pinned_cf(cf(db, CF_EVENTS)?, key) {
             Ok(Some(data)) => {
                 let event: Event =
                     serde_json::from_slice(&data).context("Failed to deserialize event")?;
                 Ok(Some(event))
             }
             Ok(None) => Ok(None),
-            Err(e) => Err(anyhow::anyhow!("Database error: {}", e)),
-        }
+            Err(e) => Err(anyhow!("Database error: {}", e)),
+        })
+        .await
     }
 
-    /// Delete an event by ID
-    pub async fn delete_event(&self, event_id: &str) -> Result<()> {
-        let db = self.db.write().await;
-        db.delete(Self::key_event(event_id))
-            .context("Failed to delete event from RocksDB")?;
-        Ok(())
+    /// Delete an event by ID with every record kept for it, as pruning does
+    async fn delete_event(&self, event_id: &str) -> Result<()> {
+        let key = event_id.to_string();
+        let retention = self.retention.clone();
+        self.blocking(move |db| {
+            let mut batch = WriteBatch::default();
+            Self::delete_records(db, &mut batch, &key, &retention)?;
+            db.write(batch)
+                .context("Failed to delete event from RocksDB")
+        })
+        .await
     }
 
-    /// Get approximate number of events in the database
-    pub async fn approximate_count(&self) -> u64 {
-        let db = self.db.read().await;
-        // This is an approximation, actual count may vary
-        db.iterator(rocksdb::IteratorMode::Start).count() as u64
+    /// Approximate number of stored events, from RocksDB's own key estimate
+    /// (constant time, unlike counting keys)
+    async fn approximate_count(&self) -> u64 {
+        self.blocking(|db| {
+            Ok(db
+                .property_int_value_cf(cf(db, CF_EVENTS)?, "rocksdb.estimate-num-keys")?
+                .unwrap_or(0))
+        })
+        .await
+        .unwrap_or(0)
     }
 
-    /// Mark an event as successfully forwarded to downstream(s)
-    pub async fn mark_forward_success(&self, event_id: &str) -> Result<()> {
-        let now_ms = chrono::Utc::now().timestamp_millis();
-        let mut batch = rocksdb::WriteBatch::default();
-        batch.put(Self::key_forward_status(event_id), b"1");
-        batch.put(Self::key_success_index(now_ms, event_id), &[]);
-        let db = self.db.write().await;
-        db.write(batch).context("Failed to mark forward success")?;
-        Ok(())
+    /// Mark an event as successfully forwarded to downstream(s); marking it
+    /// again moves its success index entry to the newest position
+    async fn mark_forward_success(&self, event_id: &str) -> Result<()> {
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let seq = self.success_seq.fetch_add(1, Ordering::Relaxed);
+        let event_id = event_id.to_string();
+        self.blocking(move |db| {
+            let forwarded = cf(db, CF_FORWARDED)?;
+            let success = cf(db, CF_SUCCESS)?;
+            let success_key = Self::success_key(now_ms, seq, &event_id);
+            let mut batch = WriteBatch::default();
+            if let Some(previous) = db
+                .get_pinned_cf(forwarded, &event_id)
+                .context("Failed to read forwarding status")?
+                .and_then(|value| Self::forwarded_success_key(&value, &event_id))
+            {
+                batch.delete_cf(success, previous);
+            }
+            batch.put_cf(forwarded, &event_id, &success_key);
+            batch.put_cf(success, &success_key, []);
+            batch.put_cf(
+                cf(db, CF_CURSORS)?,
+                SUCCESS_SEQ_KEY,
+                (seq + 1).to_be_bytes(),
+            );
+            batch.delete_cf(cf(db, CF_PENDING)?, &event_id);
+            Self::delete_deliveries(db, &mut batch, &event_id)?;
+            db.write(batch).context("Failed to mark forward success")
+        })
+        .await
+    }
+
+    async fn record_deliveries(&self, event_id: &str, steps: &[String]) -> Result<()> {
+        let keys: Vec<String> = steps
+            .iter()
+            .map(|step| format!("{event_id}{step}"))
+            .collect();
+        self.blocking(move |db| {
+            let deliveries = cf(db, CF_DELIVERIES)?;
+            let mut batch = WriteBatch::default();
+            for key in keys {
+                batch.put_cf(deliveries, key, []);
+            }
+            db.write(batch).context("Failed to record deliveries")
+        })
+        .await
+    }
+
+    async fn completed_deliveries(&self, event_id: &str) -> Result<HashSet<String>> {
+        let event_id = event_id.to_string();
+        self.blocking(move |db| {
+            let mut steps = HashSet::new();
+            for item in db.prefix_iterator_cf(cf(db, CF_DELIVERIES)?, &event_id) {
+                let (key, _) = item.context("Failed to read deliveries")?;
+                let Some(step) = key.strip_prefix(event_id.as_bytes()) else {
+                    break;
+                };
+                steps.insert(String::from_utf8_lossy(step).into_owned());
+            }
+            Ok(steps)
+        })
+        .await
     }
 
     /// Check whether an event has been marked as successfully forwarded
-    pub async fn is_forward_success(&self, event_id: &str) -> bool {
-        let db = self.db.read().await;
-        matches!(db.get(Self::key_forward_status(event_id)), Ok(Some(_)))
+    async fn is_forward_success(&self, event_id: &str) -> bool {
+        let key = event_id.to_string();
+        self.blocking(move |db| {
+            Ok(matches!(
+                db.get_pinned_cf(cf(db, CF_FORWARDED)?, key),
+                Ok(Some(_))
+            ))
+        })
+        .await
+        .unwrap_or(false)
     }
 
     /// Load up to `limit` most recent successfully forwarded event IDs (most recent first)
-    pub async fn load_recent_success_ids(&self, limit: usize) -> Vec<String> {
+    async fn load_recent_success_ids(&self, limit: usize) -> Vec<String> {
         if limit == 0 {
             return Vec::new();
         }
-        let db = self.db.read().await;
-        let mut iter = db.iterator(IteratorMode::End);
-        let mut result = Vec::with_capacity(limit.min(1024));
-        while result.len() < limit {
-            match iter.next() {
-                Some(Ok((k, _v))) => {
-                    // Only consider keys with "succ:" prefix
-                    if k.starts_with(b"succ:") {
-                        // key format: succ:{016x}:{event_id}
-                        if let Some(pos) = k.iter().position(|b| *b == b':') {
-                            // find the second colon
-                            let second = k
-                                .iter()
-                                .enumerate()
-                                .skip(pos + 1)
-                                .find(|(_, b)| **b == b':');
-                            if let Some((second_idx, _)) = second {
-                                // event id starts after second colon
-                                let event_id_bytes = &k[second_idx + 1..];
-                                if let Ok(event_id) = std::str::from_utf8(event_id_bytes) {
-                                    result.push(event_id.to_string());
-                                }
-                            }
+        self.blocking(move |db| {
+            let mut result = Vec::with_capacity(limit.min(1024));
+            // key format: {016x}:{016x seq}:{event_id}, the id always last
+            for item in db.iterator_cf(cf(db, CF_SUCCESS)?, IteratorMode::End) {
+                if result.len() >= limit {
+                    break;
+                }
+                let Ok((k, _v)) = item else { break };
+                if let Some(pos) = k.iter().rposition(|b| *b == b':')
+                    && let Ok(event_id) = std::str::from_utf8(&k[pos + 1..])
+                {
+                    result.push(event_id.to_string());
+                }
+            }
+            Ok(result)
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Load up to `limit` stored events that were never marked as forwarded
+    async fn load_pending_events(&self, limit: usize) -> Vec<Event> {
+        self.blocking(move |db| {
+            let mut read_opts = ReadOptions::default();
+            // Scan the whole family rather than one id prefix
+            read_opts.set_total_order_seek(true);
+            let events_cf = cf(db, CF_EVENTS)?;
+            let mut events = Vec::new();
+            for item in db.iterator_cf_opt(cf(db, CF_PENDING)?, read_opts, IteratorMode::Start) {
+                if events.len() >= limit {
+                    break;
+                }
+                let Ok((k, _)) = item else { break };
+                let Ok(event_id) = std::str::from_utf8(&k) else {
+                    continue;
+                };
+                match db.get_pinned_cf(events_cf, event_id) {
+                    Ok(Some(data)) => match serde_json::from_slice::<Event>(&data) {
+                        Ok(event) => events.push(event),
+                        Err(e) => {
+                            tracing::warn!("Skipping corrupt pending event {}: {}", event_id, e)
                         }
+                    },
+                    _ => tracing::warn!("Pending event {} has no stored payload", event_id),
+                }
+            }
+            Ok(events)
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+/// Archive reads and backups, which only the RocksDB backend offers
+impl RocksDBStore {
+    /// Events matching an indexed query, newest first
+    pub async fn query_events(&self, query: EventQuery) -> Result<Vec<Event>> {
+        self.blocking(move |db| {
+            let (family, scopes) = query
+                .scopes()
+                .ok_or_else(|| anyhow!("Query needs authors, kinds or an indexed tag"))?;
+            let index = cf(db, family)?;
+            let events_cf = cf(db, CF_EVENTS)?;
+            let mut found: Vec<(EventCursor, Event)> = Vec::new();
+            let mut seen = HashSet::new();
+            for scope in scopes {
+                // Each scope contributes at most `limit` matches, so the newest
+                // `limit` of their union is exact
+                let mut matched = 0;
+                let upper = query.upper_bound(&scope);
+                for item in db.iterator_cf(index, IteratorMode::From(&upper, Direction::Reverse)) {
+                    if matched >= query.limit {
+                        break;
+                    }
+                    let (key, _) = item.context("Failed to read event index")?;
+                    let Some(position) = event_index::position(&key, &scope) else {
+                        break;
+                    };
+                    if query.since.is_some_and(|since| position.created_at < since) {
+                        break;
+                    }
+                    if seen.contains(&position.id) {
+                        matched += 1;
+                        continue;
+                    }
+                    let Some(data) = db
+                        .get_pinned_cf(events_cf, &position.id)
+                        .context("Failed to read indexed event")?
+                    else {
+                        continue;
+                    };
+                    let event: Event =
+                        serde_json::from_slice(&data).context("Failed to deserialize event")?;
+                    if query.matches(&event) {
+                        seen.insert(position.id.clone());
+                        found.push((position, event));
+                        matched += 1;
                     }
                 }
-                _ => break,
             }
-        }
-        result
+            found.sort_by(|a, b| b.0.cmp(&a.0));
+            found.truncate(query.limit);
+            Ok(found.into_iter().map(|(_, event)| event).collect())
+        })
+        .await
+    }
+
+    /// Write a timestamped checkpoint to the configured directory and drop the
+    /// oldest ones beyond the retention; returns the checkpoint path
+    pub async fn create_checkpoint(&self) -> Result<PathBuf> {
+        let policy = self
+            .checkpoints
+            .clone()
+            .ok_or_else(|| anyhow!("No checkpoint directory configured"))?;
+        self.blocking(move |db| {
+            std::fs::create_dir_all(&policy.dir).with_context(|| {
+                format!(
+                    "Failed to create checkpoint directory {}",
+                    policy.dir.display()
+                )
+            })?;
+            let name = chrono::Utc::now().format("checkpoint-%Y%m%dT%H%M%S%.3fZ");
+            let path = policy.dir.join(name.to_string());
+            write_checkpoint(db, &path)?;
+
+            if policy.keep > 0 {
+                let mut existing: Vec<_> = std::fs::read_dir(&policy.dir)
+                    .context("Failed to list checkpoints")?
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.starts_with("checkpoint-"))
+                    })
+                    .collect();
+                // Names sort by creation time
+                existing.sort();
+                let excess = existing.len().saturating_sub(policy.keep);
+                for old in &existing[..excess] {
+                    if let Err(e) = std::fs::remove_dir_all(old) {
+                        tracing::warn!("Failed to remove checkpoint {}: {}", old.display(), e);
+                    }
+                }
+            }
+            Ok(path)
+        })
+        .await
     }
+
+    /// Send every event matching `filter` to `sink`, in no particular order and
+    /// ignoring `filter.limit`; stops early once the receiver is dropped.
+    /// Streams straight from the events family, so unindexed filters work too.
+    /// Returns the number of events sent.
+    pub async fn export_events(
+        &self,
+        filter: EventQuery,
+        sink: flume::Sender<Event>,
+    ) -> Result<u64> {
+        self.blocking(move |db| {
+            let mut read_opts = ReadOptions::default();
+            read_opts.set_total_order_seek(true);
+            let mut sent = 0;
+            for item in db.iterator_cf_opt(cf(db, CF_EVENTS)?, read_opts, IteratorMode::Start) {
+                let (key, value) = item.context("Failed to read stored event")?;
+                let event: Event = match serde_json::from_slice(&value) {
+                    Ok(event) => event,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Not exporting corrupt event {}: {}",
+                            String::from_utf8_lossy(&key),
+                            e
+                        );
+                        continue;
+                    }
+                };
+                if !filter.matches(&event) {
+                    continue;
+                }
+                if sink.send(event).is_err() {
+                    break;
+                }
+                sent += 1;
+            }
+            Ok(sent)
+        })
+        .await
+    }
+}
+
+/// Run blocking work (RocksDB calls, file system access) on the blocking thread pool
+async fn run_blocking<T, F>(op: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(op)
+        .await
+        .context("RocksDB task panicked")?
+}
+
+fn write_checkpoint(db: &DB, path: &Path) -> Result<()> {
+    Checkpoint::new(db)
+        .and_then(|checkpoint| checkpoint.create_checkpoint(path))
+        .with_context(|| format!("Failed to create checkpoint at {}", path.display()))
+}
+
+/// `{path}.{suffix}` in the same directory as `path`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{suffix}"));
+    path.with_file_name(name)
+}
+
+/// Handle of a column family opened in `RocksDBStore::new`
+fn cf<'a>(db: &'a DB, name: &str) -> Result<&'a ColumnFamily> {
+    db.cf_handle(name)
+        .ok_or_else(|| anyhow!("RocksDB column family {} missing", name))
 }
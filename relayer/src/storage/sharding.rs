@@ -0,0 +1,18 @@
+/// Most shards a cache is split into
+const MAX_SHARDS: usize = 16;
+/// Smallest shard worth splitting off; smaller caches stay a single exact shard
+const MIN_SHARD_CAPACITY: usize = 1024;
+
+/// Number of shards for a cache holding `capacity` items
+pub(crate) fn shard_count(capacity: usize) -> usize {
+    (capacity / MIN_SHARD_CAPACITY).clamp(1, MAX_SHARDS)
+}
+
+/// Shard for a key. Deterministic (FNV-1a), so a snapshot restored in another
+/// process puts every key back into the shard it came from.
+pub(crate) fn shard_of(key: &str, shards: usize) -> usize {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % shards as u64) as usize
+}
@@ -0,0 +1,187 @@
+use crate::storage::bloom_filter::{BloomSnapshot, GenerationSnapshot};
+use anyhow::{Context, Result, bail, ensure};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// File magic for dedupe snapshots
+const MAGIC: &[u8; 8] = b"MTDEDUPE";
+/// Bumped whenever the payload layout changes; older files are ignored
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// In-memory dedupe state written to disk for warm restarts
+///
+/// File layout: magic, version (u32 LE), payload length (u64 LE), payload,
+/// SHA-256 of the payload. Event IDs are stored as their 32 raw bytes.
+#[derive(Debug, Clone)]
+pub struct DedupeSnapshot {
+    /// Unix seconds when the snapshot was taken
+    pub taken_at: u64,
+    pub bloom: BloomSnapshot,
+    /// LRU cache contents, least recently used first
+    pub lru: Vec<[u8; 32]>,
+    /// Hot set contents, oldest first
+    pub hot_set: Vec<[u8; 32]>,
+}
+
+impl DedupeSnapshot {
+    /// Serialize into the versioned, checksummed file format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        put_u64(&mut payload, self.taken_at);
+        put_u64(&mut payload, self.bloom.rotations);
+        put_u64(&mut payload, self.bloom.generations.len() as u64);
+        for g in &self.bloom.generations {
+            put_u64(&mut payload, g.num_bits);
+            payload.extend_from_slice(&g.num_hashes.to_le_bytes());
+            put_u64(&mut payload, g.set_bits);
+            put_u64(&mut payload, g.items);
+            put_u64(&mut payload, g.age.as_millis() as u64);
+            for word in &g.bits {
+                put_u64(&mut payload, *word);
+            }
+        }
+        for ids in [&self.lru, &self.hot_set] {
+            put_u64(&mut payload, ids.len() as u64);
+            for id in ids {
+                payload.extend_from_slice(id);
+            }
+        }
+
+        let mut out = Vec::with_capacity(MAGIC.len() + 12 + payload.len() + 32);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        put_u64(&mut out, payload.len() as u64);
+        out.extend_from_slice(&payload);
+        out.extend_from_slice(&Sha256::digest(&payload));
+        out
+    }
+
+    /// Parse a snapshot, rejecting unknown versions and checksum mismatches
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut header = Reader(bytes);
+        ensure!(header.take(MAGIC.len())? == MAGIC, "not a dedupe snapshot");
+        let version = header.u32()?;
+        ensure!(
+            version == SNAPSHOT_VERSION,
+            "unsupported snapshot version {version}"
+        );
+        let len = usize::try_from(header.u64()?).context("snapshot too large")?;
+        let payload = header.take(len)?;
+        let checksum = header.take(32)?;
+        ensure!(header.0.is_empty(), "trailing bytes after snapshot");
+        ensure!(
+            Sha256::digest(payload)[..] == *checksum,
+            "snapshot checksum mismatch"
+        );
+
+        let mut r = Reader(payload);
+        let taken_at = r.u64()?;
+        let rotations = r.u64()?;
+        let generation_count = r.u64()?;
+        let mut generations = Vec::new();
+        for _ in 0..generation_count {
+            let num_bits = r.u64()?;
+            let num_hashes = r.u32()?;
+            let set_bits = r.u64()?;
+            let items = r.u64()?;
+            let age = Duration::from_millis(r.u64()?);
+            let words = num_bits.div_ceil(64);
+            ensure!(
+                words.saturating_mul(8) <= r.0.len() as u64,
+                "truncated Bloom filter generation"
+            );
+            let bits = (0..words).map(|_| r.u64()).collect::<Result<_>>()?;
+            generations.push(GenerationSnapshot {
+                num_bits,
+                num_hashes,
+                set_bits,
+                items,
+                age,
+                bits,
+            });
+        }
+        let lru = r.ids()?;
+        let hot_set = r.ids()?;
+        ensure!(r.0.is_empty(), "trailing bytes in snapshot payload");
+
+        Ok(Self {
+            taken_at,
+            bloom: BloomSnapshot {
+                rotations,
+                generations,
+            },
+            lru,
+            hot_set,
+        })
+    }
+
+    /// Write the snapshot atomically (temporary file, fsync, rename)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let tmp = path.with_extension("tmp");
+        let mut file = std::fs::File::create(&tmp)
+            .with_context(|| format!("Failed to create {}", tmp.display()))?;
+        file.write_all(&self.encode())
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to move snapshot to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Read a snapshot; `Ok(None)` if the file does not exist
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        Self::decode(&bytes)
+            .with_context(|| format!("Invalid dedupe snapshot {}", path.display()))
+            .map(Some)
+    }
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Cursor over snapshot bytes
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            bail!("truncated snapshot");
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn ids(&mut self) -> Result<Vec<[u8; 32]>> {
+        let count = self.u64()?;
+        ensure!(
+            count.saturating_mul(32) <= self.0.len() as u64,
+            "truncated ID list"
+        );
+        (0..count).map(|_| Ok(self.take(32)?.try_into()?)).collect()
+    }
+}
@@ -0,0 +1,580 @@
+//! In-process test harness: a NIP-01 mock relay, helpers to build signed Moltrade events
+//! (kinds 30931-30935) and a relayer stack (relay pool, event router, WebSocket API)
+//! wired the way `main` wires it.
+
+// Each integration test binary uses a different subset of the harness
+#![allow(dead_code)]
+
+use axum::{
+    Router,
+    extract::State,
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    response::IntoResponse,
+    routing::get,
+};
+use futures_util::StreamExt;
+use moltrade_relayer::api::websocket;
+use moltrade_relayer::config::{OverflowPolicy, default_latest_wins_kinds};
+use moltrade_relayer::core::dedupe_engine::DeduplicationEngine;
+use moltrade_relayer::core::event_router::EventRouter;
+use moltrade_relayer::core::pipeline;
+use moltrade_relayer::core::relay_pool::{ReconnectPolicy, RelayPool};
+use moltrade_relayer::core::subscription::SubscriptionService;
+use moltrade_relayer::storage::rocksdb_store::RocksDBStore;
+use nostr_sdk::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use tokio::sync::{RwLock, broadcast};
+use tokio::task::JoinHandle;
+
+pub const KIND_TRADE_SIGNAL: u16 = 30931;
+pub const KIND_COPYTRADE_INTENT: u16 = 30932;
+pub const KIND_HEARTBEAT: u16 = 30933;
+pub const KIND_EXECUTION_REPORT: u16 = 30934;
+pub const KIND_AGENT_REGISTER: u16 = 30935;
+
+/// How long tests wait for something that should happen
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something the relay pushes to every open connection
+#[derive(Debug, Clone)]
+enum Push {
+    Event(Event),
+    Notice(String),
+    CloseSubscriptions(String),
+}
+
+struct RelayState {
+    url: String,
+    events: RwLock<Vec<Event>>,
+    push: broadcast::Sender<Push>,
+    /// When set, REQs are CLOSED with `auth-required:` until the connection authenticates
+    auth_challenge: Option<String>,
+}
+
+/// Minimal NIP-01 relay on localhost: stores events, serves REQ backfill followed by EOSE,
+/// streams live events to open subscriptions and optionally requires NIP-42 AUTH
+pub struct MockRelay {
+    state: Arc<RelayState>,
+    server: JoinHandle<()>,
+}
+
+impl MockRelay {
+    pub async fn start() -> Self {
+        Self::spawn(None).await
+    }
+
+    /// Relay that sends `challenge` on connect and refuses to serve REQs before AUTH
+    pub async fn with_auth(challenge: &str) -> Self {
+        Self::spawn(Some(challenge.to_string())).await
+    }
+
+    async fn spawn(auth_challenge: Option<String>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock relay");
+        let addr = listener.local_addr().expect("mock relay address");
+        let (push, _) = broadcast::channel(1024);
+        let state = Arc::new(RelayState {
+            url: format!("ws://{}", addr),
+            events: RwLock::new(Vec::new()),
+            push,
+            auth_challenge,
+        });
+
+        let app = Router::new()
+            .route("/", get(accept))
+            .with_state(state.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Self { state, server }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.state.url
+    }
+
+    /// Store an event as if another client had published it
+    pub async fn publish(&self, event: Event) {
+        self.state.store(event).await;
+    }
+
+    /// Every event stored so far, including the ones clients published
+    pub async fn events(&self) -> Vec<Event> {
+        self.state.events.read().await.clone()
+    }
+
+    /// Send a NOTICE to every connection
+    pub fn notice(&self, message: &str) {
+        let _ = self.state.push.send(Push::Notice(message.to_string()));
+    }
+
+    /// CLOSE every open subscription with `message`
+    pub fn close_subscriptions(&self, message: &str) {
+        let _ = self
+            .state
+            .push
+            .send(Push::CloseSubscriptions(message.to_string()));
+    }
+}
+
+impl Drop for MockRelay {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl RelayState {
+    /// Store and broadcast an event. Like a compliant relay, only the newest event per
+    /// addressable coordinate is kept (newer `created_at`, then lower id); older ones
+    /// are ignored.
+    async fn store(&self, event: Event) {
+        let mut events = self.events.write().await;
+        if let Some(coordinate) = event.coordinate() {
+            let newest = |e: &Event| (std::cmp::Reverse(e.created_at), e.id);
+            if events
+                .iter()
+                .any(|e| e.coordinate() == Some(coordinate) && newest(e) < newest(&event))
+            {
+                return;
+            }
+            events.retain(|e| e.coordinate() != Some(coordinate));
+        }
+        events.push(event.clone());
+        drop(events);
+        let _ = self.push.send(Push::Event(event));
+    }
+
+    /// Stored events for a REQ, newest first per filter as NIP-01 asks for `limit`
+    async fn backfill(&self, filters: &[Filter]) -> Vec<Event> {
+        let events = self.events.read().await;
+        let mut out: Vec<Event> = Vec::new();
+        for filter in filters {
+            let mut matched: Vec<&Event> = events
+                .iter()
+                .filter(|e| filter.match_event(e, MatchEventOptions::new()))
+                .collect();
+            matched.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+            for event in matched.into_iter().take(filter.limit.unwrap_or(usize::MAX)) {
+                if !out.iter().any(|e| e.id == event.id) {
+                    out.push(event.clone());
+                }
+            }
+        }
+        out
+    }
+}
+
+async fn accept(ws: WebSocketUpgrade, State(state): State<Arc<RelayState>>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| serve_connection(socket, state))
+}
+
+/// Per-connection state of the mock relay
+struct Connection {
+    subscriptions: HashMap<SubscriptionId, Vec<Filter>>,
+    authenticated: bool,
+}
+
+async fn serve_connection(mut socket: WebSocket, state: Arc<RelayState>) {
+    let mut push = state.push.subscribe();
+    let mut conn = Connection {
+        subscriptions: HashMap::new(),
+        authenticated: state.auth_challenge.is_none(),
+    };
+    if let Some(challenge) = &state.auth_challenge
+        && send(&mut socket, RelayMessage::auth(challenge.clone()))
+            .await
+            .is_err()
+    {
+        return;
+    }
+
+    loop {
+        let replies = tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    // Pings are answered by axum
+                    Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Binary(_))) => continue,
+                    _ => break,
+                };
+                match ClientMessage::from_json(text.as_str()) {
+                    Ok(msg) => handle_client_message(&state, &mut conn, msg).await,
+                    Err(e) => vec![RelayMessage::notice(format!("invalid: {}", e))],
+                }
+            }
+            pushed = push.recv() => match pushed {
+                Ok(Push::Event(event)) => conn
+                    .subscriptions
+                    .iter()
+                    .filter(|(_, filters)| {
+                        filters.iter().any(|f| f.match_event(&event, MatchEventOptions::new()))
+                    })
+                    .map(|(id, _)| RelayMessage::event(id.clone(), event.clone()))
+                    .collect(),
+                Ok(Push::Notice(message)) => vec![RelayMessage::notice(message)],
+                Ok(Push::CloseSubscriptions(message)) => conn
+                    .subscriptions
+                    .drain()
+                    .map(|(id, _)| RelayMessage::closed(id, message.clone()))
+                    .collect(),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        for reply in replies {
+            if send(&mut socket, reply).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn handle_client_message(
+    state: &RelayState,
+    conn: &mut Connection,
+    msg: ClientMessage<'_>,
+) -> Vec<RelayMessage<'static>> {
+    match msg {
+        ClientMessage::Event(event) => {
+            let event = event.into_owned();
+            if let Err(e) = event.verify() {
+                return vec![RelayMessage::ok(event.id, false, format!("invalid: {}", e))];
+            }
+            let id = event.id;
+            state.store(event).await;
+            vec![RelayMessage::ok(id, true, "")]
+        }
+        ClientMessage::Req {
+            subscription_id,
+            filters,
+        } => {
+            let subscription_id = subscription_id.into_owned();
+            if !conn.authenticated {
+                return vec![RelayMessage::closed(
+                    subscription_id,
+                    "auth-required: authenticate to read",
+                )];
+            }
+            let filters: Vec<Filter> = filters.into_iter().map(|f| f.into_owned()).collect();
+            let mut replies: Vec<RelayMessage<'static>> = state
+                .backfill(&filters)
+                .await
+                .into_iter()
+                .map(|event| RelayMessage::event(subscription_id.clone(), event))
+                .collect();
+            replies.push(RelayMessage::eose(subscription_id.clone()));
+            // REQ with a known id replaces the subscription
+            conn.subscriptions.insert(subscription_id, filters);
+            replies
+        }
+        ClientMessage::Close(subscription_id) => {
+            conn.subscriptions.remove(subscription_id.as_ref());
+            Vec::new()
+        }
+        ClientMessage::Auth(event) => {
+            let challenge_ok = state.auth_challenge.as_deref().is_some_and(|challenge| {
+                event.tags.iter().any(|tag| {
+                    let tag = tag.as_slice();
+                    tag.first().map(String::as_str) == Some("challenge")
+                        && tag.get(1).map(String::as_str) == Some(challenge)
+                })
+            });
+            let accepted =
+                event.verify().is_ok() && event.kind == Kind::Authentication && challenge_ok;
+            conn.authenticated |= accepted;
+            let message = if accepted {
+                ""
+            } else {
+                "auth-required: invalid AUTH event"
+            };
+            vec![RelayMessage::ok(event.id, accepted, message)]
+        }
+        _ => vec![RelayMessage::notice("unsupported message")],
+    }
+}
+
+async fn send(socket: &mut WebSocket, msg: RelayMessage<'_>) -> Result<(), axum::Error> {
+    socket.send(Message::Text(msg.as_json().into())).await
+}
+
+/// Sign an event of `kind` with `content`
+pub fn signed_event(keys: &Keys, kind: u16, content: &str) -> Event {
+    EventBuilder::new(Kind::Custom(kind), content)
+        .tag(unique_identifier())
+        .sign_with_keys(keys)
+        .expect("sign event")
+}
+
+/// NIP-04 encrypted bot event addressed to the platform key, as bots publish 30931-30934
+pub fn encrypted_event(bot: &Keys, platform: &PublicKey, kind: u16, payload: &Value) -> Event {
+    let content =
+        nip04::encrypt(bot.secret_key(), platform, payload.to_string()).expect("encrypt payload");
+    EventBuilder::new(Kind::Custom(kind), content)
+        .tag(Tag::public_key(*platform))
+        .tag(unique_identifier())
+        .sign_with_keys(bot)
+        .expect("sign event")
+}
+
+/// Fresh `d` tag, so relays keep every event rather than only the newest per coordinate
+fn unique_identifier() -> Tag {
+    Tag::identifier(format!("{:016x}", rand::random::<u64>()))
+}
+
+pub fn trade_signal(bot: &Keys, platform: &PublicKey, payload: &Value) -> Event {
+    encrypted_event(bot, platform, KIND_TRADE_SIGNAL, payload)
+}
+
+pub fn copytrade_intent(bot: &Keys, platform: &PublicKey, payload: &Value) -> Event {
+    encrypted_event(bot, platform, KIND_COPYTRADE_INTENT, payload)
+}
+
+pub fn execution_report(bot: &Keys, platform: &PublicKey, payload: &Value) -> Event {
+    encrypted_event(bot, platform, KIND_EXECUTION_REPORT, payload)
+}
+
+pub fn heartbeat(bot: &Keys) -> Event {
+    signed_event(bot, KIND_HEARTBEAT, "")
+}
+
+/// Plaintext agent registration; the nostr pubkey defaults to the signer
+pub fn agent_register(bot: &Keys, eth_address: &str, name: &str) -> Event {
+    let payload = serde_json::json!({
+        "bot_pubkey": bot.public_key().to_hex(),
+        "eth_address": eth_address,
+        "name": name,
+    });
+    signed_event(bot, KIND_AGENT_REGISTER, &payload.to_string())
+}
+
+/// Random `0x` address so repeated runs against one database do not collide
+pub fn random_eth_address() -> String {
+    let bytes = Keys::generate().public_key().to_bytes();
+    format!(
+        "0x{}",
+        bytes[..20]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    )
+}
+
+/// Postgres-backed subscription service from `MOLTRADE_TEST_POSTGRES_DSN`.
+/// Tests that need it are skipped when the variable is unset.
+pub async fn postgres() -> Option<Arc<SubscriptionService>> {
+    let Ok(dsn) = std::env::var("MOLTRADE_TEST_POSTGRES_DSN") else {
+        eprintln!("MOLTRADE_TEST_POSTGRES_DSN not set, skipping");
+        return None;
+    };
+    let svc = SubscriptionService::new(&dsn, 4)
+        .await
+        .expect("connect to test database");
+    Some(Arc::new(svc))
+}
+
+/// Poll `check` until it returns true or `timeout` elapses
+pub async fn wait_for<F, Fut>(timeout: Duration, mut check: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if check().await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+/// Relayer under test
+#[derive(Default)]
+pub struct RelayerOptions {
+    /// Platform key: answers AUTH and decrypts bot events
+    pub platform_keys: Option<Keys>,
+    /// Enables registration handling, decryption and fanout (needs Postgres)
+    pub subscriptions: Option<Arc<SubscriptionService>>,
+    /// Client the router publishes encrypted follower events with
+    pub publisher: Option<Arc<Client>>,
+    /// Existing store to start from, e.g. to simulate a restart
+    pub rocksdb: Option<Arc<RocksDBStore>>,
+    /// Enables semantic dedupe of trade signals with this window
+    pub semantic_window: Option<Duration>,
+}
+
+/// Relay pool, event router and WebSocket API running in-process against mock relays
+pub struct TestRelayer {
+    pub pool: Arc<RelayPool>,
+    pub dedupe: Arc<DeduplicationEngine>,
+    pub rocksdb: Arc<RocksDBStore>,
+    /// Base `ws://` URL of the API (`/ws`, `/fanout`)
+    pub api_url: String,
+    tasks: Vec<JoinHandle<()>>,
+    _db_dir: TempDir,
+}
+
+impl TestRelayer {
+    pub async fn start(relays: &[&MockRelay]) -> Self {
+        Self::start_with(relays, RelayerOptions::default()).await
+    }
+
+    pub async fn start_with(relays: &[&MockRelay], opts: RelayerOptions) -> Self {
+        let db_dir = tempfile::tempdir().expect("temp dir");
+        let rocksdb = match opts.rocksdb {
+            Some(rocksdb) => rocksdb,
+            None => Arc::new(RocksDBStore::new(db_dir.path()).expect("open RocksDB")),
+        };
+        let dedupe = Arc::new(
+            DeduplicationEngine::new(rocksdb.clone())
+                .with_latest_wins_kinds(default_latest_wins_kinds()),
+        );
+        dedupe.warm_from_db(10_000).await;
+        let kinds: Vec<u16> = (KIND_TRADE_SIGNAL..=KIND_AGENT_REGISTER).collect();
+
+        let (ingest_tx, ingest_rx) =
+            pipeline::stage("ingest", 10_000, OverflowPolicy::Block, Vec::new(), None);
+        let mut pool = RelayPool::new(
+            Duration::from_millis(200),
+            100,
+            1,
+            Some(kinds.clone()),
+            ingest_tx,
+        )
+        .with_reconnect_policy(ReconnectPolicy {
+            liveness_timeout: Duration::from_secs(30),
+            probe_timeout: Duration::from_secs(2),
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            backfill_overlap: Duration::from_secs(60),
+            max_backfill: Duration::from_secs(3600),
+        })
+        .with_store(rocksdb.clone());
+        if let Some(keys) = &opts.platform_keys {
+            pool = pool.with_auth_keys(keys.clone());
+        }
+        let pool = Arc::new(pool);
+        pool.start().await;
+
+        let (downstream_tx, downstream_rx) = pipeline::stage(
+            "downstream",
+            10_000,
+            OverflowPolicy::Block,
+            Vec::new(),
+            None,
+        );
+        let (fanout_tx, fanout_rx) = match &opts.subscriptions {
+            Some(_) => {
+                let (tx, rx) =
+                    pipeline::stage("fanout", 10_000, OverflowPolicy::Block, Vec::new(), None);
+                (Some(tx), Some(rx))
+            }
+            None => (None, None),
+        };
+
+        let mut router = EventRouter::new(
+            dedupe.clone(),
+            100,
+            Duration::from_millis(20),
+            downstream_tx,
+            Some(kinds),
+            fanout_tx,
+            opts.subscriptions,
+            opts.platform_keys,
+            opts.publisher,
+        )
+        .with_relay_pool(pool.clone());
+        if let Some(window) = opts.semantic_window {
+            router = router.with_semantic_dedupe(window);
+        }
+        let mut tasks = vec![tokio::spawn(async move {
+            let _ = router.process_stream(ingest_rx).await;
+        })];
+
+        let app =
+            websocket::create_websocket_router(Arc::new(downstream_rx), fanout_rx.map(Arc::new));
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind API");
+        let api_url = format!("ws://{}", listener.local_addr().expect("API address"));
+        tasks.push(tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        }));
+
+        for relay in relays {
+            pool.connect_and_subscribe(relay.url().to_string())
+                .await
+                .expect("subscribe to mock relay");
+        }
+
+        Self {
+            pool,
+            dedupe,
+            rocksdb,
+            api_url,
+            tasks,
+            _db_dir: db_dir,
+        }
+    }
+
+    /// Wait until every relay finished replaying stored events
+    pub async fn wait_backfilled(&self) -> bool {
+        wait_for(TIMEOUT, || async {
+            self.pool
+                .get_relay_stats()
+                .await
+                .iter()
+                .all(|(_, _, stats)| stats.backfill_completed_at.is_some())
+        })
+        .await
+    }
+
+    /// Connect to a WebSocket endpoint of the API, e.g. `/ws`
+    pub async fn connect(&self, path: &str) -> WsClient {
+        WsClient::connect(&format!("{}{}", self.api_url, path)).await
+    }
+}
+
+impl Drop for TestRelayer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// WebSocket client for the relayer API
+pub struct WsClient {
+    stream: tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+}
+
+impl WsClient {
+    pub async fn connect(url: &str) -> Self {
+        let (stream, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .expect("connect to API WebSocket");
+        Self { stream }
+    }
+
+    /// Next JSON text frame, or `None` if nothing arrives within `timeout`
+    pub async fn next_json(&mut self, timeout: Duration) -> Option<Value> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            let frame = tokio::time::timeout(remaining, self.stream.next())
+                .await
+                .ok()??
+                .ok()?;
+            if let tokio_tungstenite::tungstenite::Message::Text(text) = frame {
+                return serde_json::from_str(text.as_str()).ok();
+            }
+        }
+    }
+}
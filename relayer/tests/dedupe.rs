@@ -0,0 +1,367 @@
+mod common;
+
+use common::{KIND_HEARTBEAT, KIND_TRADE_SIGNAL, signed_event};
+use moltrade_relayer::core::dedupe_engine::{AddressableVerdict, DeduplicationEngine};
+use moltrade_relayer::core::semantic_dedupe::{SemanticDedupe, SignalKey};
+use moltrade_relayer::storage::bloom_filter::BloomFilter;
+use moltrade_relayer::storage::hot_set::HotSet;
+use moltrade_relayer::storage::memory_store::MemoryEventStore;
+use moltrade_relayer::storage::rocksdb_store::RocksDBStore;
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn hot_set_evicts_oldest_ids_first() {
+    let hot_set = HotSet::with_capacity(3);
+    for id in ["a", "b", "c"] {
+        assert_eq!(hot_set.insert(id.to_string()), 0);
+    }
+    // Re-inserting does not refresh or grow the set
+    assert_eq!(hot_set.insert("a".to_string()), 0);
+    assert_eq!(hot_set.insert("d".to_string()), 1);
+
+    assert_eq!(hot_set.len(), 3);
+    assert!(!hot_set.contains("a"));
+    assert!(hot_set.contains("b") && hot_set.contains("c") && hot_set.contains("d"));
+    assert_eq!(hot_set.evictions(), 1);
+}
+
+#[tokio::test]
+async fn hot_set_respects_configured_size() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let rocksdb = Arc::new(RocksDBStore::new(dir.path()).expect("open RocksDB"));
+    let engine = DeduplicationEngine::new_with_params(rocksdb, 4, 10_000, 100);
+
+    let bot = Keys::generate();
+    let events: Vec<_> = (0..10)
+        .map(|i| signed_event(&bot, KIND_TRADE_SIGNAL, &format!("event {i}")))
+        .collect();
+    for event in &events {
+        assert!(!engine.is_duplicate(event).await);
+    }
+
+    let stats = engine.get_stats().await;
+    assert_eq!(stats.hot_set_capacity, 4);
+    assert_eq!(stats.hot_set_size, 4);
+    assert_eq!(stats.hot_set_evictions, 6);
+
+    // The most recent deliveries are still caught, older ones by the deeper layers
+    for event in &events {
+        assert!(engine.is_duplicate(event).await, "{}", event.content);
+    }
+}
+
+#[tokio::test]
+async fn in_memory_store_backs_the_dedupe_layers() {
+    // One-entry hot set and LRU: repeats beyond the newest are caught by the store
+    let engine =
+        DeduplicationEngine::new_with_params(Arc::new(MemoryEventStore::new()), 1, 1_000, 1);
+    let bot = Keys::generate();
+    let events: Vec<_> = (0..5)
+        .map(|i| signed_event(&bot, KIND_TRADE_SIGNAL, &format!("event {i}")))
+        .collect();
+    for event in &events {
+        assert!(!engine.is_duplicate(event).await);
+    }
+    for event in &events {
+        assert!(engine.is_duplicate(event).await, "{}", event.content);
+    }
+
+    engine
+        .mark_forward_success(&events[0].id)
+        .await
+        .expect("mark forwarded");
+    assert_eq!(engine.load_unfinished(10).await.len(), 4);
+    assert_eq!(engine.get_stats().await.rocksdb_approximate_count, 5);
+}
+
+#[test]
+fn bloom_filter_rotates_instead_of_saturating() {
+    let bloom = BloomFilter::with_capacity(1_000, 0.01);
+    // Half way into a generation, so the newest 1,000 span the two live ones
+    let ids: Vec<[u8; 32]> = (0..20_500).map(|_| rand::random()).collect();
+    for id in &ids {
+        bloom.insert(id);
+    }
+
+    let stats = bloom.stats();
+    assert_eq!(stats.generations, 2);
+    assert!(stats.rotations >= 20, "rotations: {}", stats.rotations);
+    assert!(stats.fill_ratio <= 0.5);
+    assert!(stats.estimated_fpr <= 0.01, "fpr: {}", stats.estimated_fpr);
+
+    // The newest generation's worth of IDs is always remembered
+    for id in ids.iter().rev().take(1_000) {
+        assert!(bloom.contains(id));
+    }
+    let mut false_positives = 0;
+    for _ in 0..10_000 {
+        if bloom.contains(&rand::random()) {
+            false_positives += 1;
+        }
+    }
+    assert!(false_positives < 200, "false positives: {false_positives}");
+}
+
+#[tokio::test]
+async fn bloom_false_positives_from_an_old_generation_are_recorded_again() {
+    let bot = Keys::generate();
+    let events: Vec<_> = (0..4)
+        .map(|i| signed_event(&bot, KIND_TRADE_SIGNAL, &format!("event {i}")))
+        .collect();
+    // The first event collides with the full oldest generation without being stored
+    let bloom = BloomFilter::with_capacity(2, 0.01);
+    bloom.insert(events[0].id.as_bytes());
+    bloom.insert(&rand::random());
+    let engine = DeduplicationEngine::new_with_params(Arc::new(MemoryEventStore::new()), 1, 2, 1)
+        .with_bloom_filter(bloom);
+
+    for event in &events {
+        assert!(!engine.is_duplicate(event).await, "{}", event.content);
+    }
+    // That generation has rotated out, and the hot set and LRU only hold the last event
+    assert!(engine.is_duplicate(&events[0]).await);
+}
+
+#[tokio::test]
+async fn snapshot_restores_dedupe_state_after_restart() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let snapshot_path = dir.path().join("dedupe.snapshot");
+    let rocksdb = Arc::new(RocksDBStore::new(dir.path().join("rocksdb")).expect("open RocksDB"));
+    let bot = Keys::generate();
+    let events: Vec<_> = (0..20)
+        .map(|i| signed_event(&bot, KIND_TRADE_SIGNAL, &format!("event {i}")))
+        .collect();
+
+    let before = DeduplicationEngine::new_with_params(rocksdb.clone(), 8, 10_000, 100);
+    for event in &events[..10] {
+        assert!(!before.is_duplicate(event).await);
+    }
+    before
+        .save_snapshot(&snapshot_path)
+        .await
+        .expect("save snapshot");
+    // Handled after the snapshot: only RocksDB knows about these
+    for event in &events[10..15] {
+        assert!(!before.is_duplicate(event).await);
+    }
+    let expected = before.get_stats().await;
+    drop(before);
+
+    let after = DeduplicationEngine::new_with_params(rocksdb, 8, 10_000, 100);
+    assert!(
+        after
+            .load_snapshot(&snapshot_path, Duration::from_secs(60))
+            .await
+            .expect("load snapshot")
+    );
+    let stats = after.get_stats().await;
+    assert_eq!(stats.lru_cache_size, 10);
+    assert_eq!(stats.hot_set_size, 8);
+    assert_eq!(stats.bloom_filter_size, 10);
+    assert!(expected.bloom_filter_size > stats.bloom_filter_size);
+
+    for event in &events[..15] {
+        assert!(after.is_duplicate(event).await, "{}", event.content);
+    }
+    for event in &events[15..] {
+        assert!(!after.is_duplicate(event).await, "{}", event.content);
+    }
+}
+
+#[tokio::test]
+async fn corrupt_snapshots_are_rejected() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let snapshot_path = dir.path().join("dedupe.snapshot");
+    let rocksdb = Arc::new(RocksDBStore::new(dir.path().join("rocksdb")).expect("open RocksDB"));
+    let engine = DeduplicationEngine::new_with_params(rocksdb, 8, 10_000, 100);
+    assert!(
+        !engine
+            .load_snapshot(&snapshot_path, Duration::ZERO)
+            .await
+            .expect("missing snapshot")
+    );
+
+    let bot = Keys::generate();
+    engine
+        .is_duplicate(&signed_event(&bot, KIND_TRADE_SIGNAL, "x"))
+        .await;
+    engine
+        .save_snapshot(&snapshot_path)
+        .await
+        .expect("save snapshot");
+    let mut bytes = std::fs::read(&snapshot_path).expect("read snapshot");
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0xff;
+    std::fs::write(&snapshot_path, &bytes).expect("write snapshot");
+
+    let err = engine
+        .load_snapshot(&snapshot_path, Duration::ZERO)
+        .await
+        .expect_err("corrupt snapshot loaded");
+    assert!(format!("{err:#}").contains("checksum"), "{err:#}");
+}
+
+#[tokio::test]
+async fn addressable_coordinates_follow_nip01_replacement() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let rocksdb = Arc::new(RocksDBStore::new(dir.path()).expect("open RocksDB"));
+    let engine = DeduplicationEngine::new(rocksdb).with_latest_wins_kinds(vec![KIND_HEARTBEAT]);
+    let bot = Keys::generate();
+    let now = Timestamp::now();
+    let event = |kind: u16, d: &str, created_at: Timestamp| {
+        EventBuilder::new(Kind::Custom(kind), "")
+            .tag(Tag::identifier(d))
+            .custom_created_at(created_at)
+            .sign_with_keys(&bot)
+            .expect("sign event")
+    };
+
+    // Latest wins: older arrivals are superseded, other `d` tags are separate
+    let heartbeat = event(KIND_HEARTBEAT, "subspace_op", now);
+    assert_eq!(
+        engine.check_coordinate(&heartbeat),
+        AddressableVerdict::Latest
+    );
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_HEARTBEAT, "subspace_op", now - 10)),
+        AddressableVerdict::Superseded
+    );
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_HEARTBEAT, "other", now - 10)),
+        AddressableVerdict::Latest
+    );
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_HEARTBEAT, "subspace_op", now + 10)),
+        AddressableVerdict::Latest
+    );
+
+    // Every event matters: reuse of a coordinate is reported, first time flagged
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_TRADE_SIGNAL, "subspace_op", now)),
+        AddressableVerdict::Latest
+    );
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_TRADE_SIGNAL, "subspace_op", now + 1)),
+        AddressableVerdict::SharedCoordinate { first: true }
+    );
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_TRADE_SIGNAL, "subspace_op", now + 2)),
+        AddressableVerdict::SharedCoordinate { first: false }
+    );
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_TRADE_SIGNAL, "sig-1", now)),
+        AddressableVerdict::Latest
+    );
+    assert_eq!(
+        engine.check_coordinate(&event(1, "", now)),
+        AddressableVerdict::NotAddressable
+    );
+}
+
+#[tokio::test]
+async fn coordinate_tracking_is_bounded() {
+    let engine = DeduplicationEngine::new(Arc::new(MemoryEventStore::new()))
+        .with_latest_wins_kinds(vec![KIND_HEARTBEAT])
+        .with_coordinate_capacity(8);
+    let bot = Keys::generate();
+    let now = Timestamp::now();
+    let event = |kind: u16, d: String, created_at: Timestamp| {
+        EventBuilder::new(Kind::Custom(kind), "")
+            .tag(Tag::identifier(d))
+            .custom_created_at(created_at)
+            .sign_with_keys(&bot)
+            .expect("sign event")
+    };
+
+    for i in 0..50 {
+        engine.check_coordinate(&event(KIND_HEARTBEAT, format!("hb-{i}"), now));
+        for offset in 0..2 {
+            engine.check_coordinate(&event(KIND_TRADE_SIGNAL, format!("sig-{i}"), now + offset));
+        }
+    }
+    let stats = engine.get_stats().await;
+    assert_eq!(stats.coordinate_capacity, 8);
+    assert_eq!(stats.latest_coordinates, 8);
+    assert!(stats.seen_coordinates <= 8);
+    assert!(stats.shared_coordinates > 0 && stats.shared_coordinates <= 8);
+
+    // Recent coordinates are still tracked
+    assert_eq!(
+        engine.check_coordinate(&event(KIND_HEARTBEAT, "hb-49".to_string(), now - 10)),
+        AddressableVerdict::Superseded
+    );
+}
+
+#[tokio::test]
+async fn semantic_dedupe_matches_republished_signals_within_the_window() {
+    let semantic = SemanticDedupe::new(Duration::from_millis(200));
+    let key = |author: &str, sid: &str, price: f64| {
+        SignalKey::new(
+            author.to_string(),
+            Some(sid.to_string()),
+            Some("BTC".to_string()),
+            Some("buy".to_string()),
+            Some(0.1),
+            Some(price),
+            None,
+        )
+    };
+
+    assert!(!semantic.is_duplicate(&key("bot-a", "main", 50_000.0)));
+    // Only delivered signals are remembered
+    assert!(!semantic.is_duplicate(&key("bot-a", "main", 50_000.0)));
+    semantic.record(key("bot-a", "main", 50_000.0));
+    assert!(semantic.is_duplicate(&key("bot-a", "main", 50_000.0)));
+    // Any differing field makes it a different trade
+    assert!(!semantic.is_duplicate(&key("bot-a", "main", 50_001.0)));
+    assert!(!semantic.is_duplicate(&key("bot-a", "alt", 50_000.0)));
+    assert!(!semantic.is_duplicate(&key("bot-b", "main", 50_000.0)));
+    // Payloads without trade details are never matched
+    let empty = || SignalKey::new("bot-a".to_string(), None, None, None, None, None, None);
+    semantic.record(empty());
+    assert!(!semantic.is_duplicate(&empty()));
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(!semantic.is_duplicate(&key("bot-a", "main", 50_000.0)));
+    semantic.record(key("bot-b", "main", 50_000.0));
+    // Expired entries were pruned on the way
+    assert_eq!(semantic.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_deliveries_of_one_event_are_new_once() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let rocksdb = Arc::new(RocksDBStore::new(dir.path()).expect("open RocksDB"));
+    let engine = Arc::new(DeduplicationEngine::new_with_params(
+        rocksdb, 100_000, 100_000, 100_000,
+    ));
+    let bot = Keys::generate();
+    let events: Vec<_> = (0..50)
+        .map(|i| signed_event(&bot, KIND_TRADE_SIGNAL, &format!("event {i}")))
+        .collect();
+
+    // Eight "relays" deliver every event at the same time
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let engine = engine.clone();
+            let events = events.clone();
+            tokio::spawn(async move {
+                let mut new = 0;
+                for event in &events {
+                    if !engine.is_duplicate(event).await {
+                        new += 1;
+                    }
+                }
+                new
+            })
+        })
+        .collect();
+    let mut new = 0;
+    for task in tasks {
+        new += task.await.expect("delivery task");
+    }
+    assert_eq!(new, events.len());
+    assert_eq!(engine.get_stats().await.lru_cache_size, events.len());
+}
@@ -0,0 +1,221 @@
+mod common;
+
+use common::{
+    KIND_AGENT_REGISTER, KIND_TRADE_SIGNAL, MockRelay, RelayerOptions, TIMEOUT, TestRelayer,
+    signed_event,
+};
+use moltrade_relayer::config::IngressConfig;
+use moltrade_relayer::core::ingress::{IngressValidator, Rejection};
+use moltrade_relayer::storage::rocksdb_store::RocksDBStore;
+use nostr_sdk::prelude::*;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn validator() -> IngressValidator {
+    IngressValidator::new(IngressConfig {
+        max_past_secs: 600,
+        max_backfill_secs: 3600,
+        max_future_secs: 60,
+        max_content_bytes: 1024,
+        max_tags: 4,
+        register_pow_difficulty: 8,
+    })
+}
+
+/// Re-parse an event after editing its JSON, without verifying it
+fn tampered(event: &Event, edit: impl FnOnce(&mut Value)) -> Event {
+    let mut json: Value = serde_json::from_str(&event.as_json()).expect("event JSON");
+    edit(&mut json);
+    Event::from_json(json.to_string()).expect("parse tampered event")
+}
+
+/// Quiet period in which nothing more should be forwarded
+const QUIET: Duration = Duration::from_millis(300);
+
+fn at(keys: &Keys, created_at: Timestamp) -> Event {
+    at_with(keys, created_at, "signal")
+}
+
+fn at_with(keys: &Keys, created_at: Timestamp, content: &str) -> Event {
+    EventBuilder::new(Kind::Custom(KIND_TRADE_SIGNAL), content)
+        .custom_created_at(created_at)
+        .sign_with_keys(keys)
+        .expect("sign event")
+}
+
+#[test]
+fn rejects_forged_ids_and_signatures() {
+    let keys = Keys::generate();
+    let event = signed_event(&keys, KIND_TRADE_SIGNAL, "original");
+    assert_eq!(validator().validate(&event), Ok(()));
+
+    let edited = tampered(&event, |json| json["content"] = "edited".into());
+    assert_eq!(validator().validate(&edited), Err(Rejection::InvalidId));
+
+    let other = signed_event(&keys, KIND_TRADE_SIGNAL, "other");
+    let wrong_sig = tampered(&event, |json| json["sig"] = other.sig.to_string().into());
+    assert_eq!(
+        validator().validate(&wrong_sig),
+        Err(Rejection::InvalidSignature)
+    );
+}
+
+#[test]
+fn enforces_the_clock_skew_window() {
+    let keys = Keys::generate();
+    let now = Timestamp::now();
+    assert_eq!(validator().validate(&at(&keys, now - 590)), Ok(()));
+    assert_eq!(validator().validate(&at(&keys, now + 50)), Ok(()));
+    assert_eq!(
+        validator().validate(&at(&keys, now - 700)),
+        Err(Rejection::TooOld)
+    );
+    assert_eq!(
+        validator().validate(&at(&keys, now + 120)),
+        Err(Rejection::TooNew)
+    );
+    assert!(validator().is_stale(&at(&keys, now - 700)));
+
+    // Backfilled events get the longer backfill limit
+    assert_eq!(validator().validate_stored(&at(&keys, now - 3500)), Ok(()));
+    assert_eq!(
+        validator().validate_stored(&at(&keys, now - 3700)),
+        Err(Rejection::TooOld)
+    );
+    assert_eq!(
+        validator().validate_stored(&at(&keys, now + 120)),
+        Err(Rejection::TooNew)
+    );
+}
+
+#[test]
+fn caps_content_size_and_tag_count() {
+    let keys = Keys::generate();
+    let large = signed_event(&keys, KIND_TRADE_SIGNAL, &"x".repeat(1025));
+    assert_eq!(
+        validator().validate(&large),
+        Err(Rejection::ContentTooLarge)
+    );
+
+    let tags = (0..5).map(|i| Tag::hashtag(format!("t{i}")));
+    let tagged = EventBuilder::new(Kind::Custom(KIND_TRADE_SIGNAL), "")
+        .tags(tags)
+        .sign_with_keys(&keys)
+        .expect("sign event");
+    assert_eq!(validator().validate(&tagged), Err(Rejection::TooManyTags));
+}
+
+#[test]
+fn agent_registrations_need_proof_of_work() {
+    let keys = Keys::generate();
+    let cheap = loop {
+        // A random id may have 8 leading zero bits by chance
+        let event = signed_event(&keys, KIND_AGENT_REGISTER, "{}");
+        if !event.check_pow(8) {
+            break event;
+        }
+    };
+    assert_eq!(
+        validator().validate(&cheap),
+        Err(Rejection::InsufficientPow)
+    );
+
+    let mined = EventBuilder::new(Kind::Custom(KIND_AGENT_REGISTER), "{}")
+        .pow(8)
+        .sign_with_keys(&keys)
+        .expect("sign event");
+    assert_eq!(validator().validate(&mined), Ok(()));
+
+    // Other kinds need none
+    let signal = signed_event(&keys, KIND_TRADE_SIGNAL, "{}");
+    assert_eq!(validator().validate(&signal), Ok(()));
+}
+
+#[tokio::test]
+async fn invalid_events_never_reach_dedupe() {
+    let relay = MockRelay::start().await;
+    let relayer = TestRelayer::start(&[&relay]).await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let keys = Keys::generate();
+    let genuine = signed_event(&keys, KIND_TRADE_SIGNAL, "genuine");
+    let forged = tampered(&genuine, |json| json["content"] = "forged".into());
+    // Same id as the genuine event: had it been deduped, the genuine one would be dropped
+    relay.publish(forged).await;
+    relay.publish(genuine).await;
+
+    let forwarded = downstream.next_json(TIMEOUT).await.expect("genuine event");
+    assert_eq!(forwarded["content"], "genuine");
+    assert!(downstream.next_json(QUIET).await.is_none());
+}
+
+#[tokio::test]
+async fn catch_up_backfill_accepts_events_older_than_the_live_limit() {
+    let relay = MockRelay::start().await;
+    // Distinct authors: relays keep only the newest addressable event per coordinate
+    let now = Timestamp::now();
+    relay
+        .publish(at_with(&Keys::generate(), now - 1800, "missed while down"))
+        .await;
+    relay
+        .publish(at_with(
+            &Keys::generate(),
+            now - 5400,
+            "beyond max_backfill_secs",
+        ))
+        .await;
+
+    // Down for two hours: the cursor is older than the backfill horizon
+    let dir = tempfile::tempdir().expect("temp dir");
+    let rocksdb = Arc::new(RocksDBStore::new(dir.path()).expect("open RocksDB"));
+    rocksdb
+        .set_relay_cursor(relay.url(), (now - 7200).as_secs())
+        .await
+        .expect("set cursor");
+    let relayer = TestRelayer::start_with(
+        &[&relay],
+        RelayerOptions {
+            rocksdb: Some(rocksdb),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let forwarded = downstream
+        .next_json(TIMEOUT)
+        .await
+        .expect("backfilled event");
+    assert_eq!(forwarded["content"], "missed while down");
+    assert!(downstream.next_json(QUIET).await.is_none());
+
+    // The same age is still too old for a live event
+    relay
+        .publish(at_with(&Keys::generate(), now - 1800, "live but old"))
+        .await;
+    assert!(downstream.next_json(QUIET).await.is_none());
+}
+
+#[tokio::test]
+async fn history_of_a_relay_without_cursor_is_held_to_the_live_limit() {
+    let relay = MockRelay::start().await;
+    // Distinct authors: relays keep only the newest addressable event per coordinate
+    let now = Timestamp::now();
+    relay
+        .publish(at_with(&Keys::generate(), now - 1800, "old signal"))
+        .await;
+    relay
+        .publish(at_with(&Keys::generate(), now - 60, "recent signal"))
+        .await;
+
+    let relayer = TestRelayer::start(&[&relay]).await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let forwarded = downstream.next_json(TIMEOUT).await.expect("recent event");
+    assert_eq!(forwarded["content"], "recent signal");
+    assert!(downstream.next_json(QUIET).await.is_none());
+}
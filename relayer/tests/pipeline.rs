@@ -0,0 +1,569 @@
+mod common;
+
+use common::{
+    KIND_HEARTBEAT, KIND_TRADE_SIGNAL, MockRelay, RelayerOptions, TIMEOUT, TestRelayer,
+    agent_register, random_eth_address, signed_event, trade_signal, wait_for,
+};
+use moltrade_relayer::config::OverflowPolicy;
+use moltrade_relayer::core::dedupe_engine::DeduplicationEngine;
+use moltrade_relayer::core::pipeline::{self, StageError};
+use moltrade_relayer::storage::event_store::EventStore;
+use moltrade_relayer::storage::rocksdb_store::RocksDBStore;
+use nostr_sdk::prelude::*;
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn duplicates_from_several_relays_are_forwarded_once() {
+    let relay_a = MockRelay::start().await;
+    let relay_b = MockRelay::start().await;
+    let relayer = TestRelayer::start(&[&relay_a, &relay_b]).await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let bot = Keys::generate();
+    let platform = Keys::generate();
+    let signal = trade_signal(&bot, &platform.public_key(), &json!({"symbol": "BTC"}));
+    relay_a.publish(signal.clone()).await;
+    relay_b.publish(signal.clone()).await;
+
+    let forwarded = downstream
+        .next_json(TIMEOUT)
+        .await
+        .expect("forwarded event");
+    assert_eq!(forwarded["id"], signal.id.to_hex());
+    assert!(
+        downstream
+            .next_json(std::time::Duration::from_millis(500))
+            .await
+            .is_none(),
+        "duplicate forwarded"
+    );
+
+    let event_id = signal.id.to_hex();
+    assert!(
+        wait_for(TIMEOUT, || relayer.rocksdb.is_forward_success(&event_id)).await,
+        "forward success not recorded"
+    );
+
+    // Exactly one relay wins the first-seen race
+    assert!(
+        wait_for(TIMEOUT, || async {
+            let stats = relayer.pool.get_relay_stats().await;
+            let unique: u64 = stats.iter().map(|(_, _, s)| s.unique_events).sum();
+            let duplicate: u64 = stats.iter().map(|(_, _, s)| s.duplicate_events).sum();
+            unique == 1 && duplicate == 1
+        })
+        .await
+    );
+}
+
+#[tokio::test]
+async fn trade_signal_is_decrypted_and_fanned_out() {
+    let Some(subscriptions) = common::postgres().await else {
+        return;
+    };
+    let relay_a = MockRelay::start().await;
+    let relay_b = MockRelay::start().await;
+    let platform = Keys::generate();
+    let bot = Keys::generate();
+    let follower = Keys::generate();
+
+    let publisher = Client::builder().signer(platform.clone()).build();
+    publisher.add_relay(relay_a.url()).await.expect("add relay");
+    publisher.connect().await;
+
+    let relayer = TestRelayer::start_with(
+        &[&relay_a, &relay_b],
+        RelayerOptions {
+            platform_keys: Some(platform.clone()),
+            subscriptions: Some(subscriptions.clone()),
+            publisher: Some(Arc::new(publisher)),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut fanout = relayer.connect("/fanout").await;
+    assert!(relayer.wait_backfilled().await);
+
+    // Register over nostr, then subscribe the follower
+    let eth_address = random_eth_address();
+    relay_a
+        .publish(agent_register(&bot, &eth_address, "e2e-bot"))
+        .await;
+    assert!(
+        wait_for(TIMEOUT, || async {
+            matches!(
+                subscriptions.find_bot_by_eth(&eth_address).await,
+                Ok(Some(_))
+            )
+        })
+        .await,
+        "bot not registered"
+    );
+    let bot_hex = bot.public_key().to_hex();
+    let follower_hex = follower.public_key().to_hex();
+    subscriptions
+        .add_subscription(&bot_hex, &follower_hex, &follower_hex)
+        .await
+        .expect("add subscription");
+
+    let payload = json!({
+        "agent_eth_address": eth_address,
+        "role": "leader",
+        "symbol": "BTC",
+        "strategy": "e2e",
+        "side": "buy",
+        "size": 0.1,
+        "price": 50000.0,
+    });
+    let signal = trade_signal(&bot, &platform.public_key(), &payload);
+    relay_a.publish(signal.clone()).await;
+    relay_b.publish(signal.clone()).await;
+
+    let msg = fanout.next_json(TIMEOUT).await.expect("fanout message");
+    assert_eq!(msg["target_pubkey"], follower_hex);
+    assert_eq!(msg["bot_pubkey"], bot_hex);
+    assert_eq!(msg["kind"], KIND_TRADE_SIGNAL);
+    assert_eq!(msg["original_event_id"], signal.id.to_hex());
+    let plaintext: Value =
+        serde_json::from_str(msg["payload"].as_str().expect("payload")).expect("payload JSON");
+    assert_eq!(plaintext, payload);
+    assert!(
+        fanout
+            .next_json(std::time::Duration::from_millis(500))
+            .await
+            .is_none(),
+        "duplicate fanout"
+    );
+
+    // Followers also get the signal re-encrypted to them over nostr
+    let follower_pk = follower.public_key();
+    let delivered = wait_for(TIMEOUT, || async {
+        relay_a.events().await.iter().any(|e| {
+            e.pubkey == platform.public_key()
+                && e.kind == Kind::Custom(KIND_TRADE_SIGNAL)
+                && e.tags.public_keys().any(|pk| *pk == follower_pk)
+                && nip04::decrypt(follower.secret_key(), &platform.public_key(), &e.content)
+                    .ok()
+                    .and_then(|p| serde_json::from_str::<Value>(&p).ok())
+                    == Some(payload.clone())
+        })
+    })
+    .await;
+    assert!(delivered, "encrypted follower event not published");
+}
+
+#[tokio::test]
+async fn unfinished_events_are_redelivered_after_restart() {
+    let db_dir = tempfile::tempdir().expect("temp dir");
+    let rocksdb = Arc::new(RocksDBStore::new(db_dir.path()).expect("open RocksDB"));
+    let bot = Keys::generate();
+    let finished = signed_event(&bot, KIND_TRADE_SIGNAL, "finished");
+    let unfinished = signed_event(&bot, KIND_TRADE_SIGNAL, "unfinished");
+
+    // Previous run: both events received, only one fully processed before the crash
+    let previous = DeduplicationEngine::new(rocksdb.clone());
+    assert!(!previous.is_duplicate(&finished).await);
+    assert!(!previous.is_duplicate(&unfinished).await);
+    previous
+        .mark_forward_success(&finished.id)
+        .await
+        .expect("mark forwarded");
+    drop(previous);
+
+    // Relays still hold both events and replay them on subscribe
+    let relay = MockRelay::start().await;
+    relay.publish(finished.clone()).await;
+    relay.publish(unfinished.clone()).await;
+    let relayer = TestRelayer::start_with(
+        &[&relay],
+        RelayerOptions {
+            rocksdb: Some(rocksdb.clone()),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let event = downstream
+        .next_json(TIMEOUT)
+        .await
+        .expect("redelivered event");
+    assert_eq!(event["content"], "unfinished");
+    assert!(
+        downstream
+            .next_json(std::time::Duration::from_millis(500))
+            .await
+            .is_none(),
+        "finished event or relay copy forwarded"
+    );
+    let unfinished_id = unfinished.id.to_hex();
+    assert!(wait_for(TIMEOUT, || rocksdb.is_forward_success(&unfinished_id)).await);
+}
+
+#[tokio::test]
+async fn older_heartbeat_from_another_relay_is_dropped() {
+    let relay_a = MockRelay::start().await;
+    let relay_b = MockRelay::start().await;
+    let relayer = TestRelayer::start(&[&relay_a, &relay_b]).await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let bot = Keys::generate();
+    let now = Timestamp::now();
+    let heartbeat_at = |created_at: Timestamp, content: &str| {
+        EventBuilder::new(Kind::Custom(KIND_HEARTBEAT), content)
+            .tag(Tag::identifier("subspace_op"))
+            .custom_created_at(created_at)
+            .sign_with_keys(&bot)
+            .expect("sign heartbeat")
+    };
+    relay_a.publish(heartbeat_at(now, "newer")).await;
+    let newer = downstream
+        .next_json(TIMEOUT)
+        .await
+        .expect("newer heartbeat");
+    assert_eq!(newer["content"], "newer");
+
+    // A lagging relay still has an older heartbeat at the same coordinate
+    relay_b.publish(heartbeat_at(now - 30, "older")).await;
+    assert!(
+        downstream
+            .next_json(std::time::Duration::from_millis(500))
+            .await
+            .is_none(),
+        "superseded heartbeat forwarded"
+    );
+}
+
+#[tokio::test]
+async fn republished_trade_signal_is_fanned_out_once() {
+    let Some(subscriptions) = common::postgres().await else {
+        return;
+    };
+    let relay = MockRelay::start().await;
+    let platform = Keys::generate();
+    let bot = Keys::generate();
+    let follower = Keys::generate();
+    let relayer = TestRelayer::start_with(
+        &[&relay],
+        RelayerOptions {
+            platform_keys: Some(platform.clone()),
+            subscriptions: Some(subscriptions.clone()),
+            semantic_window: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut fanout = relayer.connect("/fanout").await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let eth_address = random_eth_address();
+    relay
+        .publish(agent_register(&bot, &eth_address, "republish-bot"))
+        .await;
+    assert!(
+        wait_for(TIMEOUT, || async {
+            matches!(
+                subscriptions.find_bot_by_eth(&eth_address).await,
+                Ok(Some(_))
+            )
+        })
+        .await,
+        "bot not registered"
+    );
+    let follower_hex = follower.public_key().to_hex();
+    subscriptions
+        .add_subscription(&bot.public_key().to_hex(), &follower_hex, &follower_hex)
+        .await
+        .expect("add subscription");
+
+    let payload = |price: f64| {
+        json!({
+            "agent_eth_address": eth_address,
+            "symbol": "ETH",
+            "signal": "buy",
+            "size": 1.0,
+            "price": price,
+        })
+    };
+    let original = trade_signal(&bot, &platform.public_key(), &payload(3000.0));
+    let republished = trade_signal(&bot, &platform.public_key(), &payload(3000.0));
+    assert_ne!(original.id, republished.id);
+    relay.publish(original.clone()).await;
+    relay.publish(republished.clone()).await;
+
+    let msg = fanout.next_json(TIMEOUT).await.expect("fanout message");
+    assert_eq!(msg["original_event_id"], original.id.to_hex());
+    assert!(
+        fanout
+            .next_json(std::time::Duration::from_millis(500))
+            .await
+            .is_none(),
+        "republished signal fanned out"
+    );
+
+    // The republished copy is not forwarded downstream either, and is done with
+    let mut forwarded = Vec::new();
+    while let Some(event) = downstream
+        .next_json(std::time::Duration::from_millis(500))
+        .await
+    {
+        forwarded.push(event["id"].as_str().expect("event id").to_string());
+    }
+    assert!(forwarded.contains(&original.id.to_hex()));
+    assert!(
+        !forwarded.contains(&republished.id.to_hex()),
+        "republished signal forwarded downstream"
+    );
+    let republished_id = republished.id.to_hex();
+    assert!(
+        wait_for(TIMEOUT, || relayer
+            .rocksdb
+            .is_forward_success(&republished_id))
+        .await
+    );
+
+    // A different trade from the same bot still goes through
+    let next = trade_signal(&bot, &platform.public_key(), &payload(3100.0));
+    relay.publish(next.clone()).await;
+    let msg = fanout
+        .next_json(TIMEOUT)
+        .await
+        .expect("second fanout message");
+    assert_eq!(msg["original_event_id"], next.id.to_hex());
+}
+
+#[tokio::test]
+async fn failed_follower_publish_is_retried_without_repeating_completed_deliveries() {
+    let Some(subscriptions) = common::postgres().await else {
+        return;
+    };
+    let relay = MockRelay::start().await;
+    let platform = Keys::generate();
+    let bot = Keys::generate();
+    let followers = [Keys::generate(), Keys::generate()];
+    let db_dir = tempfile::tempdir().expect("temp dir");
+    let rocksdb = Arc::new(RocksDBStore::new(db_dir.path()).expect("open RocksDB"));
+
+    let eth_address = random_eth_address();
+    let bot_hex = bot.public_key().to_hex();
+    subscriptions
+        .register_bot(&bot_hex, &bot_hex, &eth_address, "retry-bot")
+        .await
+        .expect("register bot");
+    for follower in &followers {
+        let follower_hex = follower.public_key().to_hex();
+        subscriptions
+            .add_subscription(&bot_hex, &follower_hex, &follower_hex)
+            .await
+            .expect("add subscription");
+    }
+
+    // First run: the publisher has no relays, so every nostr publish fails
+    let offline = Client::builder().signer(platform.clone()).build();
+    let relayer = TestRelayer::start_with(
+        &[&relay],
+        RelayerOptions {
+            platform_keys: Some(platform.clone()),
+            subscriptions: Some(subscriptions.clone()),
+            publisher: Some(Arc::new(offline)),
+            rocksdb: Some(rocksdb.clone()),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut fanout = relayer.connect("/fanout").await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let payload = json!({
+        "agent_eth_address": eth_address,
+        "symbol": "SOL",
+        "side": "buy",
+        "size": 2.0,
+        "price": 150.0,
+    });
+    let signal = trade_signal(&bot, &platform.public_key(), &payload);
+    relay.publish(signal.clone()).await;
+
+    for _ in &followers {
+        let msg = fanout.next_json(TIMEOUT).await.expect("fanout message");
+        assert_eq!(msg["original_event_id"], signal.id.to_hex());
+    }
+    let forwarded = downstream.next_json(TIMEOUT).await.expect("downstream");
+    assert_eq!(forwarded["id"], signal.id.to_hex());
+    assert!(
+        wait_for(TIMEOUT, || async {
+            relayer
+                .dedupe
+                .completed_deliveries(&signal.id)
+                .await
+                .is_ok_and(|done| !done.is_empty())
+        })
+        .await,
+        "delivery progress not recorded"
+    );
+    let event_id = signal.id.to_hex();
+    assert!(!rocksdb.is_forward_success(&event_id).await);
+    drop((fanout, downstream, relayer));
+
+    // Restart with a working publisher: only the failed publishes are retried
+    let publisher = Client::builder().signer(platform.clone()).build();
+    publisher.add_relay(relay.url()).await.expect("add relay");
+    // Replay starts right away, so the publisher must already be connected
+    publisher.try_connect(TIMEOUT).await;
+    let relayer = TestRelayer::start_with(
+        &[&relay],
+        RelayerOptions {
+            platform_keys: Some(platform.clone()),
+            subscriptions: Some(subscriptions.clone()),
+            publisher: Some(Arc::new(publisher)),
+            rocksdb: Some(rocksdb.clone()),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut fanout = relayer.connect("/fanout").await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    // Forward success needs every step done, including both follower publishes
+    assert!(wait_for(TIMEOUT, || rocksdb.is_forward_success(&event_id)).await);
+    assert!(
+        relay
+            .events()
+            .await
+            .iter()
+            .any(|e| e.pubkey == platform.public_key()),
+        "follower event not published on replay"
+    );
+    let quiet = std::time::Duration::from_millis(500);
+    assert!(fanout.next_json(quiet).await.is_none(), "fanout repeated");
+    // The follower events published on replay come back from the relay as new events
+    while let Some(event) = downstream.next_json(quiet).await {
+        assert_ne!(event["id"], event_id, "downstream repeated");
+    }
+}
+
+#[tokio::test]
+async fn signal_with_a_failed_delivery_is_not_suppressed_as_republished() {
+    let Some(subscriptions) = common::postgres().await else {
+        return;
+    };
+    let relay = MockRelay::start().await;
+    let platform = Keys::generate();
+    let bot = Keys::generate();
+    let follower = Keys::generate();
+
+    let eth_address = random_eth_address();
+    let bot_hex = bot.public_key().to_hex();
+    let follower_hex = follower.public_key().to_hex();
+    subscriptions
+        .register_bot(&bot_hex, &bot_hex, &eth_address, "failing-bot")
+        .await
+        .expect("register bot");
+    subscriptions
+        .add_subscription(&bot_hex, &follower_hex, &follower_hex)
+        .await
+        .expect("add subscription");
+
+    // The publisher has no relays, so the nostr publish to the follower fails
+    let offline = Client::builder().signer(platform.clone()).build();
+    let relayer = TestRelayer::start_with(
+        &[&relay],
+        RelayerOptions {
+            platform_keys: Some(platform.clone()),
+            subscriptions: Some(subscriptions.clone()),
+            publisher: Some(Arc::new(offline)),
+            semantic_window: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut fanout = relayer.connect("/fanout").await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    let payload = json!({
+        "agent_eth_address": eth_address,
+        "symbol": "BTC",
+        "side": "sell",
+        "size": 0.5,
+        "price": 60000.0,
+    });
+    let original = trade_signal(&bot, &platform.public_key(), &payload);
+    relay.publish(original.clone()).await;
+    let msg = fanout.next_json(TIMEOUT).await.expect("fanout message");
+    assert_eq!(msg["original_event_id"], original.id.to_hex());
+    let event = downstream.next_json(TIMEOUT).await.expect("downstream");
+    assert_eq!(event["id"], original.id.to_hex());
+    let original_id = original.id.to_hex();
+    assert!(!relayer.rocksdb.is_forward_success(&original_id).await);
+
+    // The original was never fully delivered, so a republished copy still goes out
+    let republished = trade_signal(&bot, &platform.public_key(), &payload);
+    relay.publish(republished.clone()).await;
+    let msg = fanout
+        .next_json(TIMEOUT)
+        .await
+        .expect("republished fanout message");
+    assert_eq!(msg["original_event_id"], republished.id.to_hex());
+    let event = downstream
+        .next_json(TIMEOUT)
+        .await
+        .expect("republished downstream");
+    assert_eq!(event["id"], republished.id.to_hex());
+}
+
+#[tokio::test]
+async fn full_stages_only_shed_shed_kinds_silently() {
+    let bot = Keys::generate();
+    let heartbeat = || signed_event(&bot, KIND_HEARTBEAT, "heartbeat");
+    let signal = |content: &str| signed_event(&bot, KIND_TRADE_SIGNAL, content);
+    let (tx, rx) = pipeline::stage(
+        "downstream",
+        2,
+        OverflowPolicy::DropOldest,
+        vec![KIND_HEARTBEAT],
+        None,
+    );
+    tx.send(heartbeat()).await.expect("queued");
+    tx.send(signal("first")).await.expect("queued");
+
+    // The queued heartbeat makes room, a new one is shed
+    tx.send(signal("second"))
+        .await
+        .expect("evicted the heartbeat");
+    tx.send(heartbeat()).await.expect("heartbeat shed");
+    // Only signals are queued: the new one is refused, none is lost
+    assert!(matches!(
+        tx.send(signal("third")).await,
+        Err(StageError::Dropped(refused)) if refused.content == "third"
+    ));
+    let mut queued: Vec<String> = rx.drain().map(|event| event.content).collect();
+    queued.sort();
+    assert_eq!(queued, ["first", "second"]);
+
+    let (tx, rx) = pipeline::stage(
+        "fanout",
+        1,
+        OverflowPolicy::DropByKind,
+        vec![KIND_HEARTBEAT],
+        None,
+    );
+    tx.send(signal("first")).await.expect("queued");
+    tx.send(heartbeat()).await.expect("heartbeat shed");
+    // Anything else waits for room
+    let consumer = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        rx.recv_async().await.expect("first")
+    });
+    tx.send(signal("second"))
+        .await
+        .expect("queued once room was made");
+    assert_eq!(consumer.await.expect("consumer").content, "first");
+}
@@ -0,0 +1,365 @@
+mod common;
+
+use common::{KIND_TRADE_SIGNAL, MockRelay, TIMEOUT, TestRelayer, signed_event, wait_for};
+use moltrade_relayer::config::{OverflowPolicy, RelayScoringConfig};
+use moltrade_relayer::core::pipeline;
+use moltrade_relayer::core::relay_pool::{ReconnectPolicy, RelayAuth, RelayPool, RelayStatus};
+use moltrade_relayer::core::relay_scoring::RelayScorer;
+use nostr_sdk::{EventBuilder, Keys, Kind, Tag, Timestamp};
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[tokio::test]
+async fn backfills_stored_events_then_streams_live_ones() {
+    let relay = MockRelay::start().await;
+    let bot = Keys::generate();
+    relay
+        .publish(signed_event(&bot, KIND_TRADE_SIGNAL, "stored"))
+        .await;
+
+    let relayer = TestRelayer::start(&[&relay]).await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await, "relay never sent EOSE");
+
+    let stored = downstream.next_json(TIMEOUT).await.expect("stored event");
+    assert_eq!(stored["content"], "stored");
+
+    relay
+        .publish(signed_event(&bot, KIND_TRADE_SIGNAL, "live"))
+        .await;
+    let live = downstream.next_json(TIMEOUT).await.expect("live event");
+    assert_eq!(live["content"], "live");
+
+    let stats = relayer.pool.get_relay_stats().await;
+    assert_eq!(stats.len(), 1);
+    let (_, status, snapshot) = &stats[0];
+    assert_eq!(*status, RelayStatus::Connected);
+    assert_eq!(snapshot.events_received, 2);
+    assert_eq!(snapshot.unique_events, 2);
+}
+
+#[tokio::test]
+async fn notices_are_reported_in_relay_stats() {
+    let relay = MockRelay::start().await;
+    let relayer = TestRelayer::start(&[&relay]).await;
+    assert!(relayer.wait_backfilled().await);
+
+    relay.notice("rate limit approaching");
+    let reported = wait_for(TIMEOUT, || async {
+        relayer.pool.get_relay_stats().await[0]
+            .2
+            .last_notice
+            .as_deref()
+            == Some("rate limit approaching")
+    })
+    .await;
+    assert!(reported, "NOTICE not surfaced");
+}
+
+#[tokio::test]
+async fn resubscribes_after_relay_closes_the_subscription() {
+    let relay = MockRelay::start().await;
+    let bot = Keys::generate();
+    let relayer = TestRelayer::start(&[&relay]).await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    relay.close_subscriptions("error: restarting");
+    // Wait until the CLOSED has been handled
+    assert!(
+        wait_for(TIMEOUT, || async {
+            relayer.pool.get_relay_stats().await[0]
+                .2
+                .recent_messages
+                .iter()
+                .any(|m| m.kind == "closed")
+        })
+        .await
+    );
+
+    relay
+        .publish(signed_event(&bot, KIND_TRADE_SIGNAL, "after close"))
+        .await;
+    let event = downstream
+        .next_json(TIMEOUT)
+        .await
+        .expect("event after resubscribe");
+    assert_eq!(event["content"], "after close");
+}
+
+#[tokio::test]
+async fn answers_auth_challenges_with_the_platform_key() {
+    let relay = MockRelay::with_auth("challenge-123").await;
+    let bot = Keys::generate();
+    relay
+        .publish(signed_event(&bot, KIND_TRADE_SIGNAL, "paid relay"))
+        .await;
+
+    let relayer = TestRelayer::start_with(
+        &[&relay],
+        common::RelayerOptions {
+            platform_keys: Some(Keys::generate()),
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut downstream = relayer.connect("/ws").await;
+
+    let event = downstream
+        .next_json(TIMEOUT)
+        .await
+        .expect("event after AUTH");
+    assert_eq!(event["content"], "paid relay");
+    let stats = relayer.pool.get_relay_stats().await;
+    assert_eq!(stats[0].2.auth, RelayAuth::Authenticated);
+}
+
+#[tokio::test]
+async fn auth_without_a_key_is_reported() {
+    let relay = MockRelay::with_auth("challenge-456").await;
+    let relayer = TestRelayer::start(&[&relay]).await;
+
+    let reported = wait_for(TIMEOUT, || async {
+        relayer.pool.get_relay_stats().await[0].2.auth == RelayAuth::NoKey
+    })
+    .await;
+    assert!(reported, "missing key not reported");
+    assert!(
+        relayer.pool.get_relay_stats().await[0]
+            .2
+            .backfill_completed_at
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn removed_relays_stop_delivering() {
+    let relay = MockRelay::start().await;
+    let bot = Keys::generate();
+    let relayer = TestRelayer::start(&[&relay]).await;
+    let mut downstream = relayer.connect("/ws").await;
+    assert!(relayer.wait_backfilled().await);
+
+    relayer
+        .pool
+        .remove_relay(relay.url())
+        .await
+        .expect("remove relay");
+    assert_eq!(relayer.pool.active_connections(), 0);
+
+    relay
+        .publish(signed_event(&bot, KIND_TRADE_SIGNAL, "after removal"))
+        .await;
+    assert!(
+        downstream
+            .next_json(Duration::from_millis(500))
+            .await
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn a_full_ingest_stage_does_not_stall_the_shard_listener() {
+    let relay = MockRelay::start().await;
+    let bot = Keys::generate();
+    for i in 0..3 {
+        relay
+            .publish(signed_event(
+                &bot,
+                KIND_TRADE_SIGNAL,
+                &format!("stored {i}"),
+            ))
+            .await;
+    }
+
+    // Nothing drains the one-slot stage until the notice has been handled
+    let (ingest_tx, ingest_rx) =
+        pipeline::stage("ingest", 1, OverflowPolicy::Block, Vec::new(), None);
+    let pool = RelayPool::new(
+        Duration::from_secs(30),
+        10,
+        1,
+        Some(vec![KIND_TRADE_SIGNAL]),
+        ingest_tx,
+    );
+    pool.start().await;
+    pool.add_relay(relay.url().to_string(), "test")
+        .await
+        .expect("add relay");
+
+    let stats = || async { pool.get_relay_stats().await[0].2.clone() };
+    assert!(wait_for(TIMEOUT, || async { stats().await.events_received == 3 }).await);
+    relay.notice("still listening");
+    let reported = wait_for(TIMEOUT, || async {
+        stats().await.last_notice.as_deref() == Some("still listening")
+    })
+    .await;
+    assert!(reported, "listener stalled behind the full stage");
+
+    // The shard buffer held the events the stage had no room for
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        let relay_event = tokio::time::timeout(TIMEOUT, ingest_rx.recv_async())
+            .await
+            .expect("event forwarded")
+            .expect("stage open");
+        received.push(relay_event.event.content);
+    }
+    received.sort();
+    assert_eq!(received, ["stored 0", "stored 1", "stored 2"]);
+}
+
+#[tokio::test]
+async fn events_dropped_before_the_ingest_stage_are_fetched_again() {
+    let relay = MockRelay::start().await;
+    let bot = Keys::generate();
+    let now = Timestamp::now();
+    for i in 0..4 {
+        let event = EventBuilder::new(Kind::Custom(KIND_TRADE_SIGNAL), format!("stored {i}"))
+            .tag(Tag::identifier(format!("signal-{i}")))
+            .custom_created_at(now - 10 * (i + 1))
+            .sign_with_keys(&bot)
+            .expect("sign event");
+        relay.publish(event).await;
+    }
+
+    // Stage and shard buffer hold one event each and nothing drains them at first,
+    // so the listener gives up on the oldest. Without overlap, only a cursor moved
+    // back before it fetches it again.
+    let (ingest_tx, ingest_rx) =
+        pipeline::stage("ingest", 1, OverflowPolicy::Block, Vec::new(), None);
+    let pool = RelayPool::new(
+        Duration::from_millis(200),
+        10,
+        1,
+        Some(vec![KIND_TRADE_SIGNAL]),
+        ingest_tx,
+    )
+    .with_shard_buffer(1)
+    .with_reconnect_policy(ReconnectPolicy {
+        base_delay: Duration::from_millis(100),
+        backfill_overlap: Duration::ZERO,
+        ..Default::default()
+    });
+    pool.start().await;
+    pool.add_relay(relay.url().to_string(), "test")
+        .await
+        .expect("add relay");
+    assert!(
+        wait_for(Duration::from_secs(10), || async {
+            pool.get_relay_stats().await[0].2.events_received >= 4
+        })
+        .await
+    );
+    // Past the listener's wait for buffer space
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+    let mut received = HashSet::new();
+    while received.len() < 4 {
+        let relay_event = tokio::time::timeout(TIMEOUT, ingest_rx.recv_async())
+            .await
+            .expect("dropped event fetched again")
+            .expect("stage open");
+        received.insert(relay_event.event.content);
+    }
+}
+
+#[tokio::test]
+async fn scorer_matches_configured_urls_with_a_trailing_slash() {
+    let busy = MockRelay::start().await;
+    let protected = MockRelay::start().await;
+    let standby = MockRelay::start().await;
+    let relayer = TestRelayer::start(&[&busy, &protected, &standby]).await;
+    assert!(relayer.wait_backfilled().await);
+
+    // Relays from the config file, written with a trailing slash
+    let with_slash = |relay: &MockRelay| format!("{}/", relay.url());
+    let cfg = RelayScoringConfig {
+        interval_secs: 1,
+        min_score: 0.5,
+        min_uniqueness: 0.05,
+        prune_after_rounds: 1,
+        min_age_secs: 0,
+        prune_cooldown_secs: 3600,
+        min_relays: 0,
+        prune_bootstrap: false,
+        standby_relays: vec![with_slash(&standby)],
+        promote_per_round: 1,
+    };
+    let mut scorer = RelayScorer::new(relayer.pool.clone(), cfg, vec![with_slash(&protected)]);
+    scorer.tick().await;
+
+    // Only the busy relay contributes events in the next round
+    let bot = Keys::generate();
+    busy.publish(signed_event(&bot, KIND_TRADE_SIGNAL, "unique"))
+        .await;
+    assert!(
+        wait_for(TIMEOUT, || async {
+            relayer
+                .pool
+                .get_relay_stats()
+                .await
+                .iter()
+                .any(|(_, _, stats)| stats.unique_events == 1)
+        })
+        .await
+    );
+    scorer.tick().await;
+
+    assert!(relayer.pool.contains_relay(busy.url()));
+    assert!(
+        relayer.pool.contains_relay(protected.url()),
+        "protected relay pruned"
+    );
+    // Pruned, and not promoted right back from the standby list
+    assert!(
+        !relayer.pool.contains_relay(standby.url()),
+        "pruned relay promoted again"
+    );
+}
+
+#[tokio::test]
+async fn default_scoring_prunes_reliable_relays_that_add_nothing() {
+    let busy = MockRelay::start().await;
+    let redundant = MockRelay::start().await;
+    let relayer = TestRelayer::start(&[&busy, &redundant]).await;
+    assert!(relayer.wait_backfilled().await);
+
+    // Default thresholds; only the grace period and pool floor are lifted
+    let cfg = RelayScoringConfig {
+        min_age_secs: 0,
+        min_relays: 0,
+        ..Default::default()
+    };
+    let rounds = cfg.prune_after_rounds;
+    let mut scorer = RelayScorer::new(relayer.pool.clone(), cfg, Vec::new());
+    scorer.tick().await;
+
+    // The redundant relay stays connected and healthy but never delivers first
+    let bot = Keys::generate();
+    busy.publish(signed_event(&bot, KIND_TRADE_SIGNAL, "unique"))
+        .await;
+    assert!(
+        wait_for(TIMEOUT, || async {
+            relayer
+                .pool
+                .get_relay_stats()
+                .await
+                .iter()
+                .any(|(_, _, stats)| stats.unique_events == 1)
+        })
+        .await
+    );
+    for _ in 0..rounds {
+        scorer.tick().await;
+    }
+
+    assert!(relayer.pool.contains_relay(busy.url()));
+    assert!(
+        !relayer.pool.contains_relay(redundant.url()),
+        "redundant relay kept"
+    );
+    // Discovery does not add it straight back
+    assert!(relayer.pool.is_cooling_down(redundant.url()));
+    assert!(!relayer.pool.is_cooling_down(busy.url()));
+}
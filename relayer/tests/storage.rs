@@ -0,0 +1,457 @@
+mod common;
+
+use common::{KIND_HEARTBEAT, KIND_TRADE_SIGNAL, signed_event};
+use moltrade_relayer::core::archive::{self, ImportStats};
+use moltrade_relayer::core::dedupe_engine::DeduplicationEngine;
+use moltrade_relayer::core::retention::RetentionPruner;
+use moltrade_relayer::storage::event_index::{EventCursor, EventQuery};
+use moltrade_relayer::storage::event_store::EventStore;
+use moltrade_relayer::storage::memory_store::MemoryEventStore;
+use moltrade_relayer::storage::rocksdb_store::{RelayRecord, RocksDBStore};
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn legacy_single_family_databases_are_migrated() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let bot = Keys::generate();
+    let forwarded = signed_event(&bot, KIND_TRADE_SIGNAL, "forwarded");
+    let pending = signed_event(&bot, KIND_TRADE_SIGNAL, "pending");
+    let relay = RelayRecord {
+        url: "wss://relay.example".to_string(),
+        added_by: "config".to_string(),
+        added_at: 1_700_000_000,
+    };
+
+    // Layout before column families: prefixed keys in the default family
+    {
+        let db = rocksdb::DB::open_default(dir.path()).expect("open legacy RocksDB");
+        for event in [&forwarded, &pending] {
+            let key = format!("evt:{}", event.id.to_hex());
+            db.put(key, serde_json::to_vec(event).expect("serialize"))
+                .expect("put event");
+        }
+        let id = forwarded.id.to_hex();
+        db.put(format!("fwd:{id}"), b"1").expect("put forwarded");
+        db.put(format!("succ:{:016x}:{id}", 1_700_000_000_000u64), [])
+            .expect("put success index");
+        db.put(format!("pnd:{}", pending.id.to_hex()), [])
+            .expect("put pending");
+        db.put(format!("cur:{}", relay.url), 42u64.to_be_bytes())
+            .expect("put cursor");
+        db.put(
+            format!("rly:{}", relay.url),
+            serde_json::to_vec(&relay).expect("serialize"),
+        )
+        .expect("put relay");
+    }
+
+    for _ in 0..2 {
+        // Reopening an already migrated database is a no-op
+        let store = RocksDBStore::new(dir.path()).expect("open RocksDB");
+        assert!(store.exists(&forwarded.id.to_hex()).await);
+        assert_eq!(
+            store
+                .get_event(&pending.id.to_hex())
+                .await
+                .expect("get event")
+                .map(|e| e.content),
+            Some("pending".to_string())
+        );
+        assert!(store.is_forward_success(&forwarded.id.to_hex()).await);
+        assert_eq!(
+            store.load_recent_success_ids(10).await,
+            vec![forwarded.id.to_hex()]
+        );
+        let replay = store.load_pending_events(10).await;
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].id, pending.id);
+        assert_eq!(store.get_relay_cursor(&relay.url).await, Some(42));
+        let relays = store.load_relay_records().await;
+        assert_eq!(relays.len(), 1);
+        assert_eq!(relays[0].added_by, "config");
+        assert_eq!(store.approximate_count().await, 2);
+        let by_author = store
+            .query_events(EventQuery {
+                authors: vec![bot.public_key().to_hex()],
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .expect("query");
+        assert_eq!(by_author.len(), 2);
+    }
+}
+
+#[tokio::test]
+async fn counts_come_from_the_events_family_only() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let store = RocksDBStore::new(dir.path()).expect("open RocksDB");
+    let bot = Keys::generate();
+    for i in 0..25 {
+        let event = signed_event(&bot, KIND_TRADE_SIGNAL, &format!("event {i}"));
+        store.store_event(&event).await.expect("store event");
+        store
+            .mark_forward_success(&event.id.to_hex())
+            .await
+            .expect("mark forwarded");
+    }
+    // Forward status and success index entries are not counted as events
+    assert_eq!(store.approximate_count().await, 25);
+    assert!(store.load_pending_events(100).await.is_empty());
+    assert_eq!(store.load_recent_success_ids(10).await.len(), 10);
+}
+
+#[tokio::test]
+async fn expired_events_are_pruned_per_kind() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let store = RocksDBStore::new(dir.path())
+        .and_then(|s| s.with_retention(HashMap::from([(KIND_HEARTBEAT, 60)])))
+        .expect("open RocksDB");
+    let bot = Keys::generate();
+    let heartbeat = signed_event(&bot, KIND_HEARTBEAT, "");
+    let signal = signed_event(&bot, KIND_TRADE_SIGNAL, "signal");
+    for event in [&heartbeat, &signal] {
+        store.store_event(event).await.expect("store event");
+        store
+            .mark_forward_success(&event.id.to_hex())
+            .await
+            .expect("mark forwarded");
+    }
+    let created_at = heartbeat.created_at.as_secs();
+
+    // Still within the retention window
+    let stats = store
+        .prune_expired(created_at + 59, 100)
+        .await
+        .expect("prune");
+    assert_eq!(stats.events, 0);
+    assert!(store.exists(&heartbeat.id.to_hex()).await);
+
+    let stats = store
+        .prune_expired(created_at + 60, 100)
+        .await
+        .expect("prune");
+    assert_eq!(stats.events, 1);
+    assert!(stats.bytes > heartbeat.content.len() as u64);
+    assert!(!store.exists(&heartbeat.id.to_hex()).await);
+    assert!(!store.is_forward_success(&heartbeat.id.to_hex()).await);
+    assert_eq!(
+        store.load_recent_success_ids(10).await,
+        vec![signal.id.to_hex()]
+    );
+
+    // Trade signals are kept forever
+    let stats = store.prune_expired(u64::MAX, 100).await.expect("prune");
+    assert_eq!(stats.events, 0);
+    assert!(store.exists(&signal.id.to_hex()).await);
+    assert!(store.is_forward_success(&signal.id.to_hex()).await);
+}
+
+#[tokio::test]
+async fn retention_applies_to_events_stored_before_it_was_configured() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let bot = Keys::generate();
+    let heartbeats: Vec<_> = (0..3)
+        .map(|i| signed_event(&bot, KIND_HEARTBEAT, &format!("beat {i}")))
+        .collect();
+    {
+        let store = RocksDBStore::new(dir.path()).expect("open RocksDB");
+        for event in &heartbeats {
+            store.store_event(event).await.expect("store event");
+        }
+    }
+
+    let store = Arc::new(
+        RocksDBStore::new(dir.path())
+            .and_then(|s| s.with_retention(HashMap::from([(KIND_HEARTBEAT, 3600)])))
+            .expect("reopen RocksDB"),
+    );
+    for event in &heartbeats[..2] {
+        store
+            .mark_forward_success(&event.id.to_hex())
+            .await
+            .expect("mark forwarded");
+    }
+    let pruner = RetentionPruner::new(store.clone(), Duration::from_secs(300));
+    let now = heartbeats[0].created_at.as_secs() + 3600;
+    let stats = pruner.prune(now).await.expect("prune");
+    assert_eq!(stats.events, 2);
+    // An event not delivered yet is kept and still replayed as unfinished
+    let pending = store.load_pending_events(10).await;
+    assert_eq!(pending, vec![heartbeats[2].clone()]);
+
+    store
+        .mark_forward_success(&heartbeats[2].id.to_hex())
+        .await
+        .expect("mark forwarded");
+    assert_eq!(pruner.prune(now).await.expect("prune").events, 1);
+    assert!(store.load_pending_events(10).await.is_empty());
+    assert_eq!(pruner.prune(now).await.expect("prune").events, 0);
+}
+
+fn tagged_event(keys: &Keys, kind: u16, created_at: u64, symbol: &str) -> Event {
+    EventBuilder::new(Kind::Custom(kind), "")
+        .tag(Tag::identifier(format!("{:016x}", rand::random::<u64>())))
+        .tag(Tag::custom(TagKind::custom("symbol"), [symbol]))
+        .custom_created_at(Timestamp::from(created_at))
+        .sign_with_keys(keys)
+        .expect("sign event")
+}
+
+#[tokio::test]
+async fn archived_events_are_queried_through_secondary_indexes() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let store = RocksDBStore::new(dir.path()).expect("open RocksDB");
+    let (leader, other) = (Keys::generate(), Keys::generate());
+    let base = 1_700_000_000;
+    let mut leader_signals = Vec::new();
+    for i in 0..5 {
+        let symbol = if i % 2 == 0 { "BTC" } else { "ETH" };
+        let event = tagged_event(&leader, KIND_TRADE_SIGNAL, base + i, symbol);
+        store.store_event(&event).await.expect("store event");
+        leader_signals.push(event);
+    }
+    for event in [
+        tagged_event(&leader, KIND_HEARTBEAT, base + 10, "BTC"),
+        tagged_event(&other, KIND_TRADE_SIGNAL, base + 2, "BTC"),
+    ] {
+        store.store_event(&event).await.expect("store event");
+    }
+    let ids = |events: &[Event]| events.iter().map(|e| e.id).collect::<Vec<_>>();
+
+    // Author and kind, newest first, paged with a cursor
+    let mut query = EventQuery {
+        authors: vec![leader.public_key().to_hex()],
+        kinds: vec![KIND_TRADE_SIGNAL],
+        limit: 3,
+        ..Default::default()
+    };
+    let first = store.query_events(query.clone()).await.expect("query");
+    let expected: Vec<_> = leader_signals.iter().rev().map(|e| e.id).collect();
+    assert_eq!(ids(&first), expected[..3]);
+    query.cursor = first.last().map(EventCursor::of);
+    let second = store.query_events(query).await.expect("query");
+    assert_eq!(ids(&second), expected[3..]);
+
+    // Tag index, narrowed by kind and time range
+    let btc = store
+        .query_events(EventQuery {
+            kinds: vec![KIND_TRADE_SIGNAL],
+            since: Some(base + 1),
+            until: Some(base + 4),
+            tags: vec![("symbol".to_string(), vec!["BTC".to_string()])],
+            limit: 10,
+            ..Default::default()
+        })
+        .await
+        .expect("query");
+    assert_eq!(btc.len(), 3);
+    assert!(btc.windows(2).all(|w| w[0].created_at >= w[1].created_at));
+    assert!(btc.iter().any(|e| e.pubkey == other.public_key()));
+
+    // Deleted events leave the indexes
+    store
+        .delete_event(&leader_signals[4].id.to_hex())
+        .await
+        .expect("delete event");
+    let by_kind = store
+        .query_events(EventQuery {
+            kinds: vec![KIND_TRADE_SIGNAL],
+            limit: 10,
+            ..Default::default()
+        })
+        .await
+        .expect("query");
+    assert_eq!(by_kind.len(), 5);
+    assert!(!EventQuery::default().is_indexed());
+}
+
+#[tokio::test]
+async fn checkpoints_are_verified_and_restored() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let live = dir.path().join("rocksdb");
+    let backups = dir.path().join("checkpoints");
+    let store = RocksDBStore::new(&live)
+        .expect("open RocksDB")
+        .with_checkpoints(&backups, 2);
+    let bot = Keys::generate();
+    let kept = signed_event(&bot, KIND_TRADE_SIGNAL, "kept");
+    store.store_event(&kept).await.expect("store event");
+
+    let mut checkpoints = Vec::new();
+    for _ in 0..3 {
+        checkpoints.push(store.create_checkpoint().await.expect("checkpoint"));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    // Only the newest two are kept
+    assert!(!checkpoints[0].exists());
+    assert_eq!(std::fs::read_dir(&backups).expect("list").count(), 2);
+    let report = RocksDBStore::verify_checkpoint(&checkpoints[2])
+        .await
+        .expect("verify");
+    assert_eq!(report.events, 1);
+
+    // Written after the checkpoint, so gone once it is restored
+    let lost = signed_event(&bot, KIND_TRADE_SIGNAL, "lost");
+    store.store_event(&lost).await.expect("store event");
+    // Refused while the database is open
+    assert!(
+        RocksDBStore::restore_checkpoint(&checkpoints[2], &live)
+            .await
+            .is_err()
+    );
+    drop(store);
+
+    RocksDBStore::restore_checkpoint(&checkpoints[2], &live)
+        .await
+        .expect("restore");
+    let restored = RocksDBStore::new(&live).expect("open restored RocksDB");
+    assert!(restored.exists(&kept.id.to_hex()).await);
+    assert!(!restored.exists(&lost.id.to_hex()).await);
+    drop(restored);
+
+    // A damaged checkpoint fails the integrity check
+    let sst = std::fs::read_dir(&checkpoints[1])
+        .expect("list")
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .find(|p| p.extension().is_some_and(|ext| ext == "sst"))
+        .expect("checkpoint has table files");
+    let mut data = std::fs::read(&sst).expect("read sst");
+    for byte in &mut data[..64] {
+        *byte ^= 0xff;
+    }
+    std::fs::write(&sst, data).expect("corrupt sst");
+    assert!(
+        RocksDBStore::verify_checkpoint(&checkpoints[1])
+            .await
+            .is_err()
+    );
+    assert!(
+        RocksDBStore::restore_checkpoint(&checkpoints[1], &live)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn memory_and_rocksdb_stores_behave_alike() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let backends: [Arc<dyn EventStore>; 2] = [
+        Arc::new(RocksDBStore::new(dir.path()).expect("open RocksDB")),
+        Arc::new(MemoryEventStore::new()),
+    ];
+    let bot = Keys::generate();
+    let events: Vec<_> = (0..4)
+        .map(|i| tagged_event(&bot, KIND_TRADE_SIGNAL, 1_700_000_000 + i, "BTC"))
+        .collect();
+
+    for store in backends {
+        for event in &events {
+            store.store_event(event).await.expect("store event");
+        }
+        let id = |i: usize| events[i].id.to_hex();
+        assert!(store.exists(&id(0)).await);
+        assert_eq!(
+            store.get_event(&id(1)).await.expect("get event"),
+            Some(events[1].clone())
+        );
+
+        for i in [2, 0] {
+            store
+                .mark_forward_success(&id(i))
+                .await
+                .expect("mark forwarded");
+        }
+        assert!(store.is_forward_success(&id(2)).await);
+        assert!(!store.is_forward_success(&id(1)).await);
+        assert_eq!(store.load_recent_success_ids(1).await, vec![id(0)]);
+        // Re-marking moves the event to the front instead of listing it twice
+        store
+            .mark_forward_success(&id(2))
+            .await
+            .expect("mark forwarded");
+        assert_eq!(store.load_recent_success_ids(3).await, vec![id(2), id(0)]);
+        let mut pending: Vec<_> = store
+            .load_pending_events(10)
+            .await
+            .iter()
+            .map(|e| e.id.to_hex())
+            .collect();
+        pending.sort();
+        let mut expected = vec![id(1), id(3)];
+        expected.sort();
+        assert_eq!(pending, expected);
+
+        // Deleting takes the forwarding status and pending entry along
+        store.delete_event(&id(3)).await.expect("delete event");
+        store.delete_event(&id(2)).await.expect("delete event");
+        assert!(!store.exists(&id(3)).await);
+        assert!(!store.is_forward_success(&id(2)).await);
+        assert_eq!(store.load_recent_success_ids(3).await, vec![id(0)]);
+        let pending: Vec<_> = store
+            .load_pending_events(10)
+            .await
+            .iter()
+            .map(|e| e.id.to_hex())
+            .collect();
+        assert_eq!(pending, vec![id(1)]);
+        assert_eq!(store.approximate_count().await, 2);
+    }
+}
+
+#[tokio::test]
+async fn events_are_exported_and_imported_as_jsonl() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let source = Arc::new(RocksDBStore::new(dir.path()).expect("open RocksDB"));
+    let bot = Keys::generate();
+    let signals: Vec<_> = (0..3)
+        .map(|i| tagged_event(&bot, KIND_TRADE_SIGNAL, 1_700_000_000 + i, "BTC"))
+        .collect();
+    let heartbeat = signed_event(&bot, KIND_HEARTBEAT, "alive");
+    for event in signals.iter().chain([&heartbeat]) {
+        source.store_event(event).await.expect("store event");
+    }
+
+    let filter = EventQuery {
+        kinds: vec![KIND_TRADE_SIGNAL],
+        since: Some(1_700_000_001),
+        ..Default::default()
+    };
+    let mut exported = Vec::new();
+    let written = archive::export_jsonl(source, filter, &mut exported)
+        .await
+        .expect("export");
+    assert_eq!(written, 2);
+
+    // A forged signature and a malformed line are skipped
+    let mut forged = serde_json::to_value(&signals[0]).expect("serialize");
+    forged["content"] = "forged".into();
+    exported.extend_from_slice(format!("{forged}\nnot json\n\n").as_bytes());
+
+    let target = Arc::new(MemoryEventStore::new());
+    let engine = DeduplicationEngine::new(target.clone());
+    let stats = archive::import_jsonl(&engine, exported.as_slice())
+        .await
+        .expect("import");
+    assert_eq!(
+        stats,
+        ImportStats {
+            imported: 2,
+            duplicates: 0,
+            invalid: 2,
+        }
+    );
+    assert!(target.exists(&signals[2].id.to_hex()).await);
+    assert!(!target.exists(&signals[0].id.to_hex()).await);
+    // Imported events are archived, not queued for forwarding, and deduped live
+    assert!(target.load_pending_events(10).await.is_empty());
+    assert!(engine.is_duplicate(&signals[1]).await);
+
+    let again = archive::import_jsonl(&engine, exported.as_slice())
+        .await
+        .expect("import");
+    assert_eq!((again.imported, again.duplicates), (0, 2));
+}